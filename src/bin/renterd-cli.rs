@@ -0,0 +1,1248 @@
+use bigdecimal::BigDecimal;
+use clap::{Parser, Subcommand, ValueEnum};
+use either::Either;
+use futures::io::AllowStdIo;
+use futures::TryStreamExt;
+use renterd_client::bus::host::ModifyAction;
+use renterd_client::bus::object::{
+    ConflictPolicy, ListOptions, Metadata, PrefixOptions, PrefixReport, RenameMode, SortBy, SortDir,
+};
+use renterd_client::bus::webhook::{EventType, Module, Webhook};
+use renterd_client::fs::DEFAULT_BUCKET;
+use renterd_client::worker::object::{DownloadFileOptions, UploadFileOptions};
+use renterd_client::{Client, ClientBuilder, FileContractId, Hash, PublicKey, LIST_BATCH_SIZE};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::error::Error;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+#[derive(Parser)]
+#[command(
+    name = "renterd-cli",
+    version,
+    about = "Command-line client for the renterd API"
+)]
+struct Cli {
+    /// renterd API endpoint, e.g. http://localhost:9980/api/
+    #[arg(long, global = true, env = "RENTERD_API_URL")]
+    url: Option<String>,
+    /// renterd API password
+    #[arg(
+        long,
+        global = true,
+        env = "RENTERD_API_PASSWORD",
+        hide_env_values = true
+    )]
+    password: Option<String>,
+    /// Bucket used by object commands
+    #[arg(long, global = true, env = "RENTERD_BUCKET")]
+    bucket: Option<String>,
+    /// Configuration file, defaults to `$XDG_CONFIG_HOME/renterd-cli/config.toml`
+    #[arg(long, global = true, env = "RENTERD_CLI_CONFIG")]
+    config: Option<PathBuf>,
+    /// Accept invalid TLS certificates
+    #[arg(long, global = true)]
+    insecure: bool,
+    /// Print machine readable JSON
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List, transfer and manage objects
+    #[command(subcommand)]
+    Objects(ObjectsCommand),
+    /// Manage buckets
+    #[command(subcommand)]
+    Buckets(BucketsCommand),
+    /// Inspect hosts and manage the allow- and blocklist
+    #[command(subcommand)]
+    Hosts(HostsCommand),
+    /// Inspect contracts
+    #[command(subcommand)]
+    Contracts(ContractsCommand),
+    /// Read, update and compare bus settings
+    #[command(subcommand)]
+    Settings(SettingsCommand),
+    /// List and dismiss alerts
+    #[command(subcommand)]
+    Alerts(AlertsCommand),
+    /// Manage webhooks
+    #[command(subcommand)]
+    Webhooks(WebhooksCommand),
+    /// Show the wallet balance or its outputs
+    Wallet {
+        #[arg(long)]
+        outputs: bool,
+    },
+    /// Show the consensus state
+    Consensus,
+    /// Manage the autopilot
+    #[command(subcommand)]
+    Autopilot(AutopilotCommand),
+}
+
+#[derive(Subcommand)]
+enum ObjectsCommand {
+    /// List a directory, or every object below a prefix with `--recursive`
+    Ls {
+        #[arg(default_value = "/")]
+        path: String,
+        #[arg(short, long)]
+        recursive: bool,
+        #[arg(long)]
+        sort: Option<SortField>,
+        #[arg(long, requires = "sort")]
+        desc: bool,
+        /// Only list entries whose name contains this string
+        #[arg(long)]
+        substring: Option<String>,
+    },
+    /// Write an object to stdout
+    Cat { path: String },
+    /// Upload a local file
+    Put {
+        file: PathBuf,
+        path: String,
+        #[arg(long)]
+        content_type: Option<String>,
+    },
+    /// Download an object to a local file
+    Get {
+        path: String,
+        file: Option<PathBuf>,
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Delete an object, or a whole directory with `--recursive`
+    Rm {
+        path: String,
+        #[arg(short, long)]
+        recursive: bool,
+    },
+    /// Rename an object, or a directory if `from` ends with '/'
+    Mv {
+        from: String,
+        to: String,
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Copy an object, or every object below a prefix if `from` ends with '/'
+    Cp {
+        from: String,
+        to: String,
+        /// Destination bucket, defaults to the source bucket
+        #[arg(long)]
+        to_bucket: Option<String>,
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Search objects by key
+    Search {
+        key: String,
+        #[arg(long)]
+        offset: Option<usize>,
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+}
+
+#[derive(Subcommand)]
+enum BucketsCommand {
+    Ls,
+    Show {
+        name: String,
+    },
+    Create {
+        name: String,
+        #[arg(long)]
+        public: bool,
+    },
+    /// Update the read access policy of a bucket
+    Policy {
+        name: String,
+        #[arg(long, action = clap::ArgAction::Set)]
+        public: bool,
+    },
+    Rm {
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum HostsCommand {
+    Ls {
+        #[arg(long)]
+        offset: Option<NonZeroUsize>,
+        #[arg(long)]
+        limit: Option<NonZeroUsize>,
+    },
+    Inspect {
+        key: String,
+    },
+    #[command(subcommand)]
+    Allowlist(ListCommand),
+    #[command(subcommand)]
+    Blocklist(ListCommand),
+}
+
+#[derive(Subcommand)]
+enum ListCommand {
+    Show,
+    Add { entries: Vec<String> },
+    Remove { entries: Vec<String> },
+    Clear,
+}
+
+#[derive(Subcommand)]
+enum ContractsCommand {
+    Ls {
+        #[arg(long)]
+        set: Option<String>,
+    },
+    Show {
+        id: String,
+    },
+    Prunable,
+    Sets,
+}
+
+#[derive(Subcommand)]
+enum SettingsCommand {
+    Get {
+        name: SettingName,
+    },
+    /// Replace a setting with JSON given inline, as `@file` or as `-` for stdin
+    Set {
+        name: SettingName,
+        value: String,
+    },
+    /// Show how the given JSON differs from the current setting
+    Diff {
+        name: SettingName,
+        value: String,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+enum SortField {
+    Name,
+    Size,
+    Health,
+    ModTime,
+}
+
+impl From<SortField> for SortBy {
+    fn from(field: SortField) -> Self {
+        match field {
+            SortField::Name => SortBy::Name,
+            SortField::Size => SortBy::Size,
+            SortField::Health => SortBy::Health,
+            SortField::ModTime => SortBy::ModTime,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+enum SettingName {
+    ContractSet,
+    Gouging,
+    Redundancy,
+    S3Authentication,
+    UploadPacking,
+}
+
+#[derive(Subcommand)]
+enum AlertsCommand {
+    Ls {
+        #[arg(long)]
+        offset: Option<NonZeroUsize>,
+        #[arg(long)]
+        limit: Option<NonZeroUsize>,
+    },
+    /// Dismiss the given alerts, or all alerts if none are given
+    Dismiss { ids: Vec<String> },
+}
+
+#[derive(Subcommand)]
+enum WebhooksCommand {
+    Ls,
+    Add {
+        url: String,
+        #[arg(long)]
+        event: Option<WebhookEvent>,
+    },
+    Rm {
+        url: String,
+        #[arg(long)]
+        event: Option<WebhookEvent>,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+enum WebhookEvent {
+    Register,
+    Dismiss,
+}
+
+#[derive(Subcommand)]
+enum AutopilotCommand {
+    State,
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    Trigger {
+        #[arg(long)]
+        force_scan: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    Get,
+    /// Replace the config with JSON given inline, as `@file` or as `-` for stdin
+    Set {
+        value: String,
+    },
+    Diff {
+        value: String,
+    },
+}
+
+#[derive(Deserialize, Default, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    api_url: Option<String>,
+    api_password: Option<String>,
+    bucket: Option<String>,
+    accept_invalid_certs: Option<bool>,
+}
+
+impl ConfigFile {
+    fn load(path: Option<&Path>) -> Result<Self> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match default_config_path() {
+                Some(path) => (path, false),
+                None => return Ok(Self::default()),
+            },
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(content) => Ok(toml::from_str(&content)
+                .map_err(|e| format!("invalid config file `{}`: {}", path.display(), e))?),
+            Err(err) if !required && err.kind() == std::io::ErrorKind::NotFound => {
+                Ok(Self::default())
+            }
+            Err(err) => Err(format!("unable to read `{}`: {}", path.display(), err).into()),
+        }
+    }
+}
+
+fn default_config_path() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join("renterd-cli").join("config.toml"))
+}
+
+struct Settings {
+    url: String,
+    password: String,
+    bucket: Option<String>,
+    accept_invalid_certs: bool,
+}
+
+impl Settings {
+    fn resolve(cli: &Cli, file: ConfigFile) -> Result<Self> {
+        Ok(Self {
+            url: cli
+                .url
+                .clone()
+                .or(file.api_url)
+                .ok_or("no API url configured, use --url, RENTERD_API_URL or the config file")?,
+            password: cli.password.clone().or(file.api_password).ok_or(
+                "no API password configured, use --password, RENTERD_API_PASSWORD or the config file",
+            )?,
+            bucket: cli.bucket.clone().or(file.bucket),
+            accept_invalid_certs: cli.insecure || file.accept_invalid_certs.unwrap_or(false),
+        })
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    if let Err(err) = run(cli).await {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+}
+
+async fn run(cli: Cli) -> Result<()> {
+    let settings = Settings::resolve(&cli, ConfigFile::load(cli.config.as_deref())?)?;
+    let client = ClientBuilder::new()
+        .api_endpoint_url(&settings.url)
+        .api_password(&settings.password)
+        .danger_accept_invalid_certs(settings.accept_invalid_certs)
+        .build()?;
+    let bucket = settings.bucket;
+
+    let output = match cli.command {
+        Command::Objects(command) => objects(&client, bucket, command).await?,
+        Command::Buckets(command) => buckets(&client, command).await?,
+        Command::Hosts(command) => hosts(&client, command).await?,
+        Command::Contracts(command) => contracts(&client, command).await?,
+        Command::Settings(command) => settings_command(&client, command).await?,
+        Command::Alerts(command) => alerts(&client, command).await?,
+        Command::Webhooks(command) => webhooks(&client, command).await?,
+        Command::Wallet { outputs } => {
+            if outputs {
+                Value::Array(
+                    client
+                        .bus()
+                        .wallet()
+                        .outputs()
+                        .await?
+                        .into_iter()
+                        .map(|output| {
+                            json!({
+                                "id": output.id.to_string(),
+                                "address": output.address,
+                                "value": output.value.to_string(),
+                                "maturityHeight": output.maturity_height,
+                            })
+                        })
+                        .collect(),
+                )
+            } else {
+                let wallet = client.bus().wallet().get().await?;
+                json!({
+                    "address": wallet.address,
+                    "scanHeight": wallet.scan_height,
+                    "spendable": wallet.spendable.to_string(),
+                    "confirmed": wallet.confirmed.to_string(),
+                    "unconfirmed": wallet.unconfirmed.to_string(),
+                })
+            }
+        }
+        Command::Consensus => {
+            let state = client.bus().consensus().state().await?;
+            json!({
+                "blockHeight": state.block_height,
+                "lastBlockTime": state.last_block_time.to_rfc3339(),
+                "synced": state.synced,
+            })
+        }
+        Command::Autopilot(command) => autopilot(&client, command).await?,
+    };
+
+    print_output(&output, cli.json);
+    Ok(())
+}
+
+async fn objects(
+    client: &Client,
+    bucket: Option<String>,
+    command: ObjectsCommand,
+) -> Result<Value> {
+    Ok(match command {
+        ObjectsCommand::Ls {
+            path,
+            recursive,
+            sort,
+            desc,
+            substring,
+        } => {
+            let mut options = ListOptions::new();
+            if let Some(sort) = sort {
+                options = options.sort_by(sort.into()).sort_dir(if desc {
+                    SortDir::Desc
+                } else {
+                    SortDir::Asc
+                });
+            }
+            if let Some(substring) = substring {
+                options = options.substring(substring);
+            }
+            let mut entries = vec![];
+            if recursive {
+                let mut stream = client.bus().object().list(
+                    LIST_BATCH_SIZE,
+                    &options.prefix(absolute(&path)),
+                    bucket,
+                )?;
+                while let Some(batch) = stream.try_next().await? {
+                    entries.extend(batch.iter().map(metadata_json));
+                }
+            } else {
+                match client
+                    .bus()
+                    .object()
+                    .get_stream(absolute(&path), LIST_BATCH_SIZE, &options, bucket)
+                    .await?
+                    .ok_or_else(|| format!("`{}` not found", path))?
+                {
+                    Either::Left(object) => entries.push(metadata_json(&object.metadata)),
+                    Either::Right(mut stream) => {
+                        while let Some(batch) = stream.try_next().await? {
+                            entries.extend(batch.iter().map(metadata_json));
+                        }
+                    }
+                }
+            }
+            Value::Array(entries)
+        }
+        ObjectsCommand::Cat { path } => {
+            let object = client
+                .worker()
+                .object()
+                .download(absolute(&path), bucket)
+                .await?
+                .ok_or_else(|| format!("`{}` not found", path))?;
+            let reader = object.open_stream(None).await?;
+            futures::io::copy(reader, &mut AllowStdIo::new(std::io::stdout().lock())).await?;
+            Value::Null
+        }
+        ObjectsCommand::Put {
+            file,
+            path,
+            content_type,
+        } => {
+            let options = UploadFileOptions {
+                bucket,
+                content_type,
+            };
+            let path = absolute(&path);
+            client
+                .worker()
+                .object()
+                .upload_file(&file, &path, options)
+                .await?;
+            json!({ "uploaded": path })
+        }
+        ObjectsCommand::Get { path, file, force } => {
+            let file = match file {
+                Some(file) => file,
+                None => PathBuf::from(
+                    path.rsplit('/')
+                        .find(|name| !name.is_empty())
+                        .ok_or("unable to derive a file name, please specify one")?,
+                ),
+            };
+            let options = DownloadFileOptions {
+                bucket,
+                overwrite: force,
+                preserve_modified: true,
+            };
+            let object = client
+                .worker()
+                .object()
+                .download_to_file(absolute(&path), &file, options)
+                .await?
+                .ok_or_else(|| format!("`{}` not found", path))?;
+            json!({
+                "downloaded": object.path,
+                "file": file.display().to_string(),
+                "size": object.length,
+            })
+        }
+        ObjectsCommand::Rm { path, recursive } => {
+            let path = absolute(&path);
+            if recursive {
+                client.bus().object().delete_prefix(&path, bucket).await?;
+            } else {
+                client.bus().object().delete(&path, bucket, false).await?;
+            }
+            json!({ "deleted": path })
+        }
+        ObjectsCommand::Mv { from, to, force } => {
+            let (from, to) = (absolute(&from), absolute(&to));
+            let mode = if from.ends_with('/') {
+                RenameMode::Multi
+            } else {
+                RenameMode::Single
+            };
+            client
+                .bus()
+                .object()
+                .rename(
+                    from.clone(),
+                    to.clone(),
+                    bucket.unwrap_or_else(|| DEFAULT_BUCKET.to_string()),
+                    force,
+                    mode,
+                )
+                .await?;
+            json!({ "from": from, "to": to })
+        }
+        ObjectsCommand::Cp {
+            from,
+            to,
+            to_bucket,
+            force,
+        } => {
+            let (from, to) = (absolute(&from), absolute(&to));
+            let bucket = bucket.unwrap_or_else(|| DEFAULT_BUCKET.to_string());
+            let to_bucket = to_bucket.unwrap_or_else(|| bucket.clone());
+            if from.ends_with('/') {
+                let conflict = if force {
+                    ConflictPolicy::Overwrite
+                } else {
+                    ConflictPolicy::Fail
+                };
+                let report = client
+                    .bus()
+                    .object()
+                    .copy_prefix(
+                        &from,
+                        bucket,
+                        &to,
+                        to_bucket,
+                        &PrefixOptions::default().conflict(conflict),
+                    )
+                    .await?;
+                prefix_report_json(&report)
+            } else {
+                client
+                    .bus()
+                    .object()
+                    .copy(from.clone(), bucket, to.clone(), to_bucket)
+                    .await?;
+                json!({ "from": from, "to": to })
+            }
+        }
+        ObjectsCommand::Search { key, offset, limit } => Value::Array(
+            client
+                .bus()
+                .object()
+                .search(Some(key), bucket, offset, limit)
+                .await?
+                .iter()
+                .map(metadata_json)
+                .collect(),
+        ),
+    })
+}
+
+async fn buckets(client: &Client, command: BucketsCommand) -> Result<Value> {
+    let api = client.bus().bucket();
+    Ok(match command {
+        BucketsCommand::Ls => Value::Array(
+            api.get_all()
+                .await?
+                .into_iter()
+                .map(|bucket| {
+                    json!({
+                        "name": bucket.name,
+                        "createdAt": bucket.created_at.to_rfc3339(),
+                        "publicReadAccess": bucket.policy.public_read_access,
+                    })
+                })
+                .collect(),
+        ),
+        BucketsCommand::Show { name } => {
+            let bucket = api
+                .get_by_name(&name)
+                .await?
+                .ok_or_else(|| format!("bucket `{}` not found", name))?;
+            json!({
+                "name": bucket.name,
+                "createdAt": bucket.created_at.to_rfc3339(),
+                "policy": serde_json::to_value(&bucket.policy)?,
+            })
+        }
+        BucketsCommand::Create { name, public } => {
+            api.create(&name, public).await?;
+            json!({ "created": name })
+        }
+        BucketsCommand::Policy { name, public } => {
+            api.update_policy(&name, public).await?;
+            json!({ "updated": name, "publicReadAccess": public })
+        }
+        BucketsCommand::Rm { name } => {
+            api.delete(&name).await?;
+            json!({ "deleted": name })
+        }
+    })
+}
+
+async fn hosts(client: &Client, command: HostsCommand) -> Result<Value> {
+    let api = client.bus().host();
+    Ok(match command {
+        HostsCommand::Ls { offset, limit } => Value::Array(
+            api.get_all(offset, limit)
+                .await?
+                .into_iter()
+                .map(|host| {
+                    json!({
+                        "publicKey": host.public_key.to_string(),
+                        "netAddress": host.net_address,
+                        "scanned": host.scanned,
+                        "blocked": host.blocked,
+                        "storedData": host.stored_data,
+                        "lastScanSuccess": host.interactions.last_scan_success,
+                    })
+                })
+                .collect(),
+        ),
+        HostsCommand::Inspect { key } => {
+            let host = api.get_by_key(&PublicKey::try_from(key.as_str())?).await?;
+            let checks: Map<String, Value> = host
+                .checks
+                .iter()
+                .map(|(set, check)| {
+                    let unusable: Vec<&str> = check.usability.reasons().collect();
+                    (
+                        set.clone(),
+                        json!({ "usable": unusable.is_empty(), "unusable": unusable }),
+                    )
+                })
+                .collect();
+            let interactions = &host.interactions;
+            json!({
+                "publicKey": host.public_key.to_string(),
+                "netAddress": host.net_address,
+                "knownSince": host.known_since.to_rfc3339(),
+                "lastAnnouncement": host.last_announcement.to_rfc3339(),
+                "scanned": host.scanned,
+                "blocked": host.blocked,
+                "storedData": host.stored_data,
+                "subnets": host.subnets,
+                "interactions": {
+                    "totalScans": interactions.total_scans,
+                    "lastScan": interactions.last_scan.to_rfc3339(),
+                    "lastScanSuccess": interactions.last_scan_success,
+                    "lostSectors": interactions.lost_sectors,
+                    "uptimeSecs": interactions.uptime.as_secs(),
+                    "downtimeSecs": interactions.downtime.as_secs(),
+                    "successfulInteractions": interactions.successful_interactions,
+                    "failedInteractions": interactions.failed_interactions,
+                },
+                "checks": checks,
+            })
+        }
+        HostsCommand::Allowlist(command) => {
+            let action = match command {
+                ListCommand::Show => None,
+                ListCommand::Add { entries } => Some(ModifyAction::AddRemove {
+                    add: Some(parse_keys(&entries)?),
+                    remove: None,
+                }),
+                ListCommand::Remove { entries } => Some(ModifyAction::AddRemove {
+                    add: None,
+                    remove: Some(parse_keys(&entries)?),
+                }),
+                ListCommand::Clear => Some(ModifyAction::Clear),
+            };
+            if let Some(action) = action {
+                api.modify_allowlist(action).await?;
+            }
+            Value::Array(
+                api.allowlist()
+                    .await?
+                    .iter()
+                    .map(|key| Value::String(key.to_string()))
+                    .collect(),
+            )
+        }
+        HostsCommand::Blocklist(command) => {
+            let action = match command {
+                ListCommand::Show => None,
+                ListCommand::Add { entries } => Some(ModifyAction::AddRemove {
+                    add: Some(entries),
+                    remove: None,
+                }),
+                ListCommand::Remove { entries } => Some(ModifyAction::AddRemove {
+                    add: None,
+                    remove: Some(entries),
+                }),
+                ListCommand::Clear => Some(ModifyAction::Clear),
+            };
+            if let Some(action) = action {
+                api.modify_blocklist(action).await?;
+            }
+            Value::Array(
+                api.blocklist()
+                    .await?
+                    .into_iter()
+                    .map(Value::String)
+                    .collect(),
+            )
+        }
+    })
+}
+
+async fn contracts(client: &Client, command: ContractsCommand) -> Result<Value> {
+    let api = client.bus().contract();
+    Ok(match command {
+        ContractsCommand::Ls { set } => Value::Array(
+            api.get_all(set)
+                .await?
+                .into_iter()
+                .map(|contract| {
+                    json!({
+                        "id": contract.id.to_string(),
+                        "hostKey": contract.host_key.to_string(),
+                        "state": format!("{:?}", contract.state).to_lowercase(),
+                        "size": contract.size,
+                        "windowStart": contract.window_start,
+                        "windowEnd": contract.window_end,
+                        "totalCost": contract.total_cost.to_string(),
+                    })
+                })
+                .collect(),
+        ),
+        ContractsCommand::Show { id } => {
+            let contract = api
+                .get_by_id(&FileContractId::try_from(id.as_str())?)
+                .await?;
+            json!({
+                "id": contract.id.to_string(),
+                "hostKey": contract.host_key.to_string(),
+                "hostIP": contract.host_ip,
+                "siamuxAddr": contract.siamux_addr,
+                "state": format!("{:?}", contract.state).to_lowercase(),
+                "size": contract.size,
+                "startHeight": contract.start_height,
+                "proofHeight": contract.proof_height,
+                "revisionHeight": contract.revision_height,
+                "revisionNumber": contract.revision_number,
+                "windowStart": contract.window_start,
+                "windowEnd": contract.window_end,
+                "contractPrice": contract.contract_price.to_string(),
+                "totalCost": contract.total_cost.to_string(),
+                "renewedFrom": contract.renewed_from.to_string(),
+                "spending": serde_json::to_value(&contract.spending)?,
+                "contractSets": contract.contract_sets,
+            })
+        }
+        ContractsCommand::Prunable => {
+            let prunable = api.prunable().await?;
+            json!({
+                "totalPrunable": prunable.total_prunable,
+                "totalSize": prunable.total_size,
+                "contracts": prunable
+                    .contracts
+                    .iter()
+                    .map(|contract| {
+                        json!({
+                            "id": contract.id.to_string(),
+                            "prunable": contract.prunable,
+                            "size": contract.size,
+                        })
+                    })
+                    .collect::<Vec<_>>(),
+            })
+        }
+        ContractsCommand::Sets => Value::Array(
+            api.contract_sets()
+                .await?
+                .into_iter()
+                .map(Value::String)
+                .collect(),
+        ),
+    })
+}
+
+async fn get_setting(client: &Client, name: SettingName) -> Result<Value> {
+    let api = client.bus().setting();
+    Ok(match name {
+        SettingName::ContractSet => serde_json::to_value(api.contract_set().get().await?)?,
+        SettingName::Gouging => serde_json::to_value(api.gouging().get().await?)?,
+        SettingName::Redundancy => serde_json::to_value(api.redundancy().get().await?)?,
+        SettingName::S3Authentication => {
+            serde_json::to_value(api.s3_authentication().get().await?)?
+        }
+        SettingName::UploadPacking => serde_json::to_value(api.upload_packing().get().await?)?,
+    })
+}
+
+async fn update_setting(client: &Client, name: SettingName, value: Value) -> Result<()> {
+    let api = client.bus().setting();
+    match name {
+        SettingName::ContractSet => {
+            api.contract_set()
+                .update(&serde_json::from_value(value)?)
+                .await?
+        }
+        SettingName::Gouging => {
+            api.gouging()
+                .update(&serde_json::from_value(value)?)
+                .await?
+        }
+        SettingName::Redundancy => {
+            api.redundancy()
+                .update(&serde_json::from_value(value)?)
+                .await?
+        }
+        SettingName::S3Authentication => {
+            api.s3_authentication()
+                .update(&serde_json::from_value(value)?)
+                .await?
+        }
+        SettingName::UploadPacking => {
+            api.upload_packing()
+                .update(&serde_json::from_value(value)?)
+                .await?
+        }
+    }
+    Ok(())
+}
+
+async fn settings_command(client: &Client, command: SettingsCommand) -> Result<Value> {
+    Ok(match command {
+        SettingsCommand::Get { name } => get_setting(client, name).await?,
+        SettingsCommand::Set { name, value } => {
+            update_setting(client, name, read_json(&value)?).await?;
+            get_setting(client, name).await?
+        }
+        SettingsCommand::Diff { name, value } => {
+            let current = get_setting(client, name).await?;
+            Value::Array(json_diff(&current, &read_json(&value)?))
+        }
+    })
+}
+
+async fn alerts(client: &Client, command: AlertsCommand) -> Result<Value> {
+    let api = client.bus().alert();
+    Ok(match command {
+        AlertsCommand::Ls { offset, limit } => {
+            let (alerts, has_more) = api.get_all(offset, limit).await?;
+            json!({ "alerts": serde_json::to_value(alerts)?, "hasMore": has_more })
+        }
+        AlertsCommand::Dismiss { ids } => {
+            let ids = ids
+                .iter()
+                .map(|id| Hash::try_from(id.as_str()))
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            if ids.is_empty() {
+                api.dismiss(None).await?;
+            } else {
+                api.dismiss(Some(ids.iter().collect())).await?;
+            }
+            json!({ "dismissed": ids.iter().map(|id| id.to_string()).collect::<Vec<_>>() })
+        }
+    })
+}
+
+fn webhook(url: String, event: Option<WebhookEvent>) -> Webhook {
+    Webhook {
+        module: Module::Alerts,
+        event_type: event.map(|event| match event {
+            WebhookEvent::Register => EventType::Register,
+            WebhookEvent::Dismiss => EventType::Dismiss,
+        }),
+        url,
+        headers: None,
+    }
+}
+
+async fn webhooks(client: &Client, command: WebhooksCommand) -> Result<Value> {
+    let api = client.bus().webhook();
+    Ok(match command {
+        WebhooksCommand::Ls => {
+            let (webhooks, queues) = api.get_all().await?;
+            let queues: Map<String, Value> = queues
+                .into_iter()
+                .map(|queue| (queue.url, queue.size.into()))
+                .collect();
+            json!({ "webhooks": serde_json::to_value(webhooks)?, "queues": queues })
+        }
+        WebhooksCommand::Add { url, event } => {
+            let webhook = webhook(url, event);
+            api.register(&webhook).await?;
+            serde_json::to_value(webhook)?
+        }
+        WebhooksCommand::Rm { url, event } => {
+            let webhook = webhook(url, event);
+            api.delete(&webhook).await?;
+            serde_json::to_value(webhook)?
+        }
+    })
+}
+
+async fn autopilot(client: &Client, command: AutopilotCommand) -> Result<Value> {
+    let autopilot = client.autopilot();
+    Ok(match command {
+        AutopilotCommand::State => {
+            let state = autopilot.state().await?;
+            json!({
+                "configured": state.configured,
+                "migrating": state.migrating,
+                "pruning": state.pruning,
+                "scanning": state.scanning,
+                "uptimeSecs": state.uptime.as_secs(),
+                "startTime": state.common.start_time.to_rfc3339(),
+                "network": state.common.network,
+                "version": state.common.version,
+            })
+        }
+        AutopilotCommand::Config(ConfigCommand::Get) => {
+            serde_json::to_value(autopilot.config().get().await?)?
+        }
+        AutopilotCommand::Config(ConfigCommand::Set { value }) => {
+            autopilot
+                .config()
+                .update(&serde_json::from_value(read_json(&value)?)?)
+                .await?;
+            serde_json::to_value(autopilot.config().get().await?)?
+        }
+        AutopilotCommand::Config(ConfigCommand::Diff { value }) => {
+            let current = serde_json::to_value(autopilot.config().get().await?)?;
+            Value::Array(json_diff(&current, &read_json(&value)?))
+        }
+        AutopilotCommand::Trigger { force_scan } => {
+            json!({ "triggered": autopilot.trigger(force_scan).await? })
+        }
+    })
+}
+
+fn absolute(path: &str) -> String {
+    if path.starts_with('/') {
+        path.to_string()
+    } else {
+        format!("/{}", path)
+    }
+}
+
+fn parse_keys(keys: &[String]) -> Result<Vec<PublicKey>> {
+    Ok(keys
+        .iter()
+        .map(|key| PublicKey::try_from(key.as_str()))
+        .collect::<std::result::Result<_, _>>()?)
+}
+
+fn metadata_json(metadata: &Metadata) -> Value {
+    json!({
+        "name": metadata.name,
+        "size": metadata.size,
+        "modTime": metadata.mod_time.to_rfc3339(),
+        "health": metadata.health.to_string(),
+        "eTag": metadata.etag,
+        "mimeType": metadata.mime_type,
+    })
+}
+
+fn prefix_report_json(report: &PrefixReport) -> Value {
+    json!({
+        "planned": report.planned.len(),
+        "copied": report.copied,
+        "skipped": report.skipped,
+        "failed": report
+            .failed
+            .iter()
+            .map(|(key, err)| json!({ "key": key, "error": err.to_string() }))
+            .collect::<Vec<_>>(),
+    })
+}
+
+fn read_json(value: &str) -> Result<Value> {
+    let content = match value {
+        "-" => std::io::read_to_string(std::io::stdin())?,
+        value => match value.strip_prefix('@') {
+            Some(file) => std::fs::read_to_string(file)?,
+            None => value.to_string(),
+        },
+    };
+    Ok(serde_json::from_str(&content)?)
+}
+
+fn json_diff(current: &Value, desired: &Value) -> Vec<Value> {
+    let mut changes = vec![];
+    diff_values("", current, desired, &mut changes);
+    changes
+}
+
+fn diff_values(path: &str, current: &Value, desired: &Value, changes: &mut Vec<Value>) {
+    match (current, desired) {
+        (Value::Object(current), Value::Object(desired)) => {
+            for (key, value) in current {
+                let path = format!("{}/{}", path, key);
+                match desired.get(key) {
+                    Some(desired) => diff_values(&path, value, desired, changes),
+                    None => changes.push(json!({ "path": path, "current": value })),
+                }
+            }
+            for (key, value) in desired {
+                if !current.contains_key(key) {
+                    changes.push(json!({ "path": format!("{}/{}", path, key), "desired": value }));
+                }
+            }
+        }
+        (current, desired) if !same_value(current, desired) => changes.push(json!({
+            "path": if path.is_empty() { "/" } else { path },
+            "current": current,
+            "desired": desired,
+        })),
+        _ => {}
+    }
+}
+
+fn same_number(a: &str, b: &str) -> bool {
+    match (BigDecimal::from_str(a), BigDecimal::from_str(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+fn same_value(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        // numbers are compared by value so that `1` and `1.0` or `"1"` do not show up as changes,
+        // as decimals because amounts in hastings don't fit into a float
+        (Value::Number(a), Value::Number(b)) => same_number(&a.to_string(), &b.to_string()),
+        (Value::Number(n), Value::String(s)) | (Value::String(s), Value::Number(n)) => {
+            same_number(&n.to_string(), s)
+        }
+        _ => a == b,
+    }
+}
+
+fn print_output(value: &Value, json: bool) {
+    if json {
+        if !value.is_null() {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(value).unwrap_or_default()
+            );
+        }
+        return;
+    }
+    match value {
+        Value::Null => {}
+        Value::Array(items) => {
+            for item in items {
+                println!("{}", render_line(item));
+            }
+        }
+        Value::Object(fields) => {
+            for (key, value) in fields {
+                match value {
+                    Value::Array(items) if items.iter().any(Value::is_object) => {
+                        println!("{}:", key);
+                        for item in items {
+                            println!("  {}", render_line(item));
+                        }
+                    }
+                    value => println!("{}: {}", key, render_scalar(value)),
+                }
+            }
+        }
+        value => println!("{}", render_scalar(value)),
+    }
+}
+
+fn render_line(value: &Value) -> String {
+    match value {
+        Value::Object(fields) => fields
+            .values()
+            .map(render_scalar)
+            .collect::<Vec<_>>()
+            .join("\t"),
+        value => render_scalar(value),
+    }
+}
+
+fn render_scalar(value: &Value) -> String {
+    match value {
+        Value::Null => "-".to_string(),
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_file() -> anyhow::Result<()> {
+        let config: ConfigFile = toml::from_str(
+            r#"
+api_url = "http://localhost:9980/api/"
+api_password = "secret"
+bucket = "foo"
+"#,
+        )?;
+        assert_eq!(
+            config,
+            ConfigFile {
+                api_url: Some("http://localhost:9980/api/".to_string()),
+                api_password: Some("secret".to_string()),
+                bucket: Some("foo".to_string()),
+                accept_invalid_certs: None,
+            }
+        );
+        assert!(toml::from_str::<ConfigFile>("api_key = \"secret\"").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn value_comparison() -> anyhow::Result<()> {
+        let number = |s: &str| serde_json::from_str::<Value>(s);
+        assert!(same_value(&json!(1), &json!(1.0)));
+        assert!(same_value(&json!(1), &json!("1")));
+        assert!(same_value(
+            &json!("1000000000000000000000000"),
+            &number("1e24")?
+        ));
+        assert!(!same_value(
+            &number("1000000000000000000000000")?,
+            &number("1000000000000000000000001")?
+        ));
+        assert!(!same_value(&json!("a"), &json!(1)));
+        Ok(())
+    }
+
+    #[test]
+    fn settings_precedence() -> anyhow::Result<()> {
+        let cli = Cli::try_parse_from(["renterd-cli", "--url", "http://flag/api/", "consensus"])?;
+        let settings = Settings::resolve(
+            &cli,
+            ConfigFile {
+                api_url: Some("http://file/api/".to_string()),
+                api_password: Some("secret".to_string()),
+                bucket: None,
+                accept_invalid_certs: Some(true),
+            },
+        )
+        .map_err(|e| anyhow::anyhow!("{}", e))?;
+        assert_eq!(settings.url, "http://flag/api/");
+        assert_eq!(settings.password, "secret");
+        assert!(settings.accept_invalid_certs);
+
+        assert!(Settings::resolve(&cli, ConfigFile::default()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn diff() {
+        let current = json!({
+            "enabled": true,
+            "default": "autopilot",
+            "nested": { "a": 1, "b": "100" },
+            "removed": 1,
+        });
+        let desired = json!({
+            "enabled": false,
+            "default": "autopilot",
+            "nested": { "a": 1.0, "b": 100 },
+            "added": "x",
+        });
+        assert_eq!(
+            json_diff(&current, &desired),
+            vec![
+                json!({ "path": "/enabled", "current": true, "desired": false }),
+                json!({ "path": "/removed", "current": 1 }),
+                json!({ "path": "/added", "desired": "x" }),
+            ]
+        );
+        assert!(json_diff(&current, &current).is_empty());
+    }
+}
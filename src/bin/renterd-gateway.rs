@@ -0,0 +1,44 @@
+use renterd_client::gateway::Gateway;
+use renterd_client::ClientBuilder;
+use std::error::Error;
+use tokio::net::TcpListener;
+
+// configured through the environment:
+//   RENTERD_API_URL, RENTERD_API_PASSWORD  renterd API endpoint and password (required)
+//   RENTERD_GATEWAY_ADDR                   listen address, defaults to 127.0.0.1:8080
+//   RENTERD_GATEWAY_LISTINGS               render directory listings if set to `true` or `1`
+//   RENTERD_GATEWAY_BUCKETS                comma separated list of buckets to serve, all if unset
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let client = ClientBuilder::new()
+        .api_endpoint_url(required_var("RENTERD_API_URL")?)
+        .api_password(required_var("RENTERD_API_PASSWORD")?)
+        .build()?;
+
+    let mut gateway = Gateway::new(&client).listings(
+        std::env::var("RENTERD_GATEWAY_LISTINGS")
+            .is_ok_and(|listings| listings == "1" || listings.eq_ignore_ascii_case("true")),
+    );
+    if let Ok(buckets) = std::env::var("RENTERD_GATEWAY_BUCKETS") {
+        gateway = gateway.buckets(
+            buckets
+                .split(',')
+                .map(str::trim)
+                .filter(|bucket| !bucket.is_empty()),
+        );
+    }
+
+    let addr =
+        std::env::var("RENTERD_GATEWAY_ADDR").unwrap_or_else(|_| "127.0.0.1:8080".to_string());
+    let listener = TcpListener::bind(&addr).await?;
+    eprintln!(
+        "serving renterd objects on http://{}",
+        listener.local_addr()?
+    );
+    gateway.serve(listener).await?;
+    Ok(())
+}
+
+fn required_var(name: &str) -> Result<String, String> {
+    std::env::var(name).map_err(|_| format!("environment variable `{}` is not set", name))
+}
@@ -0,0 +1,37 @@
+use renterd_client::webdav::WebDav;
+use renterd_client::ClientBuilder;
+use std::error::Error;
+use tokio::net::TcpListener;
+
+// configured through the environment:
+//   RENTERD_API_URL, RENTERD_API_PASSWORD  renterd API endpoint and password (required)
+//   RENTERD_WEBDAV_ADDR                    listen address, defaults to 127.0.0.1:8081
+//   RENTERD_WEBDAV_BUCKETS                 comma separated list of buckets to serve, all if unset
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let client = ClientBuilder::new()
+        .api_endpoint_url(required_var("RENTERD_API_URL")?)
+        .api_password(required_var("RENTERD_API_PASSWORD")?)
+        .build()?;
+
+    let mut webdav = WebDav::new(&client);
+    if let Ok(buckets) = std::env::var("RENTERD_WEBDAV_BUCKETS") {
+        webdav = webdav.buckets(
+            buckets
+                .split(',')
+                .map(str::trim)
+                .filter(|bucket| !bucket.is_empty()),
+        );
+    }
+
+    let addr =
+        std::env::var("RENTERD_WEBDAV_ADDR").unwrap_or_else(|_| "127.0.0.1:8081".to_string());
+    let listener = TcpListener::bind(&addr).await?;
+    eprintln!("serving WebDAV on http://{}", listener.local_addr()?);
+    webdav.serve(listener).await?;
+    Ok(())
+}
+
+fn required_var(name: &str) -> Result<String, String> {
+    std::env::var(name).map_err(|_| format!("environment variable `{}` is not set", name))
+}
@@ -0,0 +1,578 @@
+use crate::bus::object::{
+    ListOptions, Metadata, MetadataUpdate, MetadataUpdateMethod, Object, ObjectQuery, RenameMode,
+};
+use crate::bus::stats::objects::Stats;
+use crate::bus::Bus;
+use crate::usage::{Api as UsageApi, UsageStats};
+use crate::worker::object::{DownloadFileOptions, DownloadableObject, UploadFileOptions};
+use crate::worker::Worker;
+use crate::{Either, Error, LIST_BATCH_SIZE};
+use futures::{AsyncRead, TryStream, TryStreamExt};
+use std::path::Path;
+
+// keys that could leave the prefix through `..` or empty segments are rejected
+#[derive(Clone)]
+pub struct BucketHandle {
+    bus: Bus,
+    worker: Worker,
+    name: String,
+    scope: Scope,
+}
+
+impl BucketHandle {
+    pub(super) fn new(bus: Bus, worker: Worker, name: String) -> Self {
+        Self {
+            bus,
+            worker,
+            name,
+            scope: Scope::root(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn prefix(&self) -> String {
+        self.scope.prefix()
+    }
+
+    pub fn is_scoped(&self) -> bool {
+        !self.scope.base.is_empty()
+    }
+
+    pub fn scoped<S: AsRef<str>>(&self, prefix: S) -> Result<Self, Error> {
+        Ok(Self {
+            scope: self.scope.join(prefix.as_ref())?,
+            ..self.clone()
+        })
+    }
+
+    pub fn resolve<S: AsRef<str>>(&self, key: S) -> Result<String, Error> {
+        self.scope.resolve(key.as_ref())
+    }
+
+    pub async fn get<S: AsRef<str>>(&self, key: S) -> Result<Option<Object>, Error> {
+        let path = self.resolve_object(key.as_ref())?;
+        match self
+            .bus
+            .object()
+            .get(&path, Some(self.name.clone()), None, None, None, None)
+            .await?
+        {
+            Some(Either::Left(mut object)) => {
+                object.metadata = self.scope.relativize(object.metadata).ok_or_else(|| {
+                    Error::UnexpectedResponse("object outside of the scope".to_string())
+                })?;
+                Ok(Some(object))
+            }
+            Some(Either::Right(_)) => Err(Error::UnexpectedResponse(
+                "expected an object, got a directory listing".to_string(),
+            )),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn read_dir<S: AsRef<str>>(
+        &self,
+        dir: S,
+        options: &ListOptions,
+    ) -> Result<Option<impl TryStream<Ok = Vec<Metadata>, Error = Error> + Send + Unpin>, Error>
+    {
+        let path = self.scope.resolve_dir(dir.as_ref())?;
+        let scope = self.scope.clone();
+        match self
+            .bus
+            .object()
+            .get_stream(path, LIST_BATCH_SIZE, options, Some(self.name.clone()))
+            .await?
+        {
+            Some(Either::Right(stream)) => Ok(Some(
+                stream.map_ok(move |batch| scope.relativize_all(batch)),
+            )),
+            Some(Either::Left(_)) => Err(Error::NotADirectory(dir.as_ref().to_string())),
+            None => Ok(None),
+        }
+    }
+
+    // the prefix of `options` is relative to the scope
+    pub fn list(
+        &self,
+        options: &ListOptions,
+    ) -> Result<impl TryStream<Ok = Vec<Metadata>, Error = Error> + Send + Unpin, Error> {
+        let mut options = options.clone();
+        options.prefix = Some(
+            self.scope
+                .resolve(options.prefix.as_deref().unwrap_or("/"))?,
+        );
+        let scope = self.scope.clone();
+        Ok(self
+            .bus
+            .object()
+            .list(LIST_BATCH_SIZE, &options, Some(self.name.clone()))?
+            .map_ok(move |batch| scope.relativize_all(batch)))
+    }
+
+    pub fn search<S: Into<String>>(
+        &self,
+        key: S,
+    ) -> Result<impl TryStream<Ok = Vec<Metadata>, Error = Error> + Send + Unpin, Error> {
+        let key = key.into();
+        self.query(ObjectQuery::new().key(key))
+    }
+
+    pub fn query(
+        &self,
+        mut query: ObjectQuery,
+    ) -> Result<impl TryStream<Ok = Vec<Metadata>, Error = Error> + Send + Unpin, Error> {
+        if self.is_scoped() || query.prefix.is_some() {
+            query.prefix = Some(match &query.prefix {
+                Some(prefix) => self.scope.resolve(prefix)?,
+                None => self.scope.prefix(),
+            });
+        }
+        // renterd matches the key against the absolute name, which includes the scope prefix
+        let key = query.key.clone();
+        let scope = self.scope.clone();
+        Ok(self
+            .bus
+            .object()
+            .query(query, Some(self.name.clone()))?
+            .map_ok(move |batch| {
+                scope
+                    .relativize_all(batch)
+                    .into_iter()
+                    .filter(|m| key.as_ref().is_none_or(|key| m.name.contains(key.as_str())))
+                    .collect::<Vec<_>>()
+            })
+            .try_filter(|batch| futures::future::ready(!batch.is_empty())))
+    }
+
+    pub async fn delete<S: AsRef<str>>(&self, key: S) -> Result<(), Error> {
+        let path = self.resolve_object(key.as_ref())?;
+        self.bus
+            .object()
+            .delete(path, Some(self.name.clone()), false)
+            .await
+    }
+
+    pub async fn delete_dir<S: AsRef<str>>(&self, dir: S) -> Result<(), Error> {
+        let path = self.resolve_subdir(dir.as_ref())?;
+        self.bus
+            .object()
+            .delete(path, Some(self.name.clone()), true)
+            .await
+    }
+
+    pub async fn copy<S: AsRef<str>, D: AsRef<str>>(
+        &self,
+        source: S,
+        destination: D,
+    ) -> Result<(), Error> {
+        let source = self.resolve_object(source.as_ref())?;
+        let destination = self.resolve_object(destination.as_ref())?;
+        self.bus
+            .object()
+            .copy(source, self.name.clone(), destination, self.name.clone())
+            .await
+    }
+
+    pub async fn rename<S: AsRef<str>, D: AsRef<str>>(
+        &self,
+        from: S,
+        to: D,
+        force: bool,
+    ) -> Result<(), Error> {
+        let (from, to, mode) = if from.as_ref().ends_with('/') {
+            (
+                self.resolve_subdir(from.as_ref())?,
+                self.scope.resolve_dir(to.as_ref())?,
+                RenameMode::Multi,
+            )
+        } else {
+            (
+                self.resolve_object(from.as_ref())?,
+                self.resolve_object(to.as_ref())?,
+                RenameMode::Single,
+            )
+        };
+        self.bus
+            .object()
+            .rename(from, to, self.name.clone(), force, mode)
+            .await
+    }
+
+    pub async fn update_metadata<S: AsRef<str>>(
+        &self,
+        key: S,
+        update: &MetadataUpdate,
+    ) -> Result<MetadataUpdateMethod, Error> {
+        let path = self.resolve_object(key.as_ref())?;
+        self.bus
+            .object()
+            .update_metadata(path, self.name.clone(), update)
+            .await
+    }
+
+    pub async fn upload<S: AsRef<str>, U: AsyncRead + Send + Sync + Unpin + 'static>(
+        &self,
+        key: S,
+        stream: U,
+        options: &UploadOptions,
+    ) -> Result<(), Error> {
+        let path = self.resolve_object(key.as_ref())?;
+        self.worker
+            .object()
+            .upload(
+                path,
+                options.content_type.clone(),
+                Some(self.name.clone()),
+                stream,
+            )
+            .await
+    }
+
+    pub async fn upload_file<P: AsRef<Path>, S: AsRef<str>>(
+        &self,
+        file: P,
+        key: S,
+        options: &UploadOptions,
+    ) -> Result<(), Error> {
+        let path = self.resolve_object(key.as_ref())?;
+        let mut file_options = UploadFileOptions::default().bucket(&self.name);
+        file_options.content_type = options.content_type.clone();
+        self.worker
+            .object()
+            .upload_file(file, path, file_options)
+            .await
+    }
+
+    // the `path` of the object is the absolute key within the bucket
+    pub async fn download<S: AsRef<str>>(
+        &self,
+        key: S,
+    ) -> Result<Option<DownloadableObject>, Error> {
+        let path = self.resolve_object(key.as_ref())?;
+        self.worker
+            .object()
+            .download(path, Some(self.name.clone()))
+            .await
+    }
+
+    pub async fn download_to_file<S: AsRef<str>, P: AsRef<Path>>(
+        &self,
+        key: S,
+        file: P,
+        options: &DownloadOptions,
+    ) -> Result<Option<DownloadableObject>, Error> {
+        let path = self.resolve_object(key.as_ref())?;
+        let file_options = DownloadFileOptions::default()
+            .bucket(&self.name)
+            .overwrite(options.overwrite)
+            .preserve_modified(options.preserve_modified);
+        self.worker
+            .object()
+            .download_to_file(path, file, file_options)
+            .await
+    }
+
+    // not available on scoped handles, it would disclose the usage outside of the scope
+    pub async fn stats(&self) -> Result<Stats, Error> {
+        if self.is_scoped() {
+            return Err(Error::InvalidPrefixOperation(format!(
+                "bucket statistics are not available within `{}`",
+                self.prefix()
+            )));
+        }
+        self.bus.stats().objects(Some(self.name.clone())).await
+    }
+
+    pub async fn usage(&self) -> Result<UsageStats, Error> {
+        Ok(UsageApi::new(self.bus.clone())
+            .summarize(self.prefix(), Some(self.name.clone()))
+            .await?
+            .stats)
+    }
+
+    fn resolve_object(&self, key: &str) -> Result<String, Error> {
+        if key.is_empty() || key.ends_with('/') {
+            return Err(Error::InvalidObject(format!(
+                "`{}` is not a valid object path",
+                key
+            )));
+        }
+        self.scope.resolve(key)
+    }
+
+    // deleting or moving the root of the scope would take everything the handle can reach
+    fn resolve_subdir(&self, dir: &str) -> Result<String, Error> {
+        let path = self.scope.resolve_dir(dir)?;
+        if path == self.prefix() {
+            return Err(Error::InvalidPrefixOperation(format!(
+                "`{}` is the root of the handle",
+                path
+            )));
+        }
+        Ok(path)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UploadOptions {
+    pub content_type: Option<String>,
+}
+
+impl UploadOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn content_type<S: ToString>(mut self, content_type: S) -> Self {
+        self.content_type = Some(content_type.to_string());
+        self
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DownloadOptions {
+    pub overwrite: bool,
+    pub preserve_modified: bool,
+}
+
+impl DownloadOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn overwrite(mut self, overwrite: bool) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    pub fn preserve_modified(mut self, preserve_modified: bool) -> Self {
+        self.preserve_modified = preserve_modified;
+        self
+    }
+}
+
+// stored without the trailing `/`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Scope {
+    base: String,
+}
+
+impl Scope {
+    fn root() -> Self {
+        Self {
+            base: String::new(),
+        }
+    }
+
+    fn prefix(&self) -> String {
+        format!("{}/", self.base)
+    }
+
+    fn join(&self, prefix: &str) -> Result<Self, Error> {
+        let relative = validate_key(prefix)?;
+        Ok(Self {
+            base: format!("{}{}", self.base, relative.trim_end_matches('/')),
+        })
+    }
+
+    fn resolve(&self, key: &str) -> Result<String, Error> {
+        Ok(format!("{}{}", self.base, validate_key(key)?))
+    }
+
+    fn resolve_dir(&self, dir: &str) -> Result<String, Error> {
+        let path = self.resolve(dir)?;
+        Ok(if path.ends_with('/') {
+            path
+        } else {
+            format!("{}/", path)
+        })
+    }
+
+    fn relative<'a>(&self, name: &'a str) -> Option<&'a str> {
+        name.strip_prefix(self.base.as_str())
+            .filter(|relative| relative.starts_with('/'))
+    }
+
+    fn relativize(&self, mut metadata: Metadata) -> Option<Metadata> {
+        metadata.name = self.relative(&metadata.name)?.to_string();
+        Some(metadata)
+    }
+
+    fn relativize_all(&self, batch: Vec<Metadata>) -> Vec<Metadata> {
+        batch
+            .into_iter()
+            .filter_map(|metadata| self.relativize(metadata))
+            .collect()
+    }
+}
+
+// the url parser silently drops tabs and newlines, so control characters are rejected
+fn validate_key(key: &str) -> Result<String, Error> {
+    let invalid = || Error::InvalidPath(key.to_string());
+    if key.chars().any(|c| c.is_control()) {
+        return Err(invalid());
+    }
+    let relative = key.trim_start_matches('/');
+    let segments = relative.strip_suffix('/').unwrap_or(relative);
+    if !segments.is_empty()
+        && segments
+            .split('/')
+            .any(|segment| segment.is_empty() || segment == "." || segment == "..")
+    {
+        return Err(invalid());
+    }
+    Ok(format!("/{}", relative))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{block_on, Stub};
+
+    #[test]
+    fn validate() -> anyhow::Result<()> {
+        assert_eq!(validate_key("")?, "/");
+        assert_eq!(validate_key("/")?, "/");
+        assert_eq!(validate_key("foo/bar.txt")?, "/foo/bar.txt");
+        assert_eq!(validate_key("/foo/bar/")?, "/foo/bar/");
+        assert_eq!(validate_key("//foo/..bar")?, "/foo/..bar");
+        assert_eq!(validate_key("%2e%2e/bar")?, "/%2e%2e/bar");
+        assert_eq!(validate_key("50% off?#1.txt")?, "/50% off?#1.txt");
+
+        for key in [
+            "..",
+            "/foo/../bar",
+            "foo/./bar",
+            "foo//bar",
+            "foo/..",
+            "foo/../",
+            "foo\nbar",
+        ] {
+            assert!(
+                matches!(validate_key(key), Err(Error::InvalidPath(_))),
+                "{}",
+                key
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn escaped_key() -> anyhow::Result<()> {
+        let stub = Stub::start(|_| (404, "object not found".to_string()));
+        let client = stub.client();
+        let tenant = client.bucket("default").scoped("/tenants/a/")?;
+        assert!(block_on(tenant.get("50% off?#\\1.txt"))?.is_none());
+        assert!(stub.requests()[0]
+            .path
+            .starts_with("/bus/objects/tenants/a/50%25%20off%3F%23%5C1.txt?"));
+        Ok(())
+    }
+
+    #[test]
+    fn scoped_query() -> anyhow::Result<()> {
+        let stub = Stub::start(|req| {
+            if req.is("POST", "/bus/objects/list") {
+                (
+                    200,
+                    r#"{"hasMore": false, "nextMarker": "", "objects": [
+                    {"name": "/tenants/a/report.txt", "size": 1, "health": 1, "modTime": "2024-06-27T11:56:19Z"},
+                    {"name": "/tenants/a/report/", "size": 0, "health": 1, "modTime": "2024-06-27T11:56:19Z"}]}"#
+                        .to_string(),
+                )
+            } else if req.path.starts_with("/bus/objects/tenants/a/report.txt") {
+                (
+                    200,
+                    r#"{"hasMore": false, "object": {"eTag": "x", "health": 1, "modTime": "2024-06-27T11:56:19Z",
+                    "name": "/tenants/a/report.txt", "size": 1, "metadata": {"owner": "alice"},
+                    "key": "key:aba60a4c1b9ff360214a68f09f890f9afc00d1bf23c8c9435a02311b10ff1d61", "slabs": []}}"#
+                        .to_string(),
+                )
+            } else {
+                (404, "object not found".to_string())
+            }
+        });
+        let client = stub.client();
+        let tenant = client.bucket("default").scoped("/tenants/a/")?;
+        let found: Vec<_> = block_on(
+            tenant
+                .query(
+                    ObjectQuery::new()
+                        .key("report")
+                        .user_metadata("owner", "alice"),
+                )?
+                .try_concat(),
+        )?
+        .into_iter()
+        .map(|m| m.name)
+        .collect();
+        assert_eq!(found, vec!["/report.txt"]);
+
+        let requests = stub.requests();
+        assert!(requests[0].is("POST", "/bus/objects/list"));
+        assert!(requests[0].body.contains(r#""prefix":"/tenants/a/""#));
+        assert!(requests[0].body.contains(r#""substring":"report""#));
+        assert!(requests[1..]
+            .iter()
+            .all(|r| r.path.starts_with("/bus/objects/tenants/a/")));
+        Ok(())
+    }
+
+    #[test]
+    fn scope_root() -> anyhow::Result<()> {
+        let stub = Stub::start(|_| (200, String::new()));
+        let client = stub.client();
+        let bucket = client.bucket("default");
+        let tenant = bucket.scoped("/tenants/a/")?;
+        block_on(async {
+            for handle in [&bucket, &tenant] {
+                for dir in ["", "/"] {
+                    assert!(matches!(
+                        handle.delete_dir(dir).await,
+                        Err(Error::InvalidPrefixOperation(_))
+                    ));
+                }
+                assert!(matches!(
+                    handle.rename("/", "/other/", false).await,
+                    Err(Error::InvalidPrefixOperation(_))
+                ));
+            }
+        });
+        assert!(stub.requests().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn scope() -> anyhow::Result<()> {
+        let root = Scope::root();
+        assert_eq!(root.prefix(), "/");
+        assert_eq!(root.resolve("foo")?, "/foo");
+        assert_eq!(root.resolve_dir("foo")?, "/foo/");
+        assert_eq!(root.resolve_dir("")?, "/");
+        assert_eq!(root.relative("/foo"), Some("/foo"));
+
+        let tenant = root.join("/tenants/a/")?;
+        assert_eq!(tenant.prefix(), "/tenants/a/");
+        assert_eq!(tenant.resolve("docs/file.txt")?, "/tenants/a/docs/file.txt");
+        assert_eq!(tenant.resolve("/")?, "/tenants/a/");
+        assert_eq!(tenant.resolve_dir("docs")?, "/tenants/a/docs/");
+        assert!(tenant.resolve("../b/file.txt").is_err());
+
+        assert_eq!(
+            tenant.relative("/tenants/a/docs/file.txt"),
+            Some("/docs/file.txt")
+        );
+        assert_eq!(tenant.relative("/tenants/a/"), Some("/"));
+        assert_eq!(tenant.relative("/tenants/ab/file.txt"), None);
+        assert_eq!(tenant.relative("/tenants/b/file.txt"), None);
+
+        let nested = tenant.join("docs")?;
+        assert_eq!(nested.resolve("file.txt")?, "/tenants/a/docs/file.txt");
+        assert!(tenant.join("..").is_err());
+        Ok(())
+    }
+}
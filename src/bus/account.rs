@@ -4,6 +4,7 @@ use bigdecimal::BigDecimal;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use std::time::Duration;
+use tokio::time::Instant;
 
 #[derive(Clone)]
 pub struct Api {
@@ -49,6 +50,25 @@ impl Api {
         Ok((resp.account, resp.lock_id))
     }
 
+    // renterd can't extend account locks, the lock is lost once `duration` has passed
+    pub async fn acquire_lock(
+        &self,
+        account_id: &PublicKey,
+        host_key: &PublicKey,
+        exclusive: bool,
+        duration: Duration,
+    ) -> Result<AccountLock, Error> {
+        let expires = Instant::now() + duration;
+        let (account, lock_id) = self.lock(account_id, host_key, exclusive, duration).await?;
+        Ok(AccountLock {
+            account,
+            account_id: account_id.clone(),
+            lock_id,
+            expires,
+            api: Some(self.clone()),
+        })
+    }
+
     pub async fn unlock(&self, account_id: &PublicKey, lock_id: u64) -> Result<(), Error> {
         let req = unlock_req(account_id, lock_id)?;
         let _ = self.inner.send_api_request(req).await?;
@@ -233,6 +253,49 @@ pub struct Account {
     pub requires_sync: bool,
 }
 
+pub struct AccountLock {
+    account: Account,
+    account_id: PublicKey,
+    lock_id: u64,
+    expires: Instant,
+    api: Option<Api>,
+}
+
+impl AccountLock {
+    pub fn account(&self) -> &Account {
+        &self.account
+    }
+
+    pub fn lock_id(&self) -> u64 {
+        self.lock_id
+    }
+
+    pub fn is_lost(&self) -> bool {
+        Instant::now() >= self.expires
+    }
+
+    pub async fn lost(&self) {
+        tokio::time::sleep_until(self.expires).await
+    }
+
+    pub async fn release(mut self) -> Result<(), Error> {
+        match self.api.take() {
+            Some(api) => api.unlock(&self.account_id, self.lock_id).await,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for AccountLock {
+    fn drop(&mut self) {
+        if let Some(api) = self.api.take() {
+            let account_id = self.account_id.clone();
+            let lock_id = self.lock_id;
+            super::release_in_background(async move { api.unlock(&account_id, lock_id).await });
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,17 +1,34 @@
+use crate::bus::object::{Api as ObjectApi, ListOptions, Metadata};
+use crate::bus::setting::{redundancy, Api as SettingApi};
+use crate::bus::stats::{objects::Stats, Api as StatsApi};
 use crate::Error::InvalidDataError;
-use crate::{ApiRequest, ApiRequestBuilder, ClientInner, Error, RequestContent};
+use crate::{
+    ApiRequest, ApiRequestBuilder, ClientInner, Error, RequestContent, DEFAULT_CONCURRENCY,
+    LIST_BATCH_SIZE,
+};
 use chrono::{DateTime, FixedOffset};
+use futures::{StreamExt, TryStreamExt};
+use md5::{Digest, Md5};
 use serde::{Deserialize, Serialize};
+use std::num::NonZeroUsize;
 use std::sync::Arc;
 
 #[derive(Clone)]
 pub struct Api {
     inner: Arc<ClientInner>,
+    object: ObjectApi,
+    setting: SettingApi,
+    stats: StatsApi,
 }
 
 impl Api {
     pub(super) fn new(inner: Arc<ClientInner>) -> Self {
-        Self { inner }
+        Self {
+            object: ObjectApi::new(inner.clone()),
+            setting: SettingApi::new(inner.clone()),
+            stats: StatsApi::new(inner.clone()),
+            inner,
+        }
     }
 
     pub async fn get_all(&self) -> Result<Vec<Bucket>, Error> {
@@ -55,6 +72,121 @@ impl Api {
         let _ = self.inner.send_api_request(req).await?;
         Ok(())
     }
+
+    pub async fn describe<S: AsRef<str>>(&self, name: S) -> Result<Option<Description>, Error> {
+        let bucket = match self.get_by_name(name).await? {
+            Some(bucket) => bucket,
+            None => return Ok(None),
+        };
+        let (stats, redundancy) = futures::try_join!(
+            self.stats.objects(Some(bucket.name.clone())),
+            self.setting.redundancy().get()
+        )?;
+        Ok(Some(Description {
+            bucket,
+            stats,
+            redundancy,
+        }))
+    }
+
+    // the token of a dry run has to be passed back via `PurgeOptions::confirm`,
+    // the purge is refused if the bucket changed since
+    pub async fn purge<S: AsRef<str>>(
+        &self,
+        name: S,
+        options: &PurgeOptions,
+    ) -> Result<PurgeReport, Error> {
+        let name = name.as_ref();
+        if self.get_by_name(name).await?.is_none() {
+            return Err(Error::NotFoundError);
+        }
+
+        // only the objects of this listing are deleted, anything added later keeps the bucket
+        // from being deleted instead
+        let mut fingerprint = Fingerprint::new(name);
+        let mut names = vec![];
+        let mut objects = self.object.list(
+            options.batch_size,
+            &ListOptions::default(),
+            Some(name.to_string()),
+        )?;
+        while let Some(batch) = objects.try_next().await? {
+            for metadata in batch {
+                fingerprint.add(&metadata);
+                names.push(metadata.name);
+            }
+        }
+        let mut report = fingerprint.report();
+        if options.dry_run {
+            return Ok(report);
+        }
+        if options.confirmation.as_deref() != Some(report.token.as_str()) {
+            return Err(Error::ConfirmationMismatch(name.to_string()));
+        }
+
+        let deleted = futures::stream::iter(names)
+            .map(|object| async move {
+                self.object
+                    .delete(&object, Some(name.to_string()), false)
+                    .await?;
+                Ok::<_, Error>(!object.ends_with('/'))
+            })
+            .buffer_unordered(options.concurrency.get())
+            .try_fold(0u64, |deleted, file| async move {
+                Ok(deleted + u64::from(file))
+            })
+            .await?;
+        self.delete(name).await?;
+
+        report.deleted = Some(deleted);
+        Ok(report)
+    }
+}
+
+struct Fingerprint {
+    bucket: String,
+    hasher: Md5,
+    objects: u64,
+    size: u64,
+}
+
+impl Fingerprint {
+    fn new(bucket: &str) -> Self {
+        let mut hasher = Md5::new();
+        hasher.update(bucket.as_bytes());
+        hasher.update([0]);
+        Self {
+            bucket: bucket.to_string(),
+            hasher,
+            objects: 0,
+            size: 0,
+        }
+    }
+
+    fn add(&mut self, metadata: &Metadata) {
+        self.hasher.update(metadata.name.as_bytes());
+        self.hasher.update([0]);
+        self.hasher
+            .update(metadata.etag.as_deref().unwrap_or_default().as_bytes());
+        self.hasher.update([0]);
+        self.hasher.update(metadata.size.to_be_bytes());
+        // directory markers are covered by the token but not counted
+        if !metadata.name.ends_with('/') {
+            self.objects += 1;
+            self.size += metadata.size;
+        }
+    }
+
+    fn report(self) -> PurgeReport {
+        let digest = self.hasher.finalize();
+        PurgeReport {
+            bucket: self.bucket,
+            objects: self.objects,
+            size: self.size,
+            token: hex::encode(&digest[..8]),
+            deleted: None,
+        }
+    }
 }
 
 fn get_all_req() -> ApiRequest {
@@ -109,7 +241,7 @@ struct CreateRequest<'a> {
     policy: &'a Policy,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub struct Bucket {
     pub created_at: DateTime<FixedOffset>,
@@ -117,15 +249,100 @@ pub struct Bucket {
     pub policy: Policy,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub struct Policy {
     pub public_read_access: bool,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Description {
+    pub bucket: Bucket,
+    pub stats: Stats,
+    pub redundancy: redundancy::Settings,
+}
+
+impl Description {
+    pub fn policy(&self) -> &Policy {
+        &self.bucket.policy
+    }
+
+    pub fn size(&self) -> u64 {
+        self.stats.total_objects_size
+    }
+
+    // an estimate, objects uploaded with other settings or packed into partial slabs differ
+    pub fn redundant_size(&self) -> u64 {
+        redundant_size(self.size(), &self.redundancy)
+    }
+}
+
+fn redundant_size(size: u64, redundancy: &redundancy::Settings) -> u64 {
+    if redundancy.min_shards == 0 {
+        return size;
+    }
+    let size = size as u128 * redundancy.total_shards as u128 / redundancy.min_shards as u128;
+    size.try_into().unwrap_or(u64::MAX)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PurgeOptions {
+    pub dry_run: bool,
+    pub confirmation: Option<String>,
+    pub batch_size: NonZeroUsize,
+    pub concurrency: NonZeroUsize,
+}
+
+impl Default for PurgeOptions {
+    fn default() -> Self {
+        Self {
+            dry_run: false,
+            confirmation: None,
+            batch_size: LIST_BATCH_SIZE,
+            concurrency: DEFAULT_CONCURRENCY,
+        }
+    }
+}
+
+impl PurgeOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn confirm<S: Into<String>>(mut self, token: S) -> Self {
+        self.confirmation = Some(token.into());
+        self
+    }
+
+    pub fn batch_size(mut self, batch_size: NonZeroUsize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    pub fn concurrency(mut self, concurrency: NonZeroUsize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PurgeReport {
+    pub bucket: String,
+    pub objects: u64,
+    pub size: u64,
+    pub token: String,
+    pub deleted: Option<u64>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing::{block_on, Stub};
     use crate::RequestType;
     use serde_json::Value;
 
@@ -225,4 +442,127 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn redundancy() {
+        let settings = redundancy::Settings {
+            min_shards: 10,
+            total_shards: 30,
+        };
+        assert_eq!(redundant_size(0, &settings), 0);
+        assert_eq!(redundant_size(5586849, &settings), 16760547);
+        assert_eq!(redundant_size(u64::MAX, &settings), u64::MAX);
+        assert_eq!(
+            redundant_size(
+                1000,
+                &redundancy::Settings {
+                    min_shards: 0,
+                    total_shards: 0,
+                }
+            ),
+            1000
+        );
+    }
+
+    #[test]
+    fn fingerprint() -> anyhow::Result<()> {
+        let json = r#"
+[
+  {
+    "eTag": "d34db33f",
+    "health": 1,
+    "modTime": "2024-01-01T00:00:00Z",
+    "name": "/foo/bar.txt",
+    "size": 100
+  },
+  {
+    "eTag": "c0ffee",
+    "health": 1,
+    "modTime": "2024-01-02T00:00:00Z",
+    "name": "/foo/baz.txt",
+    "size": 23
+  }
+]
+        "#;
+        let objects: Vec<Metadata> = serde_json::from_str(json)?;
+        let token = |bucket: &str, objects: &[Metadata]| {
+            let mut fingerprint = Fingerprint::new(bucket);
+            objects
+                .iter()
+                .for_each(|metadata| fingerprint.add(metadata));
+            fingerprint.report()
+        };
+
+        let report = token("photos", &objects);
+        assert_eq!(report.bucket, "photos");
+        assert_eq!(report.objects, 2);
+        assert_eq!(report.size, 123);
+        assert_eq!(report.token.len(), 16);
+        assert_eq!(report.deleted, None);
+        assert_eq!(report, token("photos", &objects));
+
+        assert_ne!(report.token, token("backups", &objects).token);
+        assert_ne!(report.token, token("photos", &objects[..1]).token);
+        let mut changed = objects.clone();
+        changed[1].etag = Some("decaf".to_string());
+        assert_ne!(report.token, token("photos", &changed).token);
+        Ok(())
+    }
+
+    #[test]
+    fn purge() -> anyhow::Result<()> {
+        let stub = Stub::start(|req| {
+            if req.is("GET", "/bus/bucket/photos") {
+                (
+                    200,
+                    r#"{"createdAt": "2023-09-19T16:03:02.737150758Z", "name": "photos",
+                    "policy": {"publicReadAccess": false}}"#
+                        .to_string(),
+                )
+            } else if req.is("POST", "/bus/objects/list") {
+                (
+                    200,
+                    r#"{"hasMore": false, "nextMarker": "", "objects": [
+                    {"name": "/dir/", "size": 0, "health": 1, "modTime": "2024-06-27T11:56:19Z"},
+                    {"name": "/dir/a.txt", "eTag": "c0ffee", "size": 5, "health": 1, "modTime": "2024-06-27T11:56:19Z"}]}"#
+                        .to_string(),
+                )
+            } else {
+                (200, String::new())
+            }
+        });
+        let client = stub.client();
+        let api = client.bus().bucket();
+
+        let dry_run = block_on(api.purge("photos", &PurgeOptions::new().dry_run(true)))?;
+        assert_eq!(dry_run.objects, 1);
+        assert_eq!(dry_run.size, 5);
+        assert!(matches!(
+            block_on(api.purge("photos", &PurgeOptions::new().confirm("wrong"))),
+            Err(Error::ConfirmationMismatch(_))
+        ));
+        let deletes = |stub: &Stub| {
+            let mut deletes: Vec<_> = stub
+                .requests()
+                .into_iter()
+                .filter(|r| r.method == "DELETE")
+                .map(|r| r.path)
+                .collect();
+            deletes.sort();
+            deletes
+        };
+        assert!(deletes(&stub).is_empty());
+
+        let report = block_on(api.purge("photos", &PurgeOptions::new().confirm(&dry_run.token)))?;
+        assert_eq!(report.deleted, Some(1));
+        assert_eq!(
+            deletes(&stub),
+            vec![
+                "/bus/bucket/photos",
+                "/bus/objects/dir/?bucket=photos&batch=false",
+                "/bus/objects/dir/a.txt?bucket=photos&batch=false",
+            ]
+        );
+        Ok(())
+    }
 }
@@ -1,12 +1,22 @@
 use crate::Error::InvalidDataError;
 use crate::{
     ApiRequest, ApiRequestBuilder, ClientInner, Error, FileContractId, Hash, PublicKey,
-    RequestContent,
+    RequestContent, DEFAULT_CONCURRENCY,
 };
+use futures::channel::oneshot;
+use futures::future::{Either, Shared};
+use futures::FutureExt;
+use futures::{StreamExt, TryStreamExt};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+use std::future::Future;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
 
 #[derive(Clone)]
 pub struct Api {
@@ -61,6 +71,22 @@ impl Api {
         Ok(resp.lock_id)
     }
 
+    // must be called within a tokio runtime
+    pub async fn acquire_lock(
+        &self,
+        contract_id: &FileContractId,
+        duration: Duration,
+        priority: i32,
+    ) -> Result<ContractLock, Error> {
+        let lock_id = self.acquire(contract_id, duration, priority).await?;
+        Ok(ContractLock::new(
+            self.clone(),
+            contract_id.clone(),
+            lock_id,
+            duration,
+        ))
+    }
+
     pub async fn ancestors(
         &self,
         contract_id: &FileContractId,
@@ -117,6 +143,68 @@ impl Api {
             .await?)
     }
 
+    // renterd only returns archived contracts as ancestors of another contract, so the history
+    // after an archived `contract_id` can only be followed up to a renewal that is still active.
+    // If there is none, e.g. because the chain expired or its host was pruned, the lineage is
+    // partial: it holds the contracts renewed into `contract_id`, but neither `contract_id` itself
+    // nor anything after it.
+    pub async fn lineage(&self, contract_id: &FileContractId) -> Result<Lineage, Error> {
+        let head = match self.get_by_id(contract_id).await {
+            Err(Error::NotFoundError) => match self.renewed(contract_id).await {
+                Err(Error::NotFoundError) => None,
+                result => Some(result?),
+            },
+            result => Some(result?),
+        };
+        let lineage = match head {
+            Some(head) => {
+                let ancestors = self.ancestors(&head.id, None).await?;
+                Lineage::new(head, ancestors)
+            }
+            None => {
+                let ancestors = self.ancestors(contract_id, None).await?;
+                // renewals stay with the same host, which narrows down the active contracts
+                let host_key = ancestors.first().map(|a| a.host_key.clone());
+                match self.find_descendant(contract_id, host_key.as_ref()).await? {
+                    Some((head, ancestors)) => Lineage::new(head, ancestors),
+                    None if ancestors.is_empty() => return Err(Error::NotFoundError),
+                    None => Lineage::partial(contract_id, ancestors),
+                }
+            }
+        };
+
+        if lineage.complete && lineage.position(contract_id).is_none() {
+            return Err(Error::UnexpectedResponse(format!(
+                "{} is missing from its own lineage",
+                contract_id
+            )));
+        }
+        Ok(lineage)
+    }
+
+    async fn find_descendant(
+        &self,
+        contract_id: &FileContractId,
+        host_key: Option<&PublicKey>,
+    ) -> Result<Option<(Contract, Vec<ArchivedContract>)>, Error> {
+        let candidates = self
+            .get_all(None)
+            .await?
+            .into_iter()
+            .filter(|contract| host_key.is_none_or(|host_key| &contract.host_key == host_key));
+        let mut candidates = futures::stream::iter(candidates)
+            .map(|contract| async move {
+                let ancestors = self.ancestors(&contract.id, None).await?;
+                Ok::<_, Error>((contract, ancestors))
+            })
+            .buffer_unordered(DEFAULT_CONCURRENCY.get())
+            .try_filter(|(_, ancestors)| {
+                futures::future::ready(ancestors.iter().any(|a| &a.id == contract_id))
+            })
+            .boxed();
+        candidates.try_next().await
+    }
+
     pub async fn create_contract_set<S: AsRef<str>>(
         &self,
         name: S,
@@ -203,6 +291,263 @@ impl Api {
     }
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Lineage {
+    pub contracts: Vec<LineageEntry>,
+    // false if no active contract was found, see `Api::lineage`
+    pub complete: bool,
+}
+
+impl Lineage {
+    fn new(head: Contract, ancestors: Vec<ArchivedContract>) -> Self {
+        let mut lineage = Self::partial(&head.id, ancestors);
+        lineage.contracts.push(LineageEntry::Active(head));
+        lineage.complete = true;
+        lineage
+    }
+
+    fn partial(head: &FileContractId, ancestors: Vec<ArchivedContract>) -> Self {
+        // follow the `renewed_to` links instead of relying on the order of the ancestors
+        let mut by_successor: BTreeMap<FileContractId, ArchivedContract> = ancestors
+            .into_iter()
+            .map(|ancestor| (ancestor.renewed_to.clone(), ancestor))
+            .collect();
+        let mut contracts = vec![];
+        let mut next = head.clone();
+        while let Some(ancestor) = by_successor.remove(&next) {
+            next = ancestor.id.clone();
+            contracts.push(LineageEntry::Archived(ancestor));
+        }
+        contracts.reverse();
+        Self {
+            contracts,
+            complete: false,
+        }
+    }
+
+    pub fn active(&self) -> Option<&Contract> {
+        self.contracts.iter().find_map(|entry| match entry {
+            LineageEntry::Active(contract) => Some(contract),
+            LineageEntry::Archived(_) => None,
+        })
+    }
+
+    pub fn position(&self, contract_id: &FileContractId) -> Option<usize> {
+        self.contracts
+            .iter()
+            .position(|entry| entry.id() == contract_id)
+    }
+
+    pub fn renewals(&self) -> usize {
+        // the last contract of a partial lineage was renewed into the requested one
+        if self.complete {
+            self.contracts.len().saturating_sub(1)
+        } else {
+            self.contracts.len()
+        }
+    }
+
+    pub fn total_spending(&self) -> Spending {
+        self.contracts.iter().map(|entry| entry.spending()).sum()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LineageEntry {
+    Archived(ArchivedContract),
+    Active(Contract),
+}
+
+impl LineageEntry {
+    pub fn id(&self) -> &FileContractId {
+        match self {
+            LineageEntry::Archived(contract) => &contract.id,
+            LineageEntry::Active(contract) => &contract.id,
+        }
+    }
+
+    pub fn host_key(&self) -> &PublicKey {
+        match self {
+            LineageEntry::Archived(contract) => &contract.host_key,
+            LineageEntry::Active(contract) => &contract.host_key,
+        }
+    }
+
+    pub fn state(&self) -> &State {
+        match self {
+            LineageEntry::Archived(contract) => &contract.state,
+            LineageEntry::Active(contract) => &contract.state,
+        }
+    }
+
+    pub fn start_height(&self) -> u64 {
+        match self {
+            LineageEntry::Archived(contract) => contract.start_height,
+            LineageEntry::Active(contract) => contract.start_height,
+        }
+    }
+
+    pub fn window_start(&self) -> u64 {
+        match self {
+            LineageEntry::Archived(contract) => contract.window_start,
+            LineageEntry::Active(contract) => contract.window_start,
+        }
+    }
+
+    pub fn window_end(&self) -> u64 {
+        match self {
+            LineageEntry::Archived(contract) => contract.window_end,
+            LineageEntry::Active(contract) => contract.window_end,
+        }
+    }
+
+    pub fn size(&self) -> u64 {
+        match self {
+            LineageEntry::Archived(contract) => contract.size,
+            LineageEntry::Active(contract) => contract.size,
+        }
+    }
+
+    pub fn spending(&self) -> &Spending {
+        match self {
+            LineageEntry::Archived(contract) => &contract.spending,
+            LineageEntry::Active(contract) => &contract.spending,
+        }
+    }
+
+    pub fn archival_reason(&self) -> Option<&ArchivalReason> {
+        match self {
+            LineageEntry::Archived(contract) => contract.archival_reason.as_ref(),
+            LineageEntry::Active(_) => None,
+        }
+    }
+}
+
+pub struct ContractLock {
+    contract_id: FileContractId,
+    lock_id: u64,
+    lost: Arc<AtomicBool>,
+    lost_signal: Shared<oneshot::Receiver<()>>,
+    held: Option<HeldLock>,
+}
+
+struct HeldLock {
+    api: Api,
+    stop: oneshot::Sender<()>,
+    keep_alive: JoinHandle<()>,
+}
+
+impl ContractLock {
+    fn new(api: Api, contract_id: FileContractId, lock_id: u64, duration: Duration) -> Self {
+        let (stop, stopped) = oneshot::channel();
+        let (lost_sender, lost_signal) = oneshot::channel();
+        let lost = Arc::new(AtomicBool::new(false));
+        let keep_alive = tokio::spawn(keep_alive_lock(
+            api.clone(),
+            contract_id.clone(),
+            lock_id,
+            duration,
+            stopped,
+            lost_sender,
+            lost.clone(),
+        ));
+        Self {
+            contract_id,
+            lock_id,
+            lost,
+            lost_signal: lost_signal.shared(),
+            held: Some(HeldLock {
+                api,
+                stop,
+                keep_alive,
+            }),
+        }
+    }
+
+    pub fn contract_id(&self) -> &FileContractId {
+        &self.contract_id
+    }
+
+    pub fn lock_id(&self) -> u64 {
+        self.lock_id
+    }
+
+    pub fn is_lost(&self) -> bool {
+        self.lost.load(Ordering::Acquire)
+    }
+
+    pub fn lost(&self) -> impl Future<Output = ()> + Send + 'static {
+        let signal = self.lost_signal.clone();
+        async move {
+            if signal.await.is_err() {
+                futures::future::pending::<()>().await
+            }
+        }
+    }
+
+    pub async fn release(mut self) -> Result<(), Error> {
+        match self.held.take() {
+            Some(held) => held.release(&self.contract_id, self.lock_id).await,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for ContractLock {
+    fn drop(&mut self) {
+        if let Some(held) = self.held.take() {
+            let contract_id = self.contract_id.clone();
+            let lock_id = self.lock_id;
+            super::release_in_background(async move { held.release(&contract_id, lock_id).await });
+        }
+    }
+}
+
+impl HeldLock {
+    async fn release(self, contract_id: &FileContractId, lock_id: u64) -> Result<(), Error> {
+        // make sure no renewal is in flight once the lock is released
+        let _ = self.stop.send(());
+        let _ = self.keep_alive.await;
+        self.api.release(contract_id, lock_id).await
+    }
+}
+
+async fn keep_alive_lock(
+    api: Api,
+    contract_id: FileContractId,
+    lock_id: u64,
+    duration: Duration,
+    mut stopped: oneshot::Receiver<()>,
+    lost_sender: oneshot::Sender<()>,
+    lost: Arc<AtomicBool>,
+) {
+    let interval = renewal_interval(duration);
+    let mut expires = Instant::now() + duration;
+    loop {
+        let sleep = Box::pin(tokio::time::sleep(interval));
+        if let Either::Left(_) = futures::future::select(&mut stopped, sleep).await {
+            return;
+        }
+
+        let started = Instant::now();
+        match api.keep_alive(&contract_id, duration, lock_id).await {
+            Ok(()) => expires = started + duration,
+            // retry on the next tick while the lock is still valid
+            Err(_) if Instant::now() + interval < expires => {}
+            Err(_) => {
+                lost.store(true, Ordering::Release);
+                let _ = lost_sender.send(());
+                return;
+            }
+        }
+    }
+}
+
+// several times per lock duration so a failed renewal can be retried in time
+fn renewal_interval(duration: Duration) -> Duration {
+    (duration / 3).max(Duration::from_millis(100))
+}
+
 fn size_req(contract_id: &FileContractId) -> ApiRequest {
     ApiRequestBuilder::get(format!("./bus/contract/{}/size", contract_id)).build()
 }
@@ -428,6 +773,8 @@ pub enum ArchivalReason {
     HostPruned,
     Removed,
     Renewed,
+    #[serde(other)]
+    Unknown,
 }
 
 #[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
@@ -462,6 +809,10 @@ pub struct ArchivedContract {
     pub host_key: PublicKey,
     pub renewed_to: FileContractId,
     pub spending: Spending,
+    #[serde(default)]
+    pub archival_reason: Option<ArchivalReason>,
+    #[serde(default, with = "crate::number_as_string")]
+    pub total_cost: u128,
     pub proof_height: u64,
     pub revision_height: u64,
     pub revision_number: u64,
@@ -472,27 +823,92 @@ pub struct ArchivedContract {
     pub window_end: u64,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub struct Spending {
     #[serde(with = "crate::number_as_string")]
-    uploads: u128,
+    pub uploads: u128,
     #[serde(with = "crate::number_as_string")]
-    downloads: u128,
+    pub downloads: u128,
     #[serde(with = "crate::number_as_string")]
-    fund_account: u128,
+    pub fund_account: u128,
     #[serde(with = "crate::number_as_string")]
-    deletions: u128,
+    pub deletions: u128,
     #[serde(with = "crate::number_as_string")]
-    sector_roots: u128,
+    pub sector_roots: u128,
+}
+
+impl Spending {
+    pub fn get(&self, category: SpendingCategory) -> u128 {
+        match category {
+            SpendingCategory::Uploads => self.uploads,
+            SpendingCategory::Downloads => self.downloads,
+            SpendingCategory::FundAccount => self.fund_account,
+            SpendingCategory::Deletions => self.deletions,
+            SpendingCategory::SectorRoots => self.sector_roots,
+        }
+    }
+
+    pub fn total(&self) -> u128 {
+        self.uploads + self.downloads + self.fund_account + self.deletions + self.sector_roots
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum SpendingCategory {
+    Uploads,
+    Downloads,
+    FundAccount,
+    Deletions,
+    SectorRoots,
+}
+
+impl SpendingCategory {
+    pub const ALL: [SpendingCategory; 5] = [
+        SpendingCategory::Uploads,
+        SpendingCategory::Downloads,
+        SpendingCategory::FundAccount,
+        SpendingCategory::Deletions,
+        SpendingCategory::SectorRoots,
+    ];
+}
+
+impl AddAssign<&Spending> for Spending {
+    fn add_assign(&mut self, other: &Spending) {
+        self.uploads += other.uploads;
+        self.downloads += other.downloads;
+        self.fund_account += other.fund_account;
+        self.deletions += other.deletions;
+        self.sector_roots += other.sector_roots;
+    }
+}
+
+impl Add<&Spending> for Spending {
+    type Output = Spending;
+
+    fn add(mut self, other: &Spending) -> Spending {
+        self += other;
+        self
+    }
+}
+
+impl<'a> Sum<&'a Spending> for Spending {
+    fn sum<I: Iterator<Item = &'a Spending>>(iter: I) -> Self {
+        iter.fold(Spending::default(), |total, spending| total + spending)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing::{block_on, Stub};
     use crate::RequestType;
     use serde_json::Value;
 
+    const CONTRACT_ID: &str =
+        "fcid:d41536902fedd6717e16839df5a6022c1d0663ebc2f44f8ad4a7bb743313dabd";
+
     #[test]
     fn get_all() -> anyhow::Result<()> {
         let req = get_all_req(None);
@@ -1264,4 +1680,242 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn lineage() -> anyhow::Result<()> {
+        let head: Contract = serde_json::from_str(
+            r#"
+ {
+	"id": "fcid:9573152b5a294ef910f08a3f18af8bf7b51a4c6ae108c0bd7c3d973db7d6c89e",
+	"hostIP": "justanotherhost.ddns.net:9882",
+	"hostKey": "ed25519:e156553dd877e99f24a5c02b72d1c1edd75dce76c663f6b939f20d3f7e9f01d9",
+	"siamuxAddr": "justanotherhost.ddns.net:9883",
+	"proofHeight": 0,
+	"revisionHeight": 76518,
+	"revisionNumber": 5,
+	"size": 4194304,
+	"startHeight": 75509,
+	"state": "active",
+	"windowStart": 83573,
+	"windowEnd": 83717,
+	"contractPrice": "200000000000000000000000",
+	"renewedFrom": "fcid:e26dcafdbddcede53cb9d24a03caa4917a8196d3733790389b638af6c9b5564b",
+	"spending": {
+		"uploads": "10",
+		"downloads": "0",
+		"fundAccount": "1000",
+		"deletions": "0",
+		"sectorRoots": "0"
+	},
+	"totalCost": "2614400000000000000000000",
+	"contractSets": null
+}
+        "#,
+        )?;
+        let ancestors: Vec<ArchivedContract> = serde_json::from_str(
+            r#"
+[
+  {
+    "id": "fcid:c92cee85afbf62e6905be22100b47ed5f0db5ad17df49d579053cf69ca217352",
+    "hostKey": "ed25519:e156553dd877e99f24a5c02b72d1c1edd75dce76c663f6b939f20d3f7e9f01d9",
+    "renewedTo": "fcid:e26dcafdbddcede53cb9d24a03caa4917a8196d3733790389b638af6c9b5564b",
+    "spending": {
+      "uploads": "1",
+      "downloads": "2",
+      "fundAccount": "3",
+      "deletions": "4",
+      "sectorRoots": "5"
+    },
+    "proofHeight": 0,
+    "revisionHeight": 61000,
+    "revisionNumber": 12,
+    "size": 8388608,
+    "startHeight": 60000,
+    "state": "complete",
+    "windowStart": 68000,
+    "windowEnd": 68144
+  },
+  {
+    "id": "fcid:e26dcafdbddcede53cb9d24a03caa4917a8196d3733790389b638af6c9b5564b",
+    "hostKey": "ed25519:e156553dd877e99f24a5c02b72d1c1edd75dce76c663f6b939f20d3f7e9f01d9",
+    "renewedTo": "fcid:9573152b5a294ef910f08a3f18af8bf7b51a4c6ae108c0bd7c3d973db7d6c89e",
+    "spending": {
+      "uploads": "100",
+      "downloads": "0",
+      "fundAccount": "0",
+      "deletions": "0",
+      "sectorRoots": "0"
+    },
+    "archivalReason": "renewed",
+    "proofHeight": 0,
+    "revisionHeight": 75509,
+    "revisionNumber": 7,
+    "size": 4194304,
+    "startHeight": 67000,
+    "state": "complete",
+    "windowStart": 75500,
+    "windowEnd": 75644
+  }
+]
+        "#,
+        )?;
+
+        let lineage = Lineage::new(head.clone(), ancestors.clone());
+        assert!(lineage.complete);
+        assert_eq!(lineage.contracts.len(), 3);
+        assert_eq!(lineage.renewals(), 2);
+        assert_eq!(
+            lineage.contracts[0],
+            LineageEntry::Archived(ancestors[0].clone())
+        );
+        assert_eq!(
+            lineage.contracts[1],
+            LineageEntry::Archived(ancestors[1].clone())
+        );
+        assert_eq!(lineage.contracts[2], LineageEntry::Active(head.clone()));
+        assert_eq!(lineage.active(), Some(&head));
+        assert_eq!(lineage.position(&head.renewed_from), Some(1));
+        assert_eq!(lineage.contracts[0].archival_reason(), None);
+        assert_eq!(
+            lineage.contracts[1].archival_reason(),
+            Some(&ArchivalReason::Renewed)
+        );
+        assert_eq!(lineage.contracts[2].archival_reason(), None);
+        assert_eq!(lineage.contracts[0].start_height(), 60000);
+        assert_eq!(lineage.contracts[2].size(), 4194304);
+
+        let total = lineage.total_spending();
+        assert_eq!(total.uploads, 111);
+        assert_eq!(total.downloads, 2);
+        assert_eq!(total.fund_account, 1003);
+        assert_eq!(total.deletions, 4);
+        assert_eq!(total.sector_roots, 5);
+        assert_eq!(total.total(), 1125);
+
+        // ancestors of another chain are ignored
+        let lineage = Lineage::new(head.clone(), ancestors[..1].to_vec());
+        assert_eq!(lineage.contracts, vec![LineageEntry::Active(head)]);
+        Ok(())
+    }
+
+    #[test]
+    fn renewal() {
+        assert_eq!(
+            renewal_interval(Duration::from_secs(30)),
+            Duration::from_secs(10)
+        );
+        assert_eq!(renewal_interval(Duration::ZERO), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn partial_lineage() -> anyhow::Result<()> {
+        const ARCHIVED: &str =
+            "fcid:e26dcafdbddcede53cb9d24a03caa4917a8196d3733790389b638af6c9b5564b";
+        const ANCESTOR: &str =
+            "fcid:c92cee85afbf62e6905be22100b47ed5f0db5ad17df49d579053cf69ca217352";
+        const OTHER: &str = "fcid:9573152b5a294ef910f08a3f18af8bf7b51a4c6ae108c0bd7c3d973db7d6c89e";
+        let stub = Stub::start(|req| {
+            if req.path == format!("/bus/contract/{}/ancestors", ARCHIVED) {
+                (
+                    200,
+                    format!(
+                        r#"[{{"id": "{}", "hostKey": "ed25519:e156553dd877e99f24a5c02b72d1c1edd75dce76c663f6b939f20d3f7e9f01d9",
+                        "renewedTo": "{}", "spending": {{"uploads": "1", "downloads": "2", "fundAccount": "3",
+                        "deletions": "4", "sectorRoots": "5"}}, "proofHeight": 0, "revisionHeight": 61000,
+                        "revisionNumber": 12, "size": 8388608, "startHeight": 60000, "state": "complete",
+                        "windowStart": 68000, "windowEnd": 68144}}]"#,
+                        ANCESTOR, ARCHIVED
+                    ),
+                )
+            } else if req.path == "/bus/contracts" {
+                // an active contract with another host can't be a renewal
+                (
+                    200,
+                    format!(
+                        r#"[{{"id": "{}", "hostIP": "host:9882", "hostKey": "ed25519:dfb16d76de07c537ad62647b39cba0497a6e339dfb1644bd8f8cda95893b1f16",
+                        "siamuxAddr": "host:9883", "proofHeight": 0, "revisionHeight": 76518, "revisionNumber": 5,
+                        "size": 0, "startHeight": 75509, "state": "active", "windowStart": 83573, "windowEnd": 83717,
+                        "contractPrice": "0", "renewedFrom": "fcid:0000000000000000000000000000000000000000000000000000000000000000",
+                        "spending": {{"uploads": "0", "downloads": "0", "fundAccount": "0", "deletions": "0", "sectorRoots": "0"}},
+                        "totalCost": "0", "contractSets": null}}]"#,
+                        OTHER
+                    ),
+                )
+            } else {
+                (404, "contract not found".to_string())
+            }
+        });
+        let client = stub.client();
+        let lineage = block_on(
+            client
+                .bus()
+                .contract()
+                .lineage(&FileContractId::try_from(ARCHIVED)?),
+        )?;
+        assert!(!lineage.complete);
+        assert_eq!(lineage.contracts.len(), 1);
+        assert_eq!(
+            lineage.contracts[0].id(),
+            &FileContractId::try_from(ANCESTOR)?
+        );
+        assert_eq!(lineage.renewals(), 1);
+        assert_eq!(lineage.active(), None);
+        assert_eq!(lineage.total_spending().fund_account, 3);
+        assert!(!stub
+            .requests()
+            .iter()
+            .any(|r| r.path.starts_with(&format!("/bus/contract/{}", OTHER))));
+        Ok(())
+    }
+
+    #[test]
+    fn kept_lock() -> anyhow::Result<()> {
+        let stub = Stub::start(|_| (200, String::new()));
+        let client = stub.client();
+        block_on(async {
+            let lock = ContractLock::new(
+                client.bus().contract().clone(),
+                FileContractId::try_from(CONTRACT_ID)?,
+                7,
+                Duration::from_millis(300),
+            );
+            tokio::time::sleep(Duration::from_millis(350)).await;
+            assert!(!lock.is_lost());
+            lock.release().await?;
+            Ok::<_, anyhow::Error>(())
+        })?;
+
+        let requests = stub.requests();
+        let keep_alive = format!("/bus/contract/{}/keepalive", CONTRACT_ID);
+        assert!(requests.iter().filter(|r| r.path == keep_alive).count() >= 2);
+        assert_eq!(
+            requests.last().map(|r| r.path.clone()),
+            Some(format!("/bus/contract/{}/release", CONTRACT_ID))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn lost_lock() -> anyhow::Result<()> {
+        // every renewal fails
+        let stub = Stub::start(|_| (500, "internal error".to_string()));
+        let client = stub.client();
+        block_on(async {
+            let contract_id = FileContractId::try_from(CONTRACT_ID)?;
+            let lock = ContractLock::new(
+                client.bus().contract().clone(),
+                contract_id.clone(),
+                7,
+                Duration::from_millis(300),
+            );
+            assert_eq!(lock.contract_id(), &contract_id);
+            assert_eq!(lock.lock_id(), 7);
+            assert!(!lock.is_lost());
+
+            tokio::time::timeout(Duration::from_secs(5), lock.lost()).await?;
+            assert!(lock.is_lost());
+            assert!(lock.release().await.is_err());
+            Ok(())
+        })
+    }
 }
@@ -512,6 +512,23 @@ pub struct UsabilityBreakDown {
     pub not_completing_scan: bool,
 }
 
+impl UsabilityBreakDown {
+    pub fn reasons(&self) -> impl Iterator<Item = &'static str> {
+        [
+            (self.blocked, "blocked"),
+            (self.offline, "offline"),
+            (self.low_score, "low score"),
+            (self.redundant_ip, "redundant ip"),
+            (self.gouging, "gouging"),
+            (self.not_accepting_contracts, "not accepting contracts"),
+            (self.not_announced, "not announced"),
+            (self.not_completing_scan, "not completing scan"),
+        ]
+        .into_iter()
+        .filter_map(|(flagged, reason)| flagged.then_some(reason))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
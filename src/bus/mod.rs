@@ -32,6 +32,7 @@ use crate::bus::txpool::Api as TxpoolApi;
 use crate::bus::wallet::Api as WalletApi;
 use crate::bus::webhook::Api as WebhookApi;
 use crate::{ClientInner, Error};
+use std::future::Future;
 use std::sync::Arc;
 
 #[derive(Clone)]
@@ -140,3 +141,12 @@ impl Bus {
         &self.webhook
     }
 }
+
+// used by the lock guards on drop, without a runtime the lock is left to time out
+fn release_in_background<F: Future<Output = Result<(), Error>> + Send + 'static>(release: F) {
+    if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+        runtime.spawn(async move {
+            let _ = release.await;
+        });
+    }
+}
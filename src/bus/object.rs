@@ -1,14 +1,15 @@
 use crate::Error::InvalidDataError;
 use crate::{
-    encode_object_path, ApiRequest, ApiRequestBuilder, ClientInner, Error, Percentage,
-    RequestContent,
+    encode_object_path, ApiRequest, ApiRequestBuilder, ClientInner, EncryptionKey, Error,
+    FileContractId, Hash, Percentage, PublicKey, RequestContent, DEFAULT_CONCURRENCY,
+    LIST_BATCH_SIZE,
 };
+use bigdecimal::BigDecimal;
 use chrono::{DateTime, FixedOffset};
 use either::Either;
-use futures::{StreamExt, TryStream};
+use futures::{StreamExt, TryStream, TryStreamExt};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::num::NonZeroUsize;
 use std::sync::Arc;
 
@@ -34,7 +35,17 @@ impl Api {
         Ok(
             match self
                 .inner
-                .send_api_request_optional(get_req(path, bucket, prefix, offset, marker, limit))
+                .send_api_request_optional(get_req(
+                    path,
+                    bucket,
+                    &ListOptions {
+                        prefix,
+                        ..Default::default()
+                    },
+                    offset,
+                    marker,
+                    limit,
+                ))
                 .await?
             {
                 Some(resp) => {
@@ -54,22 +65,29 @@ impl Api {
         &self,
         path: S,
         batch_size: NonZeroUsize,
-        prefix: Option<String>,
+        options: &ListOptions,
         bucket: Option<String>,
     ) -> Result<
         Option<Either<Object, impl TryStream<Ok = Vec<Metadata>, Error = Error> + Send + Unpin>>,
         Error,
     > {
+        if options.delimiter {
+            return Err(Error::InvalidListOptions(
+                "`delimiter` only applies to `list`, `get_stream` always lists a single directory"
+                    .to_string(),
+            ));
+        }
         let path = path.to_string();
         let batch_size = batch_size.get();
         let inner = self.inner.clone();
+        let options = options.clone();
 
         let (objects, has_more) = match _get(
             &inner,
             path.as_str(),
             bucket.clone(),
-            prefix.clone(),
-            Some(0),
+            &options,
+            None,
             None,
             Some(batch_size),
         )
@@ -91,24 +109,28 @@ impl Api {
             }
         };
 
-        let initial_state = (objects, has_more, 0usize);
+        // the last name only marks the position in a listing sorted by name
+        let by_name = matches!(options.sort_by, None | Some(SortBy::Name));
+        let initial_state = (objects, has_more, None::<String>, 0);
 
         Ok(Some(Either::Right(
             futures::stream::try_unfold(initial_state, move |state| {
                 let inner = inner.clone();
                 let path = path.clone();
-                let prefix = prefix.clone();
+                let options = options.clone();
                 let bucket = bucket.clone();
 
                 async move {
                     let objects = state.0;
                     let has_more = state.1;
-                    let mut offset = state.2;
+                    let marker = state.2;
+                    let offset = state.3;
 
                     if let Some(objects) = objects {
                         // initial objects not yet returned
-                        offset += objects.len();
-                        return Ok(Some((objects, (None, has_more, offset))));
+                        let marker = objects.last().map(|m| m.name.clone());
+                        let offset = offset + objects.len();
+                        return Ok(Some((objects, (None, has_more, marker, offset))));
                     }
 
                     if !has_more {
@@ -116,13 +138,20 @@ impl Api {
                         return Ok(None);
                     }
 
+                    // when sorted by name, continue after the last entry seen instead of at an
+                    // offset, so entries added or removed in the meantime don't shift the pages
+                    let (offset_param, marker) = if by_name {
+                        (None, marker)
+                    } else {
+                        (Some(offset), None)
+                    };
                     match _get(
                         &inner,
                         path.as_str(),
                         bucket.clone(),
-                        prefix.clone(),
-                        Some(offset),
-                        None,
+                        &options,
+                        offset_param,
+                        marker,
                         Some(batch_size),
                     )
                     .await?
@@ -131,8 +160,9 @@ impl Api {
                             if objects.is_empty() {
                                 Ok(None)
                             } else {
-                                offset += objects.len();
-                                Ok(Some((objects, (None, has_more, offset))))
+                                let marker = objects.last().map(|m| m.name.clone());
+                                let offset = offset + objects.len();
+                                Ok(Some((objects, (None, has_more, marker, offset))))
                             }
                         }
                         _ => Err(Error::UnexpectedResponse(
@@ -148,16 +178,17 @@ impl Api {
     pub fn list(
         &self,
         batch_size: NonZeroUsize,
-        prefix: Option<String>,
+        options: &ListOptions,
         bucket: Option<String>,
     ) -> Result<impl TryStream<Ok = Vec<Metadata>, Error = Error> + Send + Unpin, Error> {
         let inner = self.inner.clone();
         let batch_size = batch_size.get();
+        let options = options.clone();
         let initial_state = (true, None);
 
         Ok(futures::stream::try_unfold(initial_state, move |state| {
             let inner = inner.clone();
-            let prefix = prefix.clone();
+            let options = options.clone();
             let bucket = bucket.clone();
 
             async move {
@@ -170,7 +201,7 @@ impl Api {
                 let (objects, has_more, next_marker) = {
                     let resp: ListResponse = inner
                         .send_api_request(list_req(
-                            prefix.clone(),
+                            &options,
                             bucket.clone(),
                             next_marker,
                             batch_size,
@@ -191,7 +222,18 @@ impl Api {
         .boxed())
     }
 
-    //todo: implement PUT /objects/*key function
+    pub async fn add_object<S: AsRef<str>>(
+        &self,
+        path: S,
+        bucket: Option<String>,
+        object: &NewObject,
+    ) -> Result<(), Error> {
+        let _ = self
+            .inner
+            .send_api_request(add_object_req(path, bucket, object)?)
+            .await?;
+        Ok(())
+    }
 
     pub async fn delete<S: AsRef<str>>(
         &self,
@@ -220,12 +262,58 @@ impl Api {
                 source_bucket,
                 destination_path,
                 destination_bucket,
+                None,
+                None,
             )?)
             .await?;
         //todo: check if renterd actually responds with content here contrary to the docs
         Ok(())
     }
 
+    // renterd has no dedicated endpoint for this, the object is copied onto itself
+    pub async fn update_metadata<S: AsRef<str>>(
+        &self,
+        path: S,
+        bucket: String,
+        update: &MetadataUpdate,
+    ) -> Result<MetadataUpdateMethod, Error> {
+        let path = path.as_ref();
+        if path.is_empty() || path.ends_with('/') {
+            return Err(Error::InvalidObject(format!(
+                "`{}` is not a valid object path",
+                path
+            )));
+        }
+        update.validate()?;
+
+        let object = match self
+            .get(path, Some(bucket.clone()), None, None, None, None)
+            .await?
+        {
+            Some(Either::Left(object)) => object,
+            Some(Either::Right(_)) | None => return Err(Error::NotFoundError),
+        };
+        let (mime_type, user_metadata) = update.apply(&object);
+        if mime_type == object.metadata.mime_type
+            && user_metadata == object.user_metadata.unwrap_or_default()
+        {
+            return Ok(MetadataUpdateMethod::Unchanged);
+        }
+
+        let _ = self
+            .inner
+            .send_api_request(copy_req(
+                path.to_string(),
+                bucket.clone(),
+                path.to_string(),
+                bucket,
+                mime_type,
+                Some(user_metadata),
+            )?)
+            .await?;
+        Ok(MetadataUpdateMethod::SelfCopy)
+    }
+
     pub async fn rename(
         &self,
         from: String,
@@ -241,6 +329,214 @@ impl Api {
         Ok(())
     }
 
+    pub async fn copy_prefix<S: AsRef<str>, D: AsRef<str>>(
+        &self,
+        source_prefix: S,
+        source_bucket: String,
+        destination_prefix: D,
+        destination_bucket: String,
+        options: &PrefixOptions,
+    ) -> Result<PrefixReport, Error> {
+        self.transfer_prefix(
+            source_prefix.as_ref(),
+            source_bucket,
+            destination_prefix.as_ref(),
+            destination_bucket,
+            options,
+            false,
+        )
+        .await
+    }
+
+    pub async fn move_prefix<S: AsRef<str>, D: AsRef<str>>(
+        &self,
+        source_prefix: S,
+        source_bucket: String,
+        destination_prefix: D,
+        destination_bucket: String,
+        options: &PrefixOptions,
+    ) -> Result<PrefixReport, Error> {
+        self.transfer_prefix(
+            source_prefix.as_ref(),
+            source_bucket,
+            destination_prefix.as_ref(),
+            destination_bucket,
+            options,
+            true,
+        )
+        .await
+    }
+
+    // `/foo` doesn't match `/foobar`, the root is refused so a bucket isn't emptied by accident
+    pub async fn delete_prefix<S: AsRef<str>>(
+        &self,
+        prefix: S,
+        bucket: Option<String>,
+    ) -> Result<(), Error> {
+        let prefix = normalize_prefix(prefix.as_ref());
+        if prefix == "/" {
+            return Err(Error::InvalidPrefixOperation(
+                "refusing to delete every object in the bucket".to_string(),
+            ));
+        }
+        self.delete(prefix, bucket, true).await
+    }
+
+    async fn transfer_prefix(
+        &self,
+        source_prefix: &str,
+        source_bucket: String,
+        destination_prefix: &str,
+        destination_bucket: String,
+        options: &PrefixOptions,
+        remove_source: bool,
+    ) -> Result<PrefixReport, Error> {
+        let source_prefix = normalize_prefix(source_prefix);
+        let destination_prefix = normalize_prefix(destination_prefix);
+        if source_bucket == destination_bucket
+            && (source_prefix.starts_with(&destination_prefix)
+                || destination_prefix.starts_with(&source_prefix))
+        {
+            return Err(Error::InvalidPrefixOperation(format!(
+                "`{}` and `{}` overlap in bucket `{}`",
+                source_prefix, destination_prefix, source_bucket
+            )));
+        }
+
+        let sources = self
+            .list_all(source_prefix.clone(), Some(source_bucket.clone()))
+            .await?;
+        let existing: BTreeSet<String> = self
+            .list_all(destination_prefix.clone(), Some(destination_bucket.clone()))
+            .await?
+            .into_iter()
+            .map(|metadata| metadata.name)
+            .collect();
+
+        let mut report = PrefixReport::default();
+        for metadata in sources {
+            let destination =
+                match destination_key(&metadata.name, &source_prefix, &destination_prefix) {
+                    Some(destination) => destination,
+                    None => continue,
+                };
+            if existing.contains(&destination) {
+                match options.conflict {
+                    ConflictPolicy::Skip => {
+                        report.skipped.push(metadata.name);
+                        continue;
+                    }
+                    ConflictPolicy::Fail => return Err(Error::ObjectExists(destination)),
+                    ConflictPolicy::Overwrite => {}
+                }
+            }
+            report.planned.push(PrefixEntry {
+                source: metadata.name,
+                destination,
+                size: metadata.size,
+            });
+        }
+
+        if options.dry_run || report.planned.is_empty() {
+            return Ok(report);
+        }
+
+        let rename = remove_source && source_bucket == destination_bucket;
+        if rename && report.skipped.is_empty() {
+            // nothing has to stay behind, so renterd can move the whole prefix atomically
+            match self
+                .rename(
+                    source_prefix.clone(),
+                    destination_prefix,
+                    source_bucket,
+                    options.conflict == ConflictPolicy::Overwrite,
+                    RenameMode::Multi,
+                )
+                .await
+            {
+                Ok(()) => {
+                    for entry in &report.planned {
+                        report.copied.push(entry.destination.clone());
+                        report.deleted.push(entry.source.clone());
+                    }
+                }
+                Err(err) => report.failed.push((source_prefix, err)),
+            }
+            return Ok(report);
+        }
+
+        // the transfers own their data, borrowing `report.planned` here would make the
+        // returned future `Send` only for a specific lifetime
+        let results: Vec<_> = futures::stream::iter(report.planned.clone())
+            .map(|entry| {
+                let api = self.clone();
+                let source_bucket = source_bucket.clone();
+                let destination_bucket = destination_bucket.clone();
+                async move {
+                    if rename {
+                        let renamed = api
+                            .rename(
+                                entry.source.clone(),
+                                entry.destination.clone(),
+                                source_bucket,
+                                false,
+                                RenameMode::Single,
+                            )
+                            .await;
+                        let deleted = renamed.is_ok().then_some(Ok(()));
+                        return (entry, renamed, deleted);
+                    }
+                    let copied = api
+                        .copy(
+                            entry.source.clone(),
+                            source_bucket.clone(),
+                            entry.destination.clone(),
+                            destination_bucket,
+                        )
+                        .await;
+                    let deleted = match &copied {
+                        Ok(()) if remove_source => {
+                            Some(api.delete(&entry.source, Some(source_bucket), false).await)
+                        }
+                        _ => None,
+                    };
+                    (entry, copied, deleted)
+                }
+            })
+            .buffer_unordered(options.concurrency.get())
+            .collect()
+            .await;
+
+        for (entry, copied, deleted) in results {
+            match copied {
+                Ok(()) => report.copied.push(entry.destination),
+                Err(err) => report.failed.push((entry.source.clone(), err)),
+            }
+            match deleted {
+                Some(Ok(())) => report.deleted.push(entry.source),
+                Some(Err(err)) => report.failed.push((entry.source, err)),
+                None => {}
+            }
+        }
+        Ok(report)
+    }
+
+    async fn list_all(
+        &self,
+        prefix: String,
+        bucket: Option<String>,
+    ) -> Result<Vec<Metadata>, Error> {
+        let batches: Vec<Vec<Metadata>> = self
+            .list(LIST_BATCH_SIZE, &ListOptions::new().prefix(prefix), bucket)?
+            .try_collect()
+            .await?;
+        Ok(batches
+            .into_iter()
+            .flatten()
+            .filter(|metadata| !metadata.name.ends_with('/'))
+            .collect())
+    }
+
     pub async fn search(
         &self,
         key: Option<String>,
@@ -255,20 +551,202 @@ impl Api {
             .json()
             .await?)
     }
+
+    pub fn search_stream(
+        &self,
+        batch_size: NonZeroUsize,
+        key: Option<String>,
+        bucket: Option<String>,
+    ) -> Result<impl TryStream<Ok = Vec<Metadata>, Error = Error> + Send + Unpin, Error> {
+        let inner = self.inner.clone();
+        let batch_size = batch_size.get();
+        let initial_state = (true, 0usize);
+
+        Ok(futures::stream::try_unfold(initial_state, move |state| {
+            let inner = inner.clone();
+            let key = key.clone();
+            let bucket = bucket.clone();
+
+            async move {
+                let (has_more, offset) = state;
+                if !has_more {
+                    return Ok(None);
+                }
+
+                let objects: Vec<Metadata> = inner
+                    .send_api_request(search_req(key, bucket, Some(offset), Some(batch_size)))
+                    .await?
+                    .json()
+                    .await?;
+
+                if objects.is_empty() {
+                    return Ok(None);
+                }
+
+                // the search endpoint doesn't report `hasMore`, a short page is the last one
+                let has_more = objects.len() >= batch_size;
+                let offset = offset + objects.len();
+                Ok(Some((objects, (has_more, offset))))
+            }
+        })
+        .boxed())
+    }
+
+    // only the key is matched by renterd, user metadata costs a request per candidate
+    pub fn query(
+        &self,
+        query: ObjectQuery,
+        bucket: Option<String>,
+    ) -> Result<impl TryStream<Ok = Vec<Metadata>, Error = Error> + Send + Unpin, Error> {
+        let api = self.clone();
+        let query = Arc::new(query);
+        let batches = match &query.prefix {
+            Some(prefix) => {
+                let options = ListOptions {
+                    prefix: Some(prefix.clone()),
+                    substring: query.key.clone(),
+                    ..Default::default()
+                };
+                self.list(LIST_BATCH_SIZE, &options, bucket.clone())?
+                    .into_stream()
+                    .boxed()
+            }
+            None => self
+                .search_stream(LIST_BATCH_SIZE, query.key.clone(), bucket.clone())?
+                .into_stream()
+                .boxed(),
+        };
+
+        Ok(batches
+            .and_then(move |batch| {
+                let api = api.clone();
+                let query = query.clone();
+                let bucket = bucket.clone();
+                async move { api.filter_batch(batch, query, bucket).await }
+            })
+            .try_filter(|batch| futures::future::ready(!batch.is_empty()))
+            .boxed())
+    }
+
+    async fn filter_batch(
+        &self,
+        batch: Vec<Metadata>,
+        query: Arc<ObjectQuery>,
+        bucket: Option<String>,
+    ) -> Result<Vec<Metadata>, Error> {
+        let candidates: Vec<Metadata> = batch.into_iter().filter(|m| query.matches(m)).collect();
+        if query.user_metadata.is_empty() {
+            return Ok(candidates);
+        }
+
+        futures::stream::iter(candidates)
+            .map(|metadata| {
+                let api = self.clone();
+                let query = query.clone();
+                let bucket = bucket.clone();
+                async move {
+                    let object = api
+                        .get(&metadata.name, bucket, None, None, None, None)
+                        .await?;
+                    Ok::<_, Error>(match object {
+                        Some(Either::Left(object)) if query.matches_user_metadata(&object) => {
+                            Some(metadata)
+                        }
+                        _ => None,
+                    })
+                }
+            })
+            .buffered(DEFAULT_CONCURRENCY.get())
+            .try_filter_map(|metadata| futures::future::ready(Ok(metadata)))
+            .try_collect()
+            .await
+    }
+}
+
+pub(crate) fn normalize_prefix(prefix: &str) -> String {
+    let prefix = prefix.trim_matches('/');
+    if prefix.is_empty() {
+        "/".to_string()
+    } else {
+        format!("/{}/", prefix)
+    }
+}
+
+fn destination_key(name: &str, source_prefix: &str, destination_prefix: &str) -> Option<String> {
+    name.strip_prefix(source_prefix)
+        .filter(|relative| !relative.is_empty())
+        .map(|relative| format!("{}{}", destination_prefix, relative))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    Skip,
+    Overwrite,
+    Fail,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrefixOptions {
+    pub dry_run: bool,
+    pub conflict: ConflictPolicy,
+    pub concurrency: NonZeroUsize,
+}
+
+impl Default for PrefixOptions {
+    fn default() -> Self {
+        Self {
+            dry_run: false,
+            conflict: ConflictPolicy::Fail,
+            concurrency: DEFAULT_CONCURRENCY,
+        }
+    }
+}
+
+impl PrefixOptions {
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn conflict(mut self, conflict: ConflictPolicy) -> Self {
+        self.conflict = conflict;
+        self
+    }
+
+    pub fn concurrency(mut self, concurrency: NonZeroUsize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrefixEntry {
+    pub source: String,
+    pub destination: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct PrefixReport {
+    pub planned: Vec<PrefixEntry>,
+    pub copied: Vec<String>,
+    pub deleted: Vec<String>,
+    pub skipped: Vec<String>,
+    pub failed: Vec<(String, Error)>,
 }
 
 async fn _get<S: AsRef<str>>(
     inner: &ClientInner,
     path: S,
     bucket: Option<String>,
-    prefix: Option<String>,
+    options: &ListOptions,
     offset: Option<usize>,
     marker: Option<String>,
     limit: Option<usize>,
 ) -> Result<Option<Either<Object, (Vec<Metadata>, bool)>>, Error> {
     Ok(
         match inner
-            .send_api_request_optional(get_req(path, bucket, prefix, offset, marker, limit))
+            .send_api_request_optional(get_req(path, bucket, options, offset, marker, limit))
             .await?
         {
             Some(resp) => {
@@ -285,17 +763,21 @@ async fn _get<S: AsRef<str>>(
 }
 
 fn list_req(
-    prefix: Option<String>,
+    options: &ListOptions,
     bucket: Option<String>,
     marker: Option<String>,
     limit: usize,
 ) -> Result<ApiRequest, Error> {
     let content = Some(RequestContent::Json(
         serde_json::to_value(ListRequest {
-            prefix,
+            prefix: options.prefix.clone(),
             marker,
             bucket,
             limit,
+            sort_by: options.sort_by,
+            sort_dir: options.sort_dir,
+            substring: options.substring.clone(),
+            delimiter: options.delimiter.then(|| "/".to_string()),
         })
         .map_err(|e| InvalidDataError(e.into()))?,
     ));
@@ -315,6 +797,93 @@ struct ListRequest {
     prefix: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     marker: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sort_by: Option<SortBy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sort_dir: Option<SortDir>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    substring: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    delimiter: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ListOptions {
+    pub prefix: Option<String>,
+    // renterd only accepts a marker for listings sorted by name, `get_stream` pages through any
+    // other order by offset and can skip or repeat entries if the directory changes in between
+    pub sort_by: Option<SortBy>,
+    pub sort_dir: Option<SortDir>,
+    pub substring: Option<String>,
+    // only applies to `list`, `get_stream` rejects it since it always lists a single directory
+    pub delimiter: bool,
+}
+
+impl ListOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn prefix<S: Into<String>>(mut self, prefix: S) -> Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    pub fn sort_by(mut self, sort_by: SortBy) -> Self {
+        self.sort_by = Some(sort_by);
+        self
+    }
+
+    pub fn sort_dir(mut self, sort_dir: SortDir) -> Self {
+        self.sort_dir = Some(sort_dir);
+        self
+    }
+
+    pub fn substring<S: Into<String>>(mut self, substring: S) -> Self {
+        self.substring = Some(substring.into());
+        self
+    }
+
+    pub fn delimiter(mut self, delimiter: bool) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum SortBy {
+    Name,
+    Size,
+    Health,
+    ModTime,
+}
+
+impl SortBy {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SortBy::Name => "name",
+            SortBy::Size => "size",
+            SortBy::Health => "health",
+            SortBy::ModTime => "modTime",
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum SortDir {
+    Asc,
+    Desc,
+}
+
+impl SortDir {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SortDir::Asc => "asc",
+            SortDir::Desc => "desc",
+        }
+    }
 }
 
 #[derive(Deserialize)]
@@ -348,6 +917,116 @@ fn search_req(
         .build()
 }
 
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ObjectQuery {
+    pub key: Option<String>,
+    pub prefix: Option<String>,
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    pub modified_after: Option<DateTime<FixedOffset>>,
+    pub modified_before: Option<DateTime<FixedOffset>>,
+    pub health_below: Option<BigDecimal>,
+    pub mime_type: Option<String>,
+    pub user_metadata: BTreeMap<String, String>,
+}
+
+impl ObjectQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn key<S: Into<String>>(mut self, key: S) -> Self {
+        self.key = Some(key.into());
+        self
+    }
+
+    // with a prefix the objects are listed instead of searched, so nothing outside of it is
+    // requested
+    pub fn prefix<S: Into<String>>(mut self, prefix: S) -> Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    pub fn min_size(mut self, min_size: u64) -> Self {
+        self.min_size = Some(min_size);
+        self
+    }
+
+    pub fn max_size(mut self, max_size: u64) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    pub fn modified_after<T: Into<DateTime<FixedOffset>>>(mut self, time: T) -> Self {
+        self.modified_after = Some(time.into());
+        self
+    }
+
+    pub fn modified_before<T: Into<DateTime<FixedOffset>>>(mut self, time: T) -> Self {
+        self.modified_before = Some(time.into());
+        self
+    }
+
+    pub fn health_below(mut self, health: BigDecimal) -> Self {
+        self.health_below = Some(health);
+        self
+    }
+
+    // an exact mime type or a wildcard like `image/*`
+    pub fn mime_type<S: Into<String>>(mut self, mime_type: S) -> Self {
+        self.mime_type = Some(mime_type.into());
+        self
+    }
+
+    pub fn user_metadata<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.user_metadata.insert(key.into(), value.into());
+        self
+    }
+
+    // user metadata isn't part of `Metadata` and is not checked
+    pub fn matches(&self, metadata: &Metadata) -> bool {
+        self.min_size.is_none_or(|min| metadata.size >= min)
+            && self.max_size.is_none_or(|max| metadata.size <= max)
+            && self
+                .modified_after
+                .is_none_or(|after| metadata.mod_time >= after)
+            && self
+                .modified_before
+                .is_none_or(|before| metadata.mod_time < before)
+            && self
+                .health_below
+                .as_ref()
+                .is_none_or(|health| metadata.health.as_decimal() < health)
+            && self.mime_type.as_ref().is_none_or(|wanted| {
+                metadata
+                    .mime_type
+                    .as_ref()
+                    .is_some_and(|mime_type| mime_type_matches(wanted, mime_type))
+            })
+    }
+
+    pub fn matches_user_metadata(&self, object: &Object) -> bool {
+        self.user_metadata.iter().all(|(key, value)| {
+            object
+                .user_metadata
+                .as_ref()
+                .and_then(|metadata| metadata.get(key))
+                .is_some_and(|v| v == value)
+        })
+    }
+}
+
+fn mime_type_matches(wanted: &str, mime_type: &str) -> bool {
+    // ignore parameters like `; charset=utf-8`
+    let mime_type = mime_type.split(';').next().unwrap_or_default().trim();
+    match wanted.strip_suffix("/*") {
+        Some(kind) => mime_type
+            .split_once('/')
+            .is_some_and(|(k, _)| k.eq_ignore_ascii_case(kind)),
+        None => mime_type.eq_ignore_ascii_case(wanted),
+    }
+}
+
 fn rename_req(
     from: String,
     to: String,
@@ -392,6 +1071,8 @@ fn copy_req(
     source_bucket: String,
     destination_path: String,
     destination_bucket: String,
+    mime_type: Option<String>,
+    metadata: Option<BTreeMap<String, String>>,
 ) -> Result<ApiRequest, Error> {
     let content = Some(RequestContent::Json(
         serde_json::to_value(CopyRequest {
@@ -399,6 +1080,8 @@ fn copy_req(
             source_path,
             destination_bucket,
             destination_path,
+            mime_type,
+            metadata,
         })
         .map_err(|e| InvalidDataError(e.into()))?,
     ));
@@ -414,7 +1097,256 @@ struct CopyRequest {
     source_path: String,
     destination_bucket: String,
     destination_path: String,
-    //todo: clarify use of `mimeType` and `metadata` fields
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mime_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<BTreeMap<String, String>>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MetadataUpdate {
+    mime_type: Option<String>,
+    clear: bool,
+    set: BTreeMap<String, String>,
+    remove: BTreeSet<String>,
+}
+
+impl MetadataUpdate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn mime_type<S: Into<String>>(mut self, mime_type: S) -> Self {
+        self.mime_type = Some(mime_type.into());
+        self
+    }
+
+    pub fn clear(mut self) -> Self {
+        self.clear = true;
+        self
+    }
+
+    pub fn set<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        let key = key.into();
+        self.remove.remove(&key);
+        self.set.insert(key, value.into());
+        self
+    }
+
+    pub fn remove<K: Into<String>>(mut self, key: K) -> Self {
+        let key = key.into();
+        self.set.remove(&key);
+        self.remove.insert(key);
+        self
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        if let Some(mime_type) = &self.mime_type {
+            validate_mime_type(mime_type)?;
+        }
+        if self.set.keys().any(|k| k.is_empty()) {
+            return Err(Error::InvalidObject(
+                "user metadata keys must not be empty".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn apply(&self, object: &Object) -> (Option<String>, BTreeMap<String, String>) {
+        let mut metadata = match (&object.user_metadata, self.clear) {
+            (Some(metadata), false) => metadata.clone(),
+            _ => BTreeMap::new(),
+        };
+        metadata.retain(|k, _| !self.remove.contains(k));
+        metadata.extend(self.set.clone());
+        (
+            self.mime_type
+                .clone()
+                .or_else(|| object.metadata.mime_type.clone()),
+            metadata,
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MetadataUpdateMethod {
+    Unchanged,
+    SelfCopy,
+}
+
+fn validate_mime_type(mime_type: &str) -> Result<(), Error> {
+    if !mime_type.contains('/') || mime_type.contains(char::is_whitespace) {
+        return Err(Error::InvalidObject(format!(
+            "`{}` is not a valid mime type",
+            mime_type
+        )));
+    }
+    Ok(())
+}
+
+fn add_object_req<S: AsRef<str>>(
+    path: S,
+    bucket: Option<String>,
+    object: &NewObject,
+) -> Result<ApiRequest, Error> {
+    let path = path.as_ref();
+    if path.is_empty() || path.ends_with('/') {
+        return Err(Error::InvalidObject(format!(
+            "`{}` is not a valid object path",
+            path
+        )));
+    }
+    object.validate()?;
+
+    let content = Some(RequestContent::Json(
+        serde_json::to_value(AddObjectRequest {
+            bucket,
+            contract_set: object.contract_set.as_ref(),
+            object: AddObject {
+                key: &object.key,
+                slabs: &object.slabs,
+            },
+            used_contracts: &object.used_contracts,
+            etag: object.etag.as_ref(),
+            mime_type: object.mime_type.as_ref(),
+            metadata: &object.user_metadata,
+        })
+        .map_err(|e| InvalidDataError(e.into()))?,
+    ));
+    Ok(
+        ApiRequestBuilder::put(encode_object_path(path, "./bus/objects"))
+            .content(content)
+            .build(),
+    )
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AddObjectRequest<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bucket: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    contract_set: Option<&'a String>,
+    object: AddObject<'a>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    used_contracts: &'a BTreeMap<PublicKey, FileContractId>,
+    #[serde(rename = "eTag", skip_serializing_if = "Option::is_none")]
+    etag: Option<&'a String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mime_type: Option<&'a String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    metadata: &'a BTreeMap<String, String>,
+}
+
+#[derive(Serialize)]
+struct AddObject<'a> {
+    key: &'a EncryptionKey,
+    slabs: &'a Vec<SlabSlice>,
+}
+
+// renterd's default sector size
+const SECTOR_SIZE: u64 = 1 << 22;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NewObject {
+    pub key: EncryptionKey,
+    pub slabs: Vec<SlabSlice>,
+    pub mime_type: Option<String>,
+    pub etag: Option<String>,
+    pub user_metadata: BTreeMap<String, String>,
+    pub contract_set: Option<String>,
+    pub used_contracts: BTreeMap<PublicKey, FileContractId>,
+}
+
+impl NewObject {
+    pub fn new(key: EncryptionKey, slabs: Vec<SlabSlice>) -> Self {
+        Self {
+            key,
+            slabs,
+            mime_type: None,
+            etag: None,
+            user_metadata: BTreeMap::new(),
+            contract_set: None,
+            used_contracts: BTreeMap::new(),
+        }
+    }
+
+    pub fn mime_type<S: Into<String>>(mut self, mime_type: S) -> Self {
+        self.mime_type = Some(mime_type.into());
+        self
+    }
+
+    pub fn etag<S: Into<String>>(mut self, etag: S) -> Self {
+        self.etag = Some(etag.into());
+        self
+    }
+
+    pub fn user_metadata<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.user_metadata.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn contract_set<S: Into<String>>(mut self, contract_set: S) -> Self {
+        self.contract_set = Some(contract_set.into());
+        self
+    }
+
+    pub fn used_contract(mut self, host: PublicKey, contract: FileContractId) -> Self {
+        self.used_contracts.insert(host, contract);
+        self
+    }
+
+    pub fn size(&self) -> u64 {
+        self.slabs.iter().map(|s| s.length as u64).sum()
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        let invalid = |msg: String| Err(Error::InvalidObject(msg));
+
+        for (i, slice) in self.slabs.iter().enumerate() {
+            let slab = &slice.slab;
+            if slab.min_shards == 0 {
+                return invalid(format!("slab {}: min_shards must be at least 1", i));
+            }
+            if slab.shards.len() < slab.min_shards as usize || slab.shards.len() > 255 {
+                return invalid(format!(
+                    "slab {}: expected between {} and 255 shards, got {}",
+                    i,
+                    slab.min_shards,
+                    slab.shards.len()
+                ));
+            }
+            if slice.length == 0 {
+                return invalid(format!("slab {}: slice length must not be zero", i));
+            }
+            if slice.offset as u64 + slice.length as u64 > slab.min_shards as u64 * SECTOR_SIZE {
+                return invalid(format!(
+                    "slab {}: slice {}+{} exceeds the slab size",
+                    i, slice.offset, slice.length
+                ));
+            }
+            for (j, sector) in slab.shards.iter().enumerate() {
+                if !sector.contracts.contains_key(&sector.latest_host)
+                    && !self.used_contracts.contains_key(&sector.latest_host)
+                {
+                    return invalid(format!(
+                        "slab {} shard {}: no contract for host `{}`",
+                        i, j, sector.latest_host
+                    ));
+                }
+            }
+        }
+        if let Some(mime_type) = &self.mime_type {
+            validate_mime_type(mime_type)?;
+        }
+        if self.etag.as_ref().is_some_and(|etag| etag.is_empty()) {
+            return invalid("etag must not be empty".to_string());
+        }
+        if self.user_metadata.keys().any(|k| k.is_empty()) {
+            return invalid("user metadata keys must not be empty".to_string());
+        }
+        Ok(())
+    }
 }
 
 fn delete_req<S: AsRef<str>>(path: S, bucket: Option<String>, batch: bool) -> ApiRequest {
@@ -430,7 +1362,7 @@ fn delete_req<S: AsRef<str>>(path: S, bucket: Option<String>, batch: bool) -> Ap
 fn get_req<S: AsRef<str>>(
     path: S,
     bucket: Option<String>,
-    prefix: Option<String>,
+    options: &ListOptions,
     offset: Option<usize>,
     marker: Option<String>,
     limit: Option<usize>,
@@ -438,10 +1370,15 @@ fn get_req<S: AsRef<str>>(
     let path = encode_object_path(path, "./bus/objects");
     let params: Vec<_> = [
         bucket.map(|b| ("bucket", b)),
-        prefix.map(|p| ("prefix", p)),
+        options.prefix.clone().map(|p| ("prefix", p)),
         offset.map(|o| ("offset", format!("{}", o))),
         marker.map(|m| ("marker", m)),
         limit.map(|l| ("limit", format!("{}", l))),
+        options.sort_by.map(|s| ("sortBy", s.as_str().to_string())),
+        options
+            .sort_dir
+            .map(|d| ("sortDir", d.as_str().to_string())),
+        options.substring.clone().map(|s| ("substring", s)),
     ]
     .into_iter()
     .flatten()
@@ -477,17 +1414,51 @@ pub struct Metadata {
 pub struct Object {
     #[serde(rename = "metadata")]
     pub user_metadata: Option<BTreeMap<String, String>>,
-    pub key: Option<String>,       //todo: implement `EncryptionKey` type
-    pub slabs: Option<Vec<Value>>, //todo: implement Slab types
+    pub key: Option<EncryptionKey>,
+    pub slabs: Option<Vec<SlabSlice>>,
     #[serde(flatten)]
     pub metadata: Metadata,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SlabSlice {
+    pub slab: Slab,
+    pub offset: u32,
+    pub length: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Slab {
+    #[serde(
+        default,
+        with = "bigdecimal::serde::json_num_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub health: Option<BigDecimal>,
+    pub key: EncryptionKey,
+    pub min_shards: u8,
+    #[serde(default, deserialize_with = "crate::deserialize_null_default")]
+    pub shards: Vec<Sector>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Sector {
+    #[serde(default, deserialize_with = "crate::deserialize_null_default")]
+    pub contracts: BTreeMap<PublicKey, Vec<FileContractId>>,
+    pub latest_host: PublicKey,
+    pub root: Hash,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing::{block_on, Stub};
     use crate::RequestType;
     use bigdecimal::BigDecimal;
+    use serde_json::Value;
     use std::str::FromStr;
 
     #[test]
@@ -495,7 +1466,7 @@ mod tests {
         let req = get_req(
             "/foo/",
             Some("foo_bucket".to_string()),
-            None,
+            &ListOptions::default(),
             None,
             None,
             None,
@@ -543,7 +1514,14 @@ mod tests {
 
     #[test]
     fn get_file() -> anyhow::Result<()> {
-        let req = get_req("/foo/This is a file.zip", None, None, None, None, None);
+        let req = get_req(
+            "/foo/This is a file.zip",
+            None,
+            &ListOptions::default(),
+            None,
+            None,
+            None,
+        );
         assert_eq!(req.path, "./bus/objects/foo/This is a file.zip");
 
         let json = r#"
@@ -585,7 +1563,143 @@ mod tests {
             &BigDecimal::from_str("1.25")?
         );
 
-        //todo: test slabs
+        assert_eq!(
+            object.key,
+            Some(EncryptionKey::try_from(
+                "key:aba60a4c1b9ff360214a68f09f890f9afc00d1bf23c8c9435a02311b10ff1d61"
+            )?)
+        );
+        let slabs = object.slabs.unwrap();
+        assert_eq!(slabs.len(), 1);
+        assert_eq!(slabs[0].offset, 0);
+        assert_eq!(slabs[0].length, 3657244);
+        assert_eq!(slabs[0].slab.min_shards, 2);
+        assert_eq!(slabs[0].slab.health, Some(BigDecimal::from_str("1.25")?));
+        assert_eq!(
+            slabs[0].slab.key,
+            EncryptionKey::try_from(
+                "key:6317e69fb2048ed2137e245b19b91b6f037d929db17c0d9a70cb47be3544b2af"
+            )?
+        );
+        assert!(slabs[0].slab.shards.is_empty());
+        Ok(())
+    }
+
+    fn sample_object() -> anyhow::Result<NewObject> {
+        let host = PublicKey::try_from(
+            "ed25519:17e9e8a4cbc8a1b3b4c8e3ee4e5a8c1f0d2b7a1e3c4d5e6f708192a3b4c5d6e7",
+        )?;
+        let contract = FileContractId::try_from(
+            "fcid:5c2bb0f5ed5ba4a1b4e8c0d8e4b5a3f2e1d0c9b8a7f6e5d4c3b2a1908f7e6d5c",
+        )?;
+        let slab = Slab {
+            health: None,
+            key: EncryptionKey::try_from(
+                "key:6317e69fb2048ed2137e245b19b91b6f037d929db17c0d9a70cb47be3544b2af",
+            )?,
+            min_shards: 1,
+            shards: vec![Sector {
+                contracts: BTreeMap::from([(host.clone(), vec![contract.clone()])]),
+                latest_host: host,
+                root: Hash::try_from(
+                    "h:0a1b2c3d4e5f60718293a4b5c6d7e8f90a1b2c3d4e5f60718293a4b5c6d7e8f9",
+                )?,
+            }],
+        };
+        Ok(NewObject::new(
+            EncryptionKey::try_from(
+                "key:aba60a4c1b9ff360214a68f09f890f9afc00d1bf23c8c9435a02311b10ff1d61",
+            )?,
+            vec![SlabSlice {
+                slab,
+                offset: 0,
+                length: 1024,
+            }],
+        ))
+    }
+
+    #[test]
+    fn add_object() -> anyhow::Result<()> {
+        let object = sample_object()?
+            .mime_type("text/plain")
+            .etag("d41d8cd98f00b204e9800998ecf8427e")
+            .user_metadata("foo", "bar")
+            .contract_set("autopilot");
+        assert_eq!(object.size(), 1024);
+
+        let json = r#"
+        {
+    "bucket": "default",
+    "contractSet": "autopilot",
+    "object": {
+        "key": "key:aba60a4c1b9ff360214a68f09f890f9afc00d1bf23c8c9435a02311b10ff1d61",
+        "slabs": [
+            {
+                "slab": {
+                    "key": "key:6317e69fb2048ed2137e245b19b91b6f037d929db17c0d9a70cb47be3544b2af",
+                    "minShards": 1,
+                    "shards": [
+                        {
+                            "contracts": {
+                                "ed25519:17e9e8a4cbc8a1b3b4c8e3ee4e5a8c1f0d2b7a1e3c4d5e6f708192a3b4c5d6e7": [
+                                    "fcid:5c2bb0f5ed5ba4a1b4e8c0d8e4b5a3f2e1d0c9b8a7f6e5d4c3b2a1908f7e6d5c"
+                                ]
+                            },
+                            "latestHost": "ed25519:17e9e8a4cbc8a1b3b4c8e3ee4e5a8c1f0d2b7a1e3c4d5e6f708192a3b4c5d6e7",
+                            "root": "h:0a1b2c3d4e5f60718293a4b5c6d7e8f90a1b2c3d4e5f60718293a4b5c6d7e8f9"
+                        }
+                    ]
+                },
+                "offset": 0,
+                "length": 1024
+            }
+        ]
+    },
+    "eTag": "d41d8cd98f00b204e9800998ecf8427e",
+    "mimeType": "text/plain",
+    "metadata": {
+        "foo": "bar"
+    }
+}
+        "#;
+        let expected: Value = serde_json::from_str(json)?;
+
+        let req = add_object_req("/foo/bar.txt", Some("default".to_string()), &object)?;
+        assert_eq!(req.path, "./bus/objects/foo/bar.txt");
+        assert_eq!(req.request_type, RequestType::Put);
+        assert_eq!(req.params, None);
+        assert_eq!(req.content, Some(RequestContent::Json(expected)));
+        Ok(())
+    }
+
+    #[test]
+    fn add_object_validation() -> anyhow::Result<()> {
+        let object = sample_object()?;
+        assert!(add_object_req("/foo/", None, &object).is_err());
+        assert!(add_object_req("", None, &object).is_err());
+
+        let mut invalid = object.clone();
+        invalid.slabs[0].slab.min_shards = 2;
+        assert!(invalid.validate().is_err());
+
+        let mut invalid = object.clone();
+        invalid.slabs[0].length = 0;
+        assert!(invalid.validate().is_err());
+
+        let mut invalid = object.clone();
+        invalid.slabs[0].offset = SECTOR_SIZE as u32;
+        assert!(invalid.validate().is_err());
+
+        let mut invalid = object.clone();
+        invalid.slabs[0].slab.shards[0].contracts.clear();
+        assert!(invalid.validate().is_err());
+        let host = invalid.slabs[0].slab.shards[0].latest_host.clone();
+        let contract = object.slabs[0].slab.shards[0].contracts[&host][0].clone();
+        assert!(invalid.used_contract(host, contract).validate().is_ok());
+
+        assert!(object.clone().mime_type("text plain").validate().is_err());
+        assert!(object.clone().etag("").validate().is_err());
+        assert!(object.clone().user_metadata("", "bar").validate().is_err());
         Ok(())
     }
 
@@ -602,7 +1716,7 @@ mod tests {
         let expected: Value = serde_json::from_str(&json)?;
 
         let req = list_req(
-            Some("/foo/".to_string()),
+            &ListOptions::new().prefix("/foo/"),
             Some("bucket_name".to_string()),
             Some("marker_name".to_string()),
             5,
@@ -671,6 +1785,24 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn prefix_normalization() {
+        assert_eq!(normalize_prefix(""), "/");
+        assert_eq!(normalize_prefix("/"), "/");
+        assert_eq!(normalize_prefix("builds"), "/builds/");
+        assert_eq!(normalize_prefix("/builds/nightly/"), "/builds/nightly/");
+
+        assert_eq!(
+            destination_key("/builds/nightly/a/b.zip", "/builds/", "/archive/2024/"),
+            Some("/archive/2024/nightly/a/b.zip".to_string())
+        );
+        assert_eq!(destination_key("/builds/", "/builds/", "/archive/"), None);
+        assert_eq!(
+            destination_key("/other/b.zip", "/builds/", "/archive/"),
+            None
+        );
+    }
+
     #[test]
     fn delete() -> anyhow::Result<()> {
         let req = delete_req("/foo/bar/file.ext", Some("bucket_name".to_string()), false);
@@ -684,9 +1816,24 @@ mod tests {
             ])
         );
         assert_eq!(req.content, None);
+
+        let req = delete_req(normalize_prefix("/foo"), None, true);
+        assert_eq!(req.path, "./bus/objects/foo/");
+        assert_eq!(req.params, Some(vec![("batch".into(), "true".into())]));
         Ok(())
     }
 
+    #[test]
+    fn delete_root_prefix() {
+        let stub = Stub::start(|_| (200, String::new()));
+        let client = stub.client();
+        for prefix in ["", "/", "///"] {
+            let res = block_on(client.bus().object().delete_prefix(prefix, None));
+            assert!(matches!(res, Err(Error::InvalidPrefixOperation(_))));
+        }
+        assert!(stub.requests().is_empty());
+    }
+
     #[test]
     fn copy() -> anyhow::Result<()> {
         let json = r#"
@@ -704,6 +1851,8 @@ mod tests {
             "default".to_string(),
             "/foo/bar/file2".to_string(),
             "default".to_string(),
+            None,
+            None,
         )?;
         assert_eq!(req.path, "./bus/objects/copy");
         assert_eq!(req.request_type, RequestType::Post);
@@ -712,6 +1861,245 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn list_options() -> anyhow::Result<()> {
+        let options = ListOptions::new()
+            .prefix("/foo/")
+            .sort_by(SortBy::Size)
+            .sort_dir(SortDir::Desc)
+            .substring("bar")
+            .delimiter(true);
+
+        let json = r#"
+        {
+    "bucket": "bucket_name",
+    "limit": 5,
+    "prefix": "/foo/",
+    "sortBy": "size",
+    "sortDir": "desc",
+    "substring": "bar",
+    "delimiter": "/"
+}
+        "#;
+        let expected: Value = serde_json::from_str(json)?;
+        let req = list_req(&options, Some("bucket_name".to_string()), None, 5)?;
+        assert_eq!(req.content, Some(RequestContent::Json(expected)));
+
+        let req = get_req(
+            "/foo/",
+            None,
+            &options.sort_by(SortBy::ModTime),
+            None,
+            Some("/foo/bar".to_string()),
+            Some(10),
+        );
+        assert_eq!(
+            req.params,
+            Some(vec![
+                ("prefix".into(), "/foo/".into()),
+                ("marker".into(), "/foo/bar".into()),
+                ("limit".into(), "10".into()),
+                ("sortBy".into(), "modTime".into()),
+                ("sortDir".into(), "desc".into()),
+                ("substring".into(), "bar".into()),
+            ])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn sorted_stream_paging() -> anyhow::Result<()> {
+        let stub = Stub::start(|req| {
+            let name = if req.path.contains("offset=1") {
+                "/dir/a"
+            } else {
+                "/dir/b"
+            };
+            (
+                200,
+                format!(
+                    r#"{{"hasMore": {}, "entries": [{{"name": "{}", "size": 1, "health": 1,
+                    "modTime": "2024-06-27T11:56:19Z"}}]}}"#,
+                    !req.path.contains("offset"),
+                    name
+                ),
+            )
+        });
+        let client = stub.client();
+        let names: Vec<_> = block_on(async {
+            let stream = client
+                .bus()
+                .object()
+                .get_stream(
+                    "/dir/",
+                    NonZeroUsize::MIN,
+                    &ListOptions::new().sort_by(SortBy::Size),
+                    None,
+                )
+                .await?
+                .unwrap()
+                .right()
+                .unwrap();
+            stream.try_concat().await
+        })?
+        .into_iter()
+        .map(|m| m.name)
+        .collect();
+        assert_eq!(names, vec!["/dir/b", "/dir/a"]);
+
+        let requests = stub.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].path.contains("offset=1"));
+        assert!(!requests[1].path.contains("marker"));
+        Ok(())
+    }
+
+    #[test]
+    fn stream_delimiter() {
+        let stub = Stub::start(|_| (200, String::new()));
+        let client = stub.client();
+        let res = block_on(client.bus().object().get_stream(
+            "/dir/",
+            LIST_BATCH_SIZE,
+            &ListOptions::new().delimiter(true),
+            None,
+        ));
+        assert!(matches!(res, Err(Error::InvalidListOptions(_))));
+        assert!(stub.requests().is_empty());
+    }
+
+    #[test]
+    fn object_query() -> anyhow::Result<()> {
+        let json = r#"
+        {
+    "eTag": "d41d8cd98f00b204e9800998ecf8427e",
+    "health": 0.4,
+    "modTime": "2024-07-05T12:37:58.998523074Z",
+    "name": "/docs/report.pdf",
+    "size": 5586849,
+    "mimeType": "application/pdf",
+    "metadata": {
+        "owner": "alice"
+    }
+}
+        "#;
+        let object: Object = serde_json::from_str(json)?;
+        let metadata = &object.metadata;
+
+        let query = ObjectQuery::new()
+            .mime_type("application/pdf")
+            .health_below(BigDecimal::from_str("0.5")?)
+            .modified_after(DateTime::parse_from_rfc3339("2024-07-01T00:00:00Z")?)
+            .modified_before(DateTime::parse_from_rfc3339("2024-07-08T00:00:00Z")?)
+            .min_size(1024)
+            .max_size(10 * 1024 * 1024);
+        assert!(query.matches(metadata));
+        assert!(ObjectQuery::new()
+            .mime_type("application/*")
+            .matches(metadata));
+        assert!(!ObjectQuery::new().mime_type("image/*").matches(metadata));
+        assert!(!ObjectQuery::new().min_size(5586850).matches(metadata));
+        assert!(!ObjectQuery::new()
+            .health_below(BigDecimal::from_str("0.4")?)
+            .matches(metadata));
+        assert!(!ObjectQuery::new()
+            .modified_before(DateTime::parse_from_rfc3339("2024-07-05T12:37:58Z")?)
+            .matches(metadata));
+
+        assert!(mime_type_matches("text/plain", "text/plain; charset=utf-8"));
+        assert!(!mime_type_matches("text/plain", "text/html"));
+
+        assert!(ObjectQuery::new()
+            .user_metadata("owner", "alice")
+            .matches_user_metadata(&object));
+        assert!(!ObjectQuery::new()
+            .user_metadata("owner", "bob")
+            .matches_user_metadata(&object));
+        assert!(!ObjectQuery::new()
+            .user_metadata("team", "alice")
+            .matches_user_metadata(&object));
+        Ok(())
+    }
+
+    #[test]
+    fn copy_metadata() -> anyhow::Result<()> {
+        let json = r#"
+        {
+    "sourceBucket": "default",
+    "sourcePath": "/foo/bar/file1",
+    "destinationBucket": "default",
+    "destinationPath": "/foo/bar/file1",
+    "mimeType": "text/plain",
+    "metadata": {
+        "foo": "bar"
+    }
+}
+        "#;
+        let expected: Value = serde_json::from_str(json)?;
+
+        let req = copy_req(
+            "/foo/bar/file1".to_string(),
+            "default".to_string(),
+            "/foo/bar/file1".to_string(),
+            "default".to_string(),
+            Some("text/plain".to_string()),
+            Some(BTreeMap::from([("foo".to_string(), "bar".to_string())])),
+        )?;
+        assert_eq!(req.path, "./bus/objects/copy");
+        assert_eq!(req.content, Some(RequestContent::Json(expected)));
+        Ok(())
+    }
+
+    #[test]
+    fn metadata_update() -> anyhow::Result<()> {
+        let json = r#"
+        {
+    "eTag": "d41d8cd98f00b204e9800998ecf8427e",
+    "health": 1,
+    "modTime": "2024-06-27T11:56:19.05151211Z",
+    "name": "/foo/file.txt",
+    "size": 12,
+    "mimeType": "text/plain",
+    "metadata": {
+        "keep": "1",
+        "drop": "2",
+        "change": "3"
+    }
+}
+        "#;
+        let object: Object = serde_json::from_str(json)?;
+
+        let update = MetadataUpdate::new()
+            .remove("drop")
+            .set("change", "4")
+            .set("new", "5");
+        let (mime_type, metadata) = update.apply(&object);
+        assert_eq!(mime_type, Some("text/plain".to_string()));
+        assert_eq!(
+            metadata,
+            BTreeMap::from([
+                ("change".to_string(), "4".to_string()),
+                ("keep".to_string(), "1".to_string()),
+                ("new".to_string(), "5".to_string()),
+            ])
+        );
+
+        let update = MetadataUpdate::new()
+            .clear()
+            .set("new", "5")
+            .mime_type("application/json");
+        let (mime_type, metadata) = update.apply(&object);
+        assert_eq!(mime_type, Some("application/json".to_string()));
+        assert_eq!(
+            metadata,
+            BTreeMap::from([("new".to_string(), "5".to_string())])
+        );
+
+        assert!(MetadataUpdate::new().mime_type("text").validate().is_err());
+        assert!(MetadataUpdate::new().set("", "x").validate().is_err());
+        Ok(())
+    }
+
     #[test]
     fn rename() -> anyhow::Result<()> {
         let json = r#"
@@ -0,0 +1,604 @@
+use crate::bus::contract::{Contract, Spending};
+use crate::bus::host::Host;
+use crate::bus::Bus;
+use crate::{Error, FileContractId, PublicKey, DEFAULT_CONCURRENCY};
+use futures::{StreamExt, TryStreamExt};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Clone)]
+pub struct Api {
+    bus: Bus,
+}
+
+impl Api {
+    pub(super) fn new(bus: Bus) -> Self {
+        Self { bus }
+    }
+
+    pub async fn diff<F: AsRef<str>, T: AsRef<str>>(
+        &self,
+        from: F,
+        to: T,
+    ) -> Result<SetDiff, Error> {
+        let (from, to) =
+            futures::try_join!(self.members(from.as_ref()), self.members(to.as_ref()))?;
+        Ok(SetDiff::new(from, to))
+    }
+
+    pub async fn diff_hosts<S: AsRef<str>>(
+        &self,
+        set: S,
+        hosts: &[PublicKey],
+    ) -> Result<HostDiff, Error> {
+        let (members, active) = futures::try_join!(
+            self.members(set.as_ref()),
+            self.bus.contract().get_all(None)
+        )?;
+        Ok(HostDiff::new(members, active, hosts))
+    }
+
+    pub async fn plan<S: AsRef<str>>(&self, set: S, change: &SetChange) -> Result<SetPlan, Error> {
+        let set = set.as_ref();
+        let (members, active) =
+            futures::try_join!(self.members(set), self.bus.contract().get_all(None))?;
+        let active: BTreeSet<FileContractId> =
+            active.into_iter().map(|contract| contract.id).collect();
+        SetPlan::new(set, ids(&members), &active, change)
+    }
+
+    // fails with `Error::ContractSetModified` if the set changed since `plan` was made. renterd
+    // can only replace a set as a whole, so this is a check followed by a separate replace, not a
+    // compare-and-swap: a change made between the two requests is overwritten. The set is read
+    // again afterwards so that such a lost update is at least reported the same way.
+    pub async fn apply(&self, plan: &SetPlan) -> Result<(), Error> {
+        if ids(&self.members_or_empty(&plan.set).await?) != plan.before {
+            return Err(Error::ContractSetModified(plan.set.clone()));
+        }
+        self.bus
+            .contract()
+            .create_contract_set(&plan.set, &plan.after.iter().cloned().collect())
+            .await?;
+        if ids(&self.members_or_empty(&plan.set).await?) != plan.after {
+            return Err(Error::ContractSetModified(plan.set.clone()));
+        }
+        Ok(())
+    }
+
+    pub async fn clone_set<S: AsRef<str>, D: AsRef<str>>(
+        &self,
+        source: S,
+        destination: D,
+        overwrite: bool,
+    ) -> Result<SetPlan, Error> {
+        let (source, destination) = (source.as_ref(), destination.as_ref());
+        if !overwrite
+            && self
+                .bus
+                .contract()
+                .contract_sets()
+                .await?
+                .iter()
+                .any(|set| set == destination)
+        {
+            return Err(Error::InvalidContractSetChange(format!(
+                "contract set `{}` already exists",
+                destination
+            )));
+        }
+        let (source, before) =
+            futures::try_join!(self.members(source), self.members_or_empty(destination))?;
+        let plan = SetPlan::replace(destination, ids(&before), ids(&source));
+        self.apply(&plan).await?;
+        Ok(plan)
+    }
+
+    pub async fn summary<S: AsRef<str>>(&self, set: S) -> Result<SetSummary, Error> {
+        let set = set.as_ref();
+        let members = self.members(set).await?;
+        let host_keys: BTreeSet<PublicKey> = members
+            .iter()
+            .map(|contract| contract.host_key.clone())
+            .collect();
+        let hosts: BTreeMap<PublicKey, Host> = futures::stream::iter(host_keys)
+            .map(|host_key| {
+                let bus = self.bus.clone();
+                async move {
+                    match bus.host().get_by_key(&host_key).await {
+                        Ok(host) => Ok(Some((host_key, host))),
+                        Err(Error::NotFoundError) => Ok(None),
+                        Err(err) => Err(err),
+                    }
+                }
+            })
+            .buffer_unordered(DEFAULT_CONCURRENCY.get())
+            .try_filter_map(|host| futures::future::ready(Ok(host)))
+            .try_collect()
+            .await?;
+        Ok(SetSummary::new(set, &members, hosts))
+    }
+
+    pub async fn summaries(&self) -> Result<Vec<SetSummary>, Error> {
+        let mut summaries = vec![];
+        for set in self.bus.contract().contract_sets().await? {
+            summaries.push(self.summary(set).await?);
+        }
+        Ok(summaries)
+    }
+
+    async fn members(&self, set: &str) -> Result<Vec<Contract>, Error> {
+        let members = self.members_or_empty(set).await?;
+        // an empty listing doesn't tell a missing set from an empty one
+        if members.is_empty()
+            && !self
+                .bus
+                .contract()
+                .contract_sets()
+                .await?
+                .iter()
+                .any(|name| name == set)
+        {
+            return Err(Error::NotFoundError);
+        }
+        Ok(members)
+    }
+
+    // only for the set that is written to, which may not exist yet
+    async fn members_or_empty(&self, set: &str) -> Result<Vec<Contract>, Error> {
+        match self.bus.contract().get_all(Some(set.to_string())).await {
+            Err(Error::NotFoundError) => Ok(vec![]),
+            result => result,
+        }
+    }
+}
+
+fn ids(contracts: &[Contract]) -> BTreeSet<FileContractId> {
+    contracts
+        .iter()
+        .map(|contract| contract.id.clone())
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetDiff {
+    pub added: Vec<Contract>,
+    pub removed: Vec<Contract>,
+    pub unchanged: Vec<Contract>,
+}
+
+impl SetDiff {
+    fn new(from: Vec<Contract>, to: Vec<Contract>) -> Self {
+        let from_ids = ids(&from);
+        let to_ids = ids(&to);
+        let (unchanged, removed) = from
+            .into_iter()
+            .partition(|contract| to_ids.contains(&contract.id));
+        let added = to
+            .into_iter()
+            .filter(|contract| !from_ids.contains(&contract.id))
+            .collect();
+        Self {
+            added,
+            removed,
+            unchanged,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostDiff {
+    pub add: Vec<Contract>,
+    pub remove: Vec<Contract>,
+    pub unavailable: Vec<PublicKey>,
+}
+
+impl HostDiff {
+    fn new(members: Vec<Contract>, active: Vec<Contract>, hosts: &[PublicKey]) -> Self {
+        let wanted: BTreeSet<&PublicKey> = hosts.iter().collect();
+        let covered: BTreeSet<PublicKey> = members
+            .iter()
+            .filter(|contract| wanted.contains(&contract.host_key))
+            .map(|contract| contract.host_key.clone())
+            .collect();
+
+        // prefer the contract that runs the longest if a host has several
+        let mut candidates: BTreeMap<PublicKey, Contract> = BTreeMap::new();
+        for contract in active {
+            if !wanted.contains(&contract.host_key) || covered.contains(&contract.host_key) {
+                continue;
+            }
+            match candidates.get(&contract.host_key) {
+                Some(candidate) if candidate.window_end >= contract.window_end => {}
+                _ => {
+                    candidates.insert(contract.host_key.clone(), contract);
+                }
+            }
+        }
+
+        let unavailable = wanted
+            .iter()
+            .filter(|host| !covered.contains(*host) && !candidates.contains_key(*host))
+            .map(|host| (*host).clone())
+            .collect();
+        let remove = members
+            .into_iter()
+            .filter(|contract| !wanted.contains(&contract.host_key))
+            .collect();
+        Self {
+            add: candidates.into_values().collect(),
+            remove,
+            unavailable,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.add.is_empty() && self.remove.is_empty()
+    }
+
+    pub fn change(&self) -> SetChange {
+        SetChange {
+            add: ids(&self.add),
+            remove: ids(&self.remove),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SetChange {
+    pub add: BTreeSet<FileContractId>,
+    pub remove: BTreeSet<FileContractId>,
+}
+
+impl SetChange {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_contract(mut self, contract_id: FileContractId) -> Self {
+        self.add.insert(contract_id);
+        self
+    }
+
+    pub fn remove_contract(mut self, contract_id: FileContractId) -> Self {
+        self.remove.insert(contract_id);
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetPlan {
+    pub set: String,
+    pub before: BTreeSet<FileContractId>,
+    pub after: BTreeSet<FileContractId>,
+}
+
+impl SetPlan {
+    fn new(
+        set: &str,
+        before: BTreeSet<FileContractId>,
+        active: &BTreeSet<FileContractId>,
+        change: &SetChange,
+    ) -> Result<Self, Error> {
+        if let Some(contract_id) = change.add.intersection(&change.remove).next() {
+            return Err(Error::InvalidContractSetChange(format!(
+                "{} is both added and removed",
+                contract_id
+            )));
+        }
+        if let Some(contract_id) = change.add.difference(active).next() {
+            return Err(Error::InvalidContractSetChange(format!(
+                "{} is not an active contract",
+                contract_id
+            )));
+        }
+        let after = before
+            .difference(&change.remove)
+            .chain(change.add.iter())
+            .cloned()
+            .collect();
+        Ok(Self::replace(set, before, after))
+    }
+
+    fn replace(
+        set: &str,
+        before: BTreeSet<FileContractId>,
+        after: BTreeSet<FileContractId>,
+    ) -> Self {
+        Self {
+            set: set.to_string(),
+            before,
+            after,
+        }
+    }
+
+    pub fn added(&self) -> impl Iterator<Item = &FileContractId> {
+        self.after.difference(&self.before)
+    }
+
+    pub fn removed(&self) -> impl Iterator<Item = &FileContractId> {
+        self.before.difference(&self.after)
+    }
+
+    pub fn is_noop(&self) -> bool {
+        self.before == self.after
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetSummary {
+    pub name: String,
+    pub contracts: u64,
+    pub size: u64,
+    pub total_cost: u128,
+    pub spending: Spending,
+    pub hosts: Vec<SetHost>,
+}
+
+impl SetSummary {
+    fn new(name: &str, members: &[Contract], mut hosts: BTreeMap<PublicKey, Host>) -> Self {
+        let mut by_host: BTreeMap<PublicKey, SetHost> = BTreeMap::new();
+        for contract in members {
+            let entry = by_host
+                .entry(contract.host_key.clone())
+                .or_insert_with(|| SetHost {
+                    host_key: contract.host_key.clone(),
+                    contracts: 0,
+                    size: 0,
+                    host: hosts.remove(&contract.host_key),
+                });
+            entry.contracts += 1;
+            entry.size += contract.size;
+        }
+        let mut hosts: Vec<SetHost> = by_host.into_values().collect();
+        hosts.sort_by(|a, b| {
+            b.size
+                .cmp(&a.size)
+                .then_with(|| a.host_key.cmp(&b.host_key))
+        });
+
+        Self {
+            name: name.to_string(),
+            contracts: members.len() as u64,
+            size: members.iter().map(|contract| contract.size).sum(),
+            total_cost: members.iter().map(|contract| contract.total_cost).sum(),
+            spending: members.iter().map(|contract| &contract.spending).sum(),
+            hosts,
+        }
+    }
+
+    // unknown to the bus, blocked or failing any of the autopilot's usability checks
+    pub fn unhealthy_hosts(&self) -> impl Iterator<Item = &SetHost> {
+        self.hosts.iter().filter(|host| {
+            host.host.as_ref().is_none_or(|host| {
+                host.blocked
+                    || host
+                        .checks
+                        .values()
+                        .any(|check| check.usability.reasons().next().is_some())
+            })
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetHost {
+    pub host_key: PublicKey,
+    pub contracts: u64,
+    pub size: u64,
+    pub host: Option<Host>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{block_on, contract, contract_json, Stub};
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    const HOST_A: &str = "e156553dd877e99f24a5c02b72d1c1edd75dce76c663f6b939f20d3f7e9f01d9";
+    const HOST_B: &str = "dfb16d76de07c537ad62647b39cba0497a6e339dfb1644bd8f8cda95893b1f16";
+    const HOST_C: &str = "d129f18cdfb12fa426fe60f5f2f77e2498d173977e88a037328d1a0fa8b56d68";
+
+    fn host(key: &str) -> PublicKey {
+        PublicKey::try_from(format!("ed25519:{}", key).as_str()).unwrap()
+    }
+
+    #[test]
+    fn clone_missing_source() {
+        let created = AtomicBool::new(false);
+        let stub = Stub::start(move |req| {
+            if req.is("PUT", "/bus/contracts/set/new") {
+                created.store(true, Ordering::Relaxed);
+            }
+            if req.is("GET", "/bus/contracts/sets") {
+                (200, r#"["autopilot", "backup"]"#.to_string())
+            } else if req.is("GET", "/bus/contracts?contractset=backup")
+                || (created.load(Ordering::Relaxed)
+                    && req.is("GET", "/bus/contracts?contractset=new"))
+            {
+                (
+                    200,
+                    format!("[{}]", contract_json('1', HOST_A, 1000, 5000, 10)),
+                )
+            } else if req.method == "GET" {
+                (404, "contract set not found".to_string())
+            } else {
+                (200, String::new())
+            }
+        });
+        let client = stub.client();
+        let api = client.contract_set();
+        assert!(matches!(
+            block_on(api.clone_set("autopliot", "backup", true)),
+            Err(Error::NotFoundError)
+        ));
+        assert!(matches!(
+            block_on(api.diff("backup", "autopliot")),
+            Err(Error::NotFoundError)
+        ));
+        assert!(stub.requests().iter().all(|r| r.method == "GET"));
+
+        let plan = block_on(api.clone_set("backup", "new", true)).unwrap();
+        assert!(plan.before.is_empty());
+        assert_eq!(plan.after.len(), 1);
+        assert!(stub
+            .requests()
+            .iter()
+            .any(|r| r.is("PUT", "/bus/contracts/set/new")));
+    }
+
+    #[test]
+    fn lost_update() {
+        let reads = AtomicUsize::new(0);
+        let stub = Stub::start(move |req| {
+            if !req.is("GET", "/bus/contracts?contractset=autopilot") {
+                return (200, String::new());
+            }
+            // someone else adds a contract between the check and the replace
+            let contracts = match reads.fetch_add(1, Ordering::Relaxed) {
+                0 => vec![],
+                _ => vec![
+                    contract_json('1', HOST_A, 1000, 5000, 10),
+                    contract_json('2', HOST_B, 1000, 5000, 10),
+                ],
+            };
+            (200, format!("[{}]", contracts.join(",")))
+        });
+        let client = stub.client();
+        let plan = SetPlan::replace(
+            "autopilot",
+            BTreeSet::new(),
+            BTreeSet::from([contract('1', HOST_A, 1000, 5000, 10).id]),
+        );
+        assert!(matches!(
+            block_on(client.contract_set().apply(&plan)),
+            Err(Error::ContractSetModified(_))
+        ));
+        assert!(stub
+            .requests()
+            .iter()
+            .any(|r| r.is("PUT", "/bus/contracts/set/autopilot")));
+    }
+
+    #[test]
+    fn diff() {
+        let a = contract('1', HOST_A, 1000, 5000, 10);
+        let b = contract('2', HOST_B, 1000, 5000, 20);
+        let c = contract('3', HOST_C, 1000, 5000, 30);
+
+        let diff = SetDiff::new(vec![a.clone(), b.clone()], vec![b.clone(), c.clone()]);
+        assert_eq!(diff.added, vec![c.clone()]);
+        assert_eq!(diff.removed, vec![a.clone()]);
+        assert_eq!(diff.unchanged, vec![b.clone()]);
+        assert!(!diff.is_empty());
+        assert!(SetDiff::new(vec![a.clone()], vec![a]).is_empty());
+    }
+
+    #[test]
+    fn diff_hosts() {
+        let in_set_a = contract('1', HOST_A, 1000, 5000, 10);
+        let in_set_b = contract('2', HOST_B, 1000, 5000, 20);
+        let short_c = contract('3', HOST_C, 1000, 5000, 0);
+        let long_c = contract('4', HOST_C, 1000, 6000, 0);
+        let active = vec![
+            in_set_a.clone(),
+            in_set_b.clone(),
+            short_c.clone(),
+            long_c.clone(),
+        ];
+        let unknown = PublicKey::try_from(
+            "ed25519:dfb16d76de07c537ad62647b39cba0497a6e339dfb1644bd8f8cda95893b1f17",
+        )
+        .unwrap();
+
+        let diff = HostDiff::new(
+            vec![in_set_a.clone(), in_set_b.clone()],
+            active,
+            &[host(HOST_A), host(HOST_C), unknown.clone()],
+        );
+        assert_eq!(diff.add, vec![long_c.clone()]);
+        assert_eq!(diff.remove, vec![in_set_b.clone()]);
+        assert_eq!(diff.unavailable, vec![unknown]);
+
+        let change = diff.change();
+        assert_eq!(
+            change,
+            SetChange::new()
+                .add_contract(long_c.id)
+                .remove_contract(in_set_b.id)
+        );
+    }
+
+    #[test]
+    fn plan() -> anyhow::Result<()> {
+        let a = contract('1', HOST_A, 1000, 5000, 10).id;
+        let b = contract('2', HOST_B, 1000, 5000, 20).id;
+        let c = contract('3', HOST_C, 1000, 5000, 30).id;
+        let d = contract('4', HOST_C, 1000, 5000, 30).id;
+        let before: BTreeSet<_> = [a.clone(), b.clone()].into();
+        let active: BTreeSet<_> = [a.clone(), b.clone(), c.clone()].into();
+
+        let plan = SetPlan::new(
+            "autopilot",
+            before.clone(),
+            &active,
+            &SetChange::new()
+                .add_contract(c.clone())
+                .remove_contract(a.clone()),
+        )?;
+        assert_eq!(plan.set, "autopilot");
+        assert_eq!(plan.before, before);
+        assert_eq!(plan.after, [b.clone(), c.clone()].into());
+        assert_eq!(plan.added().collect::<Vec<_>>(), vec![&c]);
+        assert_eq!(plan.removed().collect::<Vec<_>>(), vec![&a]);
+        assert!(!plan.is_noop());
+
+        let plan = SetPlan::new("autopilot", before.clone(), &active, &SetChange::new())?;
+        assert!(plan.is_noop());
+
+        assert!(matches!(
+            SetPlan::new(
+                "autopilot",
+                before.clone(),
+                &active,
+                &SetChange::new().add_contract(d)
+            ),
+            Err(Error::InvalidContractSetChange(_))
+        ));
+        assert!(matches!(
+            SetPlan::new(
+                "autopilot",
+                before,
+                &active,
+                &SetChange::new().add_contract(c.clone()).remove_contract(c)
+            ),
+            Err(Error::InvalidContractSetChange(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn summary() {
+        let members = vec![
+            contract('1', HOST_A, 1000, 5000, 10),
+            contract('2', HOST_B, 1000, 5000, 20),
+            contract('3', HOST_B, 1000, 6000, 30),
+        ];
+        let summary = SetSummary::new("autopilot", &members, BTreeMap::new());
+        assert_eq!(summary.name, "autopilot");
+        assert_eq!(summary.contracts, 3);
+        assert_eq!(summary.size, 60);
+        assert_eq!(summary.total_cost, 1500);
+        assert_eq!(summary.spending.uploads, 3000);
+        assert_eq!(summary.spending.total(), 3150);
+
+        assert_eq!(summary.hosts.len(), 2);
+        assert_eq!(summary.hosts[0].host_key, host(HOST_B));
+        assert_eq!(summary.hosts[0].contracts, 2);
+        assert_eq!(summary.hosts[0].size, 50);
+        assert_eq!(summary.hosts[1].host_key, host(HOST_A));
+        assert_eq!(summary.unhealthy_hosts().count(), 2);
+    }
+}
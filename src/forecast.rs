@@ -0,0 +1,362 @@
+use crate::autopilot::Autopilot;
+use crate::bus::consensus::State;
+use crate::bus::contract::Contract;
+use crate::bus::Bus;
+use crate::{Error, FileContractId, PublicKey};
+use chrono::{DateTime, FixedOffset, TimeDelta};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+const DEFAULT_BLOCK_TIME: Duration = Duration::from_secs(600);
+
+#[derive(Clone)]
+pub struct Api {
+    bus: Bus,
+    autopilot: Autopilot,
+}
+
+impl Api {
+    pub(super) fn new(bus: Bus, autopilot: Autopilot) -> Self {
+        Self { bus, autopilot }
+    }
+
+    // the autopilot config is only skipped if both the renew window and the period are set
+    pub async fn forecast(&self, options: &ForecastOptions) -> Result<Forecast, Error> {
+        let (renew_window, period, allowance) = match (options.renew_window, options.period) {
+            (Some(renew_window), Some(period)) => (renew_window, period, None),
+            (renew_window, period) => {
+                let config = self.autopilot.config().get().await?.contract_config;
+                (
+                    renew_window.unwrap_or(config.renew_window),
+                    period.unwrap_or(config.period),
+                    Some(config.allowance),
+                )
+            }
+        };
+        let (state, contracts) = futures::try_join!(
+            self.bus.consensus().state(),
+            self.bus.contract().get_all(options.contract_set.clone())
+        )?;
+        Ok(Forecast::new(
+            &state,
+            &contracts,
+            renew_window,
+            period,
+            allowance,
+            options.block_time,
+        ))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForecastOptions {
+    pub contract_set: Option<String>,
+    pub renew_window: Option<u64>,
+    pub period: Option<u64>,
+    pub block_time: Duration,
+}
+
+impl Default for ForecastOptions {
+    fn default() -> Self {
+        Self {
+            contract_set: None,
+            renew_window: None,
+            period: None,
+            block_time: DEFAULT_BLOCK_TIME,
+        }
+    }
+}
+
+impl ForecastOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn contract_set<S: Into<String>>(mut self, contract_set: S) -> Self {
+        self.contract_set = Some(contract_set.into());
+        self
+    }
+
+    pub fn renew_window(mut self, renew_window: u64) -> Self {
+        self.renew_window = Some(renew_window);
+        self
+    }
+
+    pub fn period(mut self, period: u64) -> Self {
+        self.period = Some(period);
+        self
+    }
+
+    pub fn block_time(mut self, block_time: Duration) -> Self {
+        self.block_time = block_time;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Forecast {
+    pub block_height: u64,
+    pub renew_window: u64,
+    pub period: u64,
+    pub contracts: Vec<ContractForecast>,
+    pub hosts: Vec<HostExposure>,
+    pub allowance: AllowanceForecast,
+}
+
+impl Forecast {
+    fn new(
+        state: &State,
+        contracts: &[Contract],
+        renew_window: u64,
+        period: u64,
+        allowance: Option<u128>,
+        block_time: Duration,
+    ) -> Self {
+        let height = state.block_height;
+        let block_time = block_time.as_secs() as i64;
+        let at_height = |target: u64| {
+            let seconds = (target as i64)
+                .saturating_sub(height as i64)
+                .saturating_mul(block_time);
+            TimeDelta::try_seconds(seconds)
+                .and_then(|delta| state.last_block_time.checked_add_signed(delta))
+                .unwrap_or(state.last_block_time)
+        };
+
+        let mut forecasts: Vec<ContractForecast> = contracts
+            .iter()
+            .map(|contract| ContractForecast {
+                id: contract.id.clone(),
+                host_key: contract.host_key.clone(),
+                size: contract.size,
+                end_height: contract.window_start,
+                proof_deadline: contract.window_end,
+                blocks_left: contract.window_start.saturating_sub(height),
+                expires_at: at_height(contract.window_start),
+                in_renew_window: height.saturating_add(renew_window) >= contract.window_start,
+                expired: height >= contract.window_start,
+            })
+            .collect();
+        forecasts.sort_by(|a, b| {
+            a.end_height
+                .cmp(&b.end_height)
+                .then_with(|| a.id.cmp(&b.id))
+        });
+
+        let total_size: u64 = contracts.iter().map(|contract| contract.size).sum();
+        let mut hosts: BTreeMap<PublicKey, HostExposure> = BTreeMap::new();
+        for contract in contracts {
+            let exposure = hosts
+                .entry(contract.host_key.clone())
+                .or_insert_with(|| HostExposure {
+                    host_key: contract.host_key.clone(),
+                    contracts: 0,
+                    size: 0,
+                    share: 0.0,
+                });
+            exposure.contracts += 1;
+            exposure.size += contract.size;
+        }
+        let mut hosts: Vec<HostExposure> = hosts
+            .into_values()
+            .map(|mut exposure| {
+                if total_size > 0 {
+                    exposure.share = exposure.size as f64 / total_size as f64;
+                }
+                exposure
+            })
+            .collect();
+        hosts.sort_by(|a, b| {
+            b.size
+                .cmp(&a.size)
+                .then_with(|| a.host_key.cmp(&b.host_key))
+        });
+
+        let spent: u128 = contracts
+            .iter()
+            .map(|contract| contract.spending.total())
+            .sum();
+        // the renew window and the period can be anything the caller passed in
+        let projected: u128 = contracts
+            .iter()
+            .map(|contract| {
+                let elapsed = height.saturating_sub(contract.start_height).max(1);
+                let spending = contract.spending.total();
+                (spending.saturating_mul(period as u128) / elapsed as u128)
+                    .saturating_add(contract.contract_price)
+            })
+            .fold(0, u128::saturating_add);
+
+        Self {
+            block_height: height,
+            renew_window,
+            period,
+            contracts: forecasts,
+            hosts,
+            allowance: AllowanceForecast {
+                current: allowance,
+                spent,
+                projected,
+            },
+        }
+    }
+
+    pub fn due_for_renewal(&self) -> impl Iterator<Item = &ContractForecast> {
+        self.contracts
+            .iter()
+            .filter(|contract| contract.in_renew_window)
+    }
+
+    pub fn expiring_within(&self, blocks: u64) -> impl Iterator<Item = &ContractForecast> {
+        self.contracts
+            .iter()
+            .filter(move |contract| contract.blocks_left <= blocks)
+    }
+
+    pub fn host_loss(&self, host_key: &PublicKey) -> u64 {
+        self.hosts
+            .iter()
+            .find(|exposure| &exposure.host_key == host_key)
+            .map(|exposure| exposure.size)
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContractForecast {
+    pub id: FileContractId,
+    pub host_key: PublicKey,
+    pub size: u64,
+    // the start of the proof window
+    pub end_height: u64,
+    pub proof_deadline: u64,
+    pub blocks_left: u64,
+    pub expires_at: DateTime<FixedOffset>,
+    pub in_renew_window: bool,
+    pub expired: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostExposure {
+    pub host_key: PublicKey,
+    pub contracts: u64,
+    pub size: u64,
+    pub share: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AllowanceForecast {
+    pub current: Option<u128>,
+    pub spent: u128,
+    // extrapolated from the spending rate of each active contract plus the price of forming
+    // its renewal
+    pub projected: u128,
+}
+
+impl AllowanceForecast {
+    pub fn is_insufficient(&self) -> bool {
+        self.current
+            .is_some_and(|allowance| self.projected > allowance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::contract;
+
+    #[test]
+    fn forecast() -> anyhow::Result<()> {
+        let host_a = "e156553dd877e99f24a5c02b72d1c1edd75dce76c663f6b939f20d3f7e9f01d9";
+        let host_b = "dfb16d76de07c537ad62647b39cba0497a6e339dfb1644bd8f8cda95893b1f16";
+        let contracts = vec![
+            contract('1', host_a, 9000, 13000, 300),
+            contract('2', host_b, 9500, 10500, 100),
+            contract('3', host_a, 9000, 9900, 0),
+        ];
+        let state = State {
+            block_height: 10000,
+            last_block_time: DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z")?,
+            synced: true,
+        };
+
+        let forecast = Forecast::new(
+            &state,
+            &contracts,
+            1000,
+            4000,
+            Some(10000),
+            DEFAULT_BLOCK_TIME,
+        );
+        let ids: Vec<_> = forecast.contracts.iter().map(|c| c.id.clone()).collect();
+        assert_eq!(
+            ids,
+            vec![
+                contracts[2].id.clone(),
+                contracts[1].id.clone(),
+                contracts[0].id.clone()
+            ]
+        );
+
+        let expired = &forecast.contracts[0];
+        assert!(expired.expired);
+        assert!(expired.in_renew_window);
+        assert_eq!(expired.blocks_left, 0);
+        assert_eq!(
+            expired.expires_at,
+            DateTime::parse_from_rfc3339("2023-12-31T07:20:00Z")?
+        );
+
+        let due = &forecast.contracts[1];
+        assert!(!due.expired);
+        assert!(due.in_renew_window);
+        assert_eq!(due.blocks_left, 500);
+        assert_eq!(
+            due.expires_at,
+            DateTime::parse_from_rfc3339("2024-01-04T11:20:00Z")?
+        );
+        assert_eq!(due.proof_deadline, 10644);
+
+        assert!(!forecast.contracts[2].in_renew_window);
+        assert_eq!(forecast.due_for_renewal().count(), 2);
+        assert_eq!(forecast.expiring_within(500).count(), 2);
+        assert_eq!(forecast.expiring_within(499).count(), 1);
+
+        assert_eq!(forecast.hosts.len(), 2);
+        assert_eq!(forecast.hosts[0].host_key, contracts[0].host_key);
+        assert_eq!(forecast.hosts[0].contracts, 2);
+        assert_eq!(forecast.hosts[0].share, 0.75);
+        assert_eq!(forecast.host_loss(&contracts[0].host_key), 300);
+        assert_eq!(forecast.host_loss(&contracts[1].host_key), 100);
+
+        // 1050 spent over 1000, 500 and 1000 blocks, extrapolated to 4000 blocks
+        assert_eq!(forecast.allowance.spent, 3150);
+        assert_eq!(forecast.allowance.projected, 4300 + 8500 + 4300);
+        assert_eq!(forecast.allowance.current, Some(10000));
+        assert!(forecast.allowance.is_insufficient());
+        Ok(())
+    }
+
+    #[test]
+    fn unbounded_options() -> anyhow::Result<()> {
+        let host = "e156553dd877e99f24a5c02b72d1c1edd75dce76c663f6b939f20d3f7e9f01d9";
+        let mut contracts = vec![contract('1', host, 9999, 13000, 300)];
+        contracts[0].spending.uploads = u128::MAX / 2;
+        let state = State {
+            block_height: 10000,
+            last_block_time: DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z")?,
+            synced: true,
+        };
+        let forecast = Forecast::new(
+            &state,
+            &contracts,
+            u64::MAX,
+            u64::MAX,
+            None,
+            DEFAULT_BLOCK_TIME,
+        );
+        assert!(forecast.contracts[0].in_renew_window);
+        assert_eq!(forecast.allowance.projected, u128::MAX);
+        Ok(())
+    }
+}
@@ -0,0 +1,589 @@
+use crate::bus::object::{normalize_prefix, ListOptions, Metadata, RenameMode};
+use crate::bus::Bus;
+use crate::worker::object::DownloadableObject;
+use crate::worker::Worker;
+use crate::{Error, Percentage, LIST_BATCH_SIZE};
+use bytes::Bytes;
+use chrono::{DateTime, FixedOffset};
+use either::Either;
+use futures::channel::mpsc;
+use futures::future::BoxFuture;
+use futures::TryStreamExt;
+use futures::{ready, AsyncRead, AsyncSeek, AsyncWrite, FutureExt, SinkExt, Stream, StreamExt};
+use std::collections::BTreeMap;
+use std::io::SeekFrom;
+use std::num::NonZeroUsize;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::task::{JoinError, JoinHandle};
+
+pub const DEFAULT_BUCKET: &str = "default";
+
+#[derive(Clone)]
+pub struct RenterdFs {
+    bus: Bus,
+    worker: Worker,
+    bucket: Option<String>,
+}
+
+impl RenterdFs {
+    pub(super) fn new(bus: Bus, worker: Worker, bucket: Option<String>) -> Self {
+        Self {
+            bus,
+            worker,
+            bucket,
+        }
+    }
+
+    pub fn bucket(&self) -> &str {
+        self.bucket.as_deref().unwrap_or(DEFAULT_BUCKET)
+    }
+
+    pub async fn metadata<S: AsRef<str>>(&self, path: S) -> Result<Option<Entry>, Error> {
+        let path = path.as_ref();
+        if !path.ends_with('/') {
+            match self
+                .bus
+                .object()
+                .get(path, self.bucket.clone(), None, None, None, Some(1))
+                .await?
+            {
+                Some(Either::Left(object)) => return Ok(Some(object.metadata.into())),
+                Some(Either::Right(_)) | None => {}
+            }
+        }
+
+        // renterd lists any missing directory as empty, so a directory only exists if there is
+        // a marker object for it or any object below it
+        let dir = normalize_prefix(path);
+        if dir == "/" {
+            return Ok(Some(Entry::directory(dir)));
+        }
+        let mut objects = self.bus.object().list(
+            NonZeroUsize::MIN,
+            &ListOptions::new().prefix(dir.as_str()),
+            self.bucket.clone(),
+        )?;
+        match objects.try_next().await {
+            Ok(batch) => Ok(batch.map(|_| Entry::directory(dir))),
+            Err(Error::NotFoundError) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub async fn read_dir<S: AsRef<str>>(
+        &self,
+        path: S,
+    ) -> Result<impl Stream<Item = Result<Entry, Error>> + Send + Unpin, Error> {
+        match self
+            .bus
+            .object()
+            .get_stream(
+                normalize_prefix(path.as_ref()),
+                LIST_BATCH_SIZE,
+                &ListOptions::default(),
+                self.bucket.clone(),
+            )
+            .await?
+        {
+            Some(Either::Right(stream)) => Ok(stream
+                .map_ok(|batch| {
+                    futures::stream::iter(batch.into_iter().map(|metadata| Ok(metadata.into())))
+                })
+                .try_flatten()
+                .boxed()),
+            Some(Either::Left(object)) => Err(Error::NotADirectory(object.metadata.name)),
+            None => Err(Error::NotFoundError),
+        }
+    }
+
+    pub async fn open<S: AsRef<str>>(&self, path: S) -> Result<File, Error> {
+        let path = path.as_ref();
+        if path.ends_with('/') {
+            return Err(Error::NotDownloadableObject(path.to_string()));
+        }
+        let object = self
+            .worker
+            .object()
+            .download(path, self.bucket.clone())
+            .await?
+            .ok_or(Error::NotFoundError)?;
+        Ok(File::new(object.pin_etag(true)))
+    }
+
+    // the upload runs as a tokio task, so this panics outside of a tokio runtime
+    pub fn create<S: AsRef<str>>(&self, path: S, content_type: Option<String>) -> FileWriter {
+        self.create_with_metadata(path, content_type, BTreeMap::new())
+    }
+
+    pub fn create_with_metadata<S: AsRef<str>>(
+        &self,
+        path: S,
+        content_type: Option<String>,
+        user_metadata: BTreeMap<String, String>,
+    ) -> FileWriter {
+        let (sender, receiver) = mpsc::channel::<std::io::Result<Bytes>>(4);
+        let finished = Arc::new(AtomicBool::new(false));
+        let worker = self.worker.clone();
+        let path = path.as_ref().to_string();
+        let bucket = self.bucket.clone();
+        let body = upload_body(receiver, finished.clone());
+        let upload = tokio::spawn(async move {
+            worker
+                .object()
+                .upload_with_metadata(path, content_type, &user_metadata, bucket, body)
+                .await
+        });
+        FileWriter {
+            sender,
+            finished,
+            upload,
+            joined: false,
+        }
+    }
+
+    pub async fn remove<S: AsRef<str>>(&self, path: S) -> Result<(), Error> {
+        self.bus
+            .object()
+            .delete(path, self.bucket.clone(), false)
+            .await
+    }
+
+    pub async fn remove_dir_all<S: AsRef<str>>(&self, path: S) -> Result<(), Error> {
+        self.bus
+            .object()
+            .delete(normalize_prefix(path.as_ref()), self.bucket.clone(), true)
+            .await
+    }
+
+    pub async fn rename<F: AsRef<str>, T: AsRef<str>>(&self, from: F, to: T) -> Result<(), Error> {
+        let (from, to) = (from.as_ref(), to.as_ref());
+        let (from, to, mode) = if from.ends_with('/') {
+            (
+                normalize_prefix(from),
+                normalize_prefix(to),
+                RenameMode::Multi,
+            )
+        } else {
+            (from.to_string(), to.to_string(), RenameMode::Single)
+        };
+        self.bus
+            .object()
+            .rename(from, to, self.bucket().to_string(), false, mode)
+            .await
+    }
+
+    pub async fn copy<F: AsRef<str>, T: AsRef<str>>(&self, from: F, to: T) -> Result<(), Error> {
+        self.bus
+            .object()
+            .copy(
+                from.as_ref().to_string(),
+                self.bucket().to_string(),
+                to.as_ref().to_string(),
+                self.bucket().to_string(),
+            )
+            .await
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub path: String,
+    pub is_dir: bool,
+    pub size: u64,
+    pub modified: Option<DateTime<FixedOffset>>,
+    pub etag: Option<String>,
+    pub mime_type: Option<String>,
+    pub health: Option<Percentage>,
+}
+
+impl Entry {
+    fn directory(path: String) -> Self {
+        Self {
+            path,
+            is_dir: true,
+            size: 0,
+            modified: None,
+            etag: None,
+            mime_type: None,
+            health: None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.path
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .unwrap_or_default()
+    }
+}
+
+impl From<Metadata> for Entry {
+    fn from(metadata: Metadata) -> Self {
+        Self {
+            is_dir: metadata.name.ends_with('/'),
+            path: metadata.name,
+            size: metadata.size,
+            modified: Some(metadata.mod_time),
+            etag: metadata.etag,
+            mime_type: metadata.mime_type,
+            health: Some(metadata.health),
+        }
+    }
+}
+
+type Reader = Box<dyn AsyncRead + Send + Unpin>;
+
+enum ReadState {
+    Idle,
+    Opening(BoxFuture<'static, Result<Reader, Error>>),
+    Reading(Reader),
+}
+
+pub struct File {
+    object: Arc<DownloadableObject>,
+    position: u64,
+    state: ReadState,
+}
+
+impl File {
+    fn new(object: DownloadableObject) -> Self {
+        Self {
+            object: Arc::new(object),
+            position: 0,
+            state: ReadState::Idle,
+        }
+    }
+
+    pub fn object(&self) -> &DownloadableObject {
+        &self.object
+    }
+}
+
+impl AsyncRead for File {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let this = self.get_mut();
+        loop {
+            if this.object.length.is_some_and(|len| this.position >= len) {
+                return Poll::Ready(Ok(0));
+            }
+            match &mut this.state {
+                ReadState::Idle => {
+                    let object = this.object.clone();
+                    let offset = (this.position > 0).then_some(this.position);
+                    this.state = ReadState::Opening(
+                        async move {
+                            object
+                                .open_stream(offset)
+                                .await
+                                .map(|reader| Box::new(reader) as Reader)
+                        }
+                        .boxed(),
+                    );
+                }
+                ReadState::Opening(future) => match ready!(future.poll_unpin(cx)) {
+                    Ok(reader) => this.state = ReadState::Reading(reader),
+                    Err(err) => {
+                        this.state = ReadState::Idle;
+                        return Poll::Ready(Err(into_io_error(err)));
+                    }
+                },
+                ReadState::Reading(reader) => {
+                    let n = ready!(Pin::new(reader).poll_read(cx, buf))?;
+                    this.position += n as u64;
+                    return Poll::Ready(Ok(n));
+                }
+            }
+        }
+    }
+}
+
+impl AsyncSeek for File {
+    fn poll_seek(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        pos: SeekFrom,
+    ) -> Poll<std::io::Result<u64>> {
+        let this = self.get_mut();
+        let position = seek_position(this.position, this.object.length, pos)?;
+        if position != this.position {
+            if !this.object.seekable {
+                return Poll::Ready(Err(into_io_error(Error::NotSeekable(
+                    this.object.path.clone(),
+                ))));
+            }
+            this.position = position;
+            this.state = ReadState::Idle;
+        }
+        Poll::Ready(Ok(position))
+    }
+}
+
+fn seek_position(current: u64, length: Option<u64>, pos: SeekFrom) -> std::io::Result<u64> {
+    let (base, delta) = match pos {
+        SeekFrom::Start(offset) => return Ok(offset),
+        SeekFrom::Current(delta) => (current, delta),
+        SeekFrom::End(delta) => (
+            length.ok_or_else(|| {
+                std::io::Error::new(std::io::ErrorKind::Unsupported, "object length unknown")
+            })?,
+            delta,
+        ),
+    };
+    base.checked_add_signed(delta).ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "invalid seek to a negative or overflowing position",
+        )
+    })
+}
+
+// the upload only ends cleanly after `finish`, a writer that is dropped or aborted fails the
+// stream so that renterd discards the partially uploaded object
+fn upload_body(
+    receiver: mpsc::Receiver<std::io::Result<Bytes>>,
+    finished: Arc<AtomicBool>,
+) -> impl AsyncRead + Send + Sync + Unpin {
+    receiver
+        .chain(futures::stream::once(futures::future::lazy(move |_| {
+            if finished.load(Ordering::Acquire) {
+                Ok(Bytes::new())
+            } else {
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::Interrupted,
+                    "upload aborted",
+                ))
+            }
+        })))
+        .into_async_read()
+}
+
+pub struct FileWriter {
+    sender: mpsc::Sender<std::io::Result<Bytes>>,
+    finished: Arc<AtomicBool>,
+    upload: JoinHandle<Result<(), Error>>,
+    // set once `upload` returned its result, a `JoinHandle` must not be polled again after that
+    joined: bool,
+}
+
+impl FileWriter {
+    pub async fn finish(mut self) -> Result<(), Error> {
+        self.finished.store(true, Ordering::Release);
+        self.sender.close_channel();
+        if self.joined {
+            return Ok(());
+        }
+        self.joined = true;
+        upload_result((&mut self.upload).await)
+    }
+
+    // fails the upload stream so that renterd discards the partially uploaded object
+    pub async fn abort(mut self) {
+        let _ = self
+            .sender
+            .send(Err(std::io::Error::new(
+                std::io::ErrorKind::Interrupted,
+                "upload aborted",
+            )))
+            .await;
+        self.sender.close_channel();
+        if !self.joined {
+            let _ = (&mut self.upload).await;
+        }
+    }
+}
+
+impl Drop for FileWriter {
+    fn drop(&mut self) {
+        // no-op if the upload already completed through `finish` or `abort`
+        self.upload.abort();
+    }
+}
+
+impl AsyncWrite for FileWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.sender.poll_ready(cx)).map_err(|_| upload_ended())?;
+        this.sender
+            .start_send(Ok(Bytes::copy_from_slice(buf)))
+            .map_err(|_| upload_ended())?;
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.get_mut()
+            .sender
+            .poll_flush_unpin(cx)
+            .map_err(|_| upload_ended())
+    }
+
+    // only completes once the upload did, so the writer can be dropped afterwards
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        this.finished.store(true, Ordering::Release);
+        ready!(this.sender.poll_close_unpin(cx)).map_err(|_| upload_ended())?;
+        if this.joined {
+            return Poll::Ready(Ok(()));
+        }
+        let result = ready!(this.upload.poll_unpin(cx));
+        this.joined = true;
+        Poll::Ready(upload_result(result).map_err(into_io_error))
+    }
+}
+
+fn upload_result(result: Result<Result<(), Error>, JoinError>) -> Result<(), Error> {
+    result.map_err(|e| Error::IoError(std::io::Error::other(e)))?
+}
+
+fn upload_ended() -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::BrokenPipe,
+        "upload ended before all data was written",
+    )
+}
+
+fn into_io_error(err: Error) -> std::io::Error {
+    match err {
+        Error::IoError(err) => err,
+        Error::NotFoundError => std::io::Error::new(std::io::ErrorKind::NotFound, err),
+        err => std::io::Error::other(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{block_on, directories, Request, Stub};
+    use bigdecimal::BigDecimal;
+    use futures::{AsyncReadExt, AsyncWriteExt};
+
+    #[test]
+    fn metadata() -> anyhow::Result<()> {
+        let stub = Stub::start(directories);
+        let fs = stub.client().fs(None);
+        block_on(async {
+            assert_eq!(fs.metadata("/missing").await?, None);
+            assert_eq!(fs.metadata("/missing/").await?, None);
+            assert_eq!(
+                fs.metadata("/dir").await?,
+                Some(Entry::directory("/dir/".to_string()))
+            );
+            assert_eq!(
+                fs.metadata("/empty/").await?,
+                Some(Entry::directory("/empty/".to_string()))
+            );
+            assert_eq!(
+                fs.metadata("/").await?,
+                Some(Entry::directory("/".to_string()))
+            );
+            Ok::<_, Error>(())
+        })?;
+        Ok(())
+    }
+
+    #[test]
+    fn rename_dir() -> anyhow::Result<()> {
+        let stub = Stub::start(|_: &Request| (200, String::new()));
+        let fs = stub.client().fs(None);
+        block_on(fs.rename("/a/", "/b"))?;
+        let body: serde_json::Value = serde_json::from_str(&stub.requests()[0].body)?;
+        assert_eq!(body["from"], "/a/");
+        assert_eq!(body["to"], "/b/");
+        assert_eq!(body["mode"], "multi");
+        Ok(())
+    }
+
+    #[test]
+    fn unfinished_upload() -> anyhow::Result<()> {
+        let read = |finish: bool| {
+            let (mut sender, receiver) = mpsc::channel(4);
+            let finished = Arc::new(AtomicBool::new(false));
+            let mut body = upload_body(receiver, finished.clone());
+            sender.try_send(Ok(Bytes::from_static(b"partial")))?;
+            finished.store(finish, Ordering::Release);
+            drop(sender);
+            let mut buf = vec![];
+            futures::executor::block_on(body.read_to_end(&mut buf))?;
+            Ok::<_, anyhow::Error>(buf)
+        };
+        assert_eq!(read(true)?, b"partial");
+        assert!(read(false).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn closed_upload() -> anyhow::Result<()> {
+        let stub = Stub::start(|_: &Request| (200, String::new()));
+        let fs = stub.client().fs(None);
+        block_on(async {
+            let mut writer = fs.create("/foo.txt", None);
+            writer.write_all(b"hello").await?;
+            writer.close().await?;
+            drop(writer);
+            Ok::<_, anyhow::Error>(())
+        })?;
+        let requests = stub.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "PUT");
+        assert!(requests[0].path.starts_with("/worker/objects/foo.txt"));
+        assert_eq!(requests[0].body, "hello");
+        Ok(())
+    }
+
+    #[test]
+    fn entry_names() {
+        assert_eq!(Entry::directory("/foo/bar/".to_string()).name(), "bar");
+        assert_eq!(Entry::directory("/".to_string()).name(), "");
+    }
+
+    #[test]
+    fn entry_from_metadata() -> anyhow::Result<()> {
+        let entry: Entry = Metadata {
+            etag: Some("d41d8cd98f00b204e9800998ecf8427e".to_string()),
+            health: Percentage::from_decimal(BigDecimal::from(1)),
+            mod_time: DateTime::parse_from_rfc3339("2024-07-05T12:37:58.998523074Z")?,
+            name: "/foo/report.pdf".to_string(),
+            size: 5586849,
+            mime_type: Some("application/pdf".to_string()),
+        }
+        .into();
+        assert!(!entry.is_dir);
+        assert_eq!(entry.name(), "report.pdf");
+        assert_eq!(entry.size, 5586849);
+
+        let entry: Entry = Metadata {
+            etag: None,
+            health: Percentage::from_decimal(BigDecimal::from(1)),
+            mod_time: DateTime::parse_from_rfc3339("2024-07-05T12:37:58.998523074Z")?,
+            name: "/foo/".to_string(),
+            size: 5586849,
+            mime_type: None,
+        }
+        .into();
+        assert!(entry.is_dir);
+        assert_eq!(entry.name(), "foo");
+        Ok(())
+    }
+
+    #[test]
+    fn seeking() -> anyhow::Result<()> {
+        assert_eq!(seek_position(10, Some(100), SeekFrom::Start(50))?, 50);
+        assert_eq!(seek_position(10, Some(100), SeekFrom::Current(5))?, 15);
+        assert_eq!(seek_position(10, Some(100), SeekFrom::Current(-10))?, 0);
+        assert_eq!(seek_position(10, Some(100), SeekFrom::End(-20))?, 80);
+        assert!(seek_position(10, Some(100), SeekFrom::Current(-11)).is_err());
+        assert!(seek_position(10, None, SeekFrom::End(0)).is_err());
+        Ok(())
+    }
+}
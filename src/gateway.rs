@@ -0,0 +1,581 @@
+use crate::bus::object::ListOptions;
+use crate::bus::Bus;
+use crate::worker::object::{Conditional, Conditions, DownloadableObject};
+use crate::worker::Worker;
+use crate::{Client, Either, Error, LIST_BATCH_SIZE};
+use bytes::Bytes;
+use chrono::{DateTime, FixedOffset, Utc};
+use futures::{AsyncRead, TryStreamExt};
+use http_body_util::combinators::BoxBody;
+use http_body_util::{BodyExt, Empty, Full, StreamBody};
+use hyper::body::{Frame, Incoming};
+use hyper::header::{
+    HeaderValue, ACCEPT_RANGES, ALLOW, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG,
+    IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, RANGE,
+};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{HeaderMap, Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use std::collections::BTreeSet;
+use std::convert::Infallible;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio_util::compat::FuturesAsyncReadCompatExt;
+use tokio_util::io::ReaderStream;
+
+pub type Body = BoxBody<Bytes, std::io::Error>;
+
+#[derive(Clone)]
+pub struct Gateway {
+    bus: Bus,
+    worker: Worker,
+    listings: bool,
+    buckets: Option<Arc<BTreeSet<String>>>,
+}
+
+impl Gateway {
+    pub fn new(client: &Client) -> Self {
+        Self {
+            bus: client.bus().clone(),
+            worker: client.worker().clone(),
+            listings: false,
+            buckets: None,
+        }
+    }
+
+    pub fn listings(mut self, listings: bool) -> Self {
+        self.listings = listings;
+        self
+    }
+
+    pub fn buckets<I: IntoIterator<Item = S>, S: ToString>(mut self, buckets: I) -> Self {
+        self.buckets = Some(Arc::new(
+            buckets.into_iter().map(|b| b.to_string()).collect(),
+        ));
+        self
+    }
+
+    pub async fn serve(self, listener: TcpListener) -> Result<(), Error> {
+        serve_connections(listener, move |req| {
+            let gateway = self.clone();
+            async move { gateway.handle(req).await }
+        })
+        .await
+    }
+
+    pub async fn handle<B>(&self, req: Request<B>) -> Response<Body> {
+        let head = req.method() == Method::HEAD;
+        if req.method() != Method::GET && !head {
+            let mut resp = status_response(StatusCode::METHOD_NOT_ALLOWED);
+            resp.headers_mut()
+                .insert(ALLOW, HeaderValue::from_static("GET, HEAD"));
+            return resp;
+        }
+
+        let resp = match parse_path(req.uri().path()) {
+            None => Ok(status_response(StatusCode::BAD_REQUEST)),
+            Some((None, _)) if self.listings => self.bucket_listing().await,
+            Some((Some(bucket), _)) if !self.allowed(&bucket) => {
+                Ok(status_response(StatusCode::NOT_FOUND))
+            }
+            Some((Some(bucket), key)) if key.ends_with('/') => {
+                if self.listings {
+                    self.listing(bucket, key).await
+                } else {
+                    Ok(status_response(StatusCode::NOT_FOUND))
+                }
+            }
+            Some((Some(bucket), key)) => self.object(bucket, key, req.headers(), head).await,
+            Some((None, _)) => Ok(status_response(StatusCode::NOT_FOUND)),
+        };
+
+        let mut resp = resp.unwrap_or_else(error_response);
+        if head {
+            *resp.body_mut() = empty();
+        }
+        resp
+    }
+
+    fn allowed(&self, bucket: &str) -> bool {
+        self.buckets
+            .as_ref()
+            .is_none_or(|buckets| buckets.contains(bucket))
+    }
+
+    async fn object(
+        &self,
+        bucket: String,
+        key: String,
+        headers: &HeaderMap,
+        head: bool,
+    ) -> Result<Response<Body>, Error> {
+        let conditions = conditions(headers);
+        let range = headers
+            .get(RANGE)
+            .and_then(|range| range.to_str().ok())
+            .and_then(parse_range);
+
+        if head || range.is_some() {
+            let object = match self
+                .worker
+                .object()
+                .download_conditional(&key, Some(bucket), &conditions)
+                .await?
+            {
+                Some(Conditional::Modified(object)) => object.pin_etag(true),
+                Some(conditional) => return Ok(conditional_status(conditional)),
+                None => return Ok(status_response(StatusCode::NOT_FOUND)),
+            };
+            if head {
+                return Ok(object_response(
+                    &object,
+                    StatusCode::OK,
+                    object.length,
+                    empty(),
+                ));
+            }
+            let range = match (range, object.length.filter(|_| object.seekable)) {
+                (Some(range), Some(length)) => range.resolve(length).ok_or(length),
+                // the worker is unable to serve ranges for this object, fall back to the full content
+                _ => {
+                    let body = reader_body(object.open_stream(None).await?);
+                    return Ok(object_response(
+                        &object,
+                        StatusCode::OK,
+                        object.length,
+                        body,
+                    ));
+                }
+            };
+            return Ok(match range {
+                Ok((offset, length)) => {
+                    let body = reader_body(object.open_range(offset, length).await?);
+                    let mut resp =
+                        object_response(&object, StatusCode::PARTIAL_CONTENT, Some(length), body);
+                    resp.headers_mut().insert(
+                        CONTENT_RANGE,
+                        header_value(format!(
+                            "bytes {}-{}/{}",
+                            offset,
+                            offset + length - 1,
+                            object.length.unwrap_or_default()
+                        )),
+                    );
+                    resp
+                }
+                Err(total) => {
+                    let mut resp = status_response(StatusCode::RANGE_NOT_SATISFIABLE);
+                    resp.headers_mut()
+                        .insert(CONTENT_RANGE, header_value(format!("bytes */{}", total)));
+                    resp
+                }
+            });
+        }
+
+        match self
+            .worker
+            .object()
+            .open_conditional(key, Some(bucket), &conditions)
+            .await?
+        {
+            Some(Conditional::Modified((object, reader))) => Ok(object_response(
+                &object,
+                StatusCode::OK,
+                object.length,
+                reader_body(reader),
+            )),
+            Some(Conditional::NotModified) => Ok(status_response(StatusCode::NOT_MODIFIED)),
+            Some(Conditional::PreconditionFailed) => {
+                Ok(status_response(StatusCode::PRECONDITION_FAILED))
+            }
+            None => Ok(status_response(StatusCode::NOT_FOUND)),
+        }
+    }
+
+    async fn listing(&self, bucket: String, key: String) -> Result<Response<Body>, Error> {
+        let mut entries = vec![];
+        match self
+            .bus
+            .object()
+            .get_stream(
+                &key,
+                LIST_BATCH_SIZE,
+                &ListOptions::default(),
+                Some(bucket.clone()),
+            )
+            .await?
+        {
+            Some(Either::Right(mut stream)) => {
+                while let Some(batch) = stream.try_next().await? {
+                    entries.extend(batch.into_iter().map(|metadata| ListingEntry {
+                        name: metadata.name,
+                        size: Some(metadata.size),
+                        modified: Some(metadata.mod_time),
+                    }));
+                }
+            }
+            Some(Either::Left(_)) | None => return Ok(status_response(StatusCode::NOT_FOUND)),
+        }
+        if entries.is_empty() && key != "/" {
+            return Ok(status_response(StatusCode::NOT_FOUND));
+        }
+        let prefix = format!("/{}", encode_path(&bucket));
+        Ok(html_response(render_listing(
+            &format!("/{}{}", bucket, key),
+            &prefix,
+            &entries,
+        )))
+    }
+
+    async fn bucket_listing(&self) -> Result<Response<Body>, Error> {
+        let entries: Vec<_> = self
+            .bus
+            .bucket()
+            .get_all()
+            .await?
+            .into_iter()
+            .filter(|bucket| self.allowed(&bucket.name))
+            .map(|bucket| ListingEntry {
+                name: format!("/{}/", bucket.name),
+                size: None,
+                modified: Some(bucket.created_at),
+            })
+            .collect();
+        Ok(html_response(render_listing("/", "", &entries)))
+    }
+}
+
+pub(crate) async fn serve_connections<H, F>(listener: TcpListener, handler: H) -> Result<(), Error>
+where
+    H: Fn(Request<Incoming>) -> F + Clone + Send + 'static,
+    F: Future<Output = Response<Body>> + Send + 'static,
+{
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let handler = handler.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |req| {
+                let resp = handler(req);
+                async move { Ok::<_, Infallible>(resp.await) }
+            });
+            let _ = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await;
+        });
+    }
+}
+
+pub(crate) fn parse_path(path: &str) -> Option<(Option<String>, String)> {
+    let path = urlencoding::decode(path).ok()?;
+    let path = path.strip_prefix('/')?;
+    if path.is_empty() {
+        return Some((None, "/".to_string()));
+    }
+    let (bucket, key) = match path.split_once('/') {
+        Some((bucket, key)) => (bucket, format!("/{}", key)),
+        None => (path, "/".to_string()),
+    };
+    if bucket.is_empty() || key.split('/').any(|segment| segment == "..") {
+        return None;
+    }
+    Some((Some(bucket.to_string()), key))
+}
+
+fn conditions(headers: &HeaderMap) -> Conditions {
+    let mut conditions = Conditions::default();
+    if let Some(etag) = headers.get(IF_NONE_MATCH).and_then(|v| v.to_str().ok()) {
+        conditions = conditions.if_none_match(etag);
+    }
+    if let Some(date) = headers
+        .get(IF_MODIFIED_SINCE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| DateTime::parse_from_rfc2822(v).ok())
+    {
+        conditions = conditions.if_modified_since(date);
+    }
+    conditions
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ByteRange {
+    Bounded(u64, u64),
+    From(u64),
+    Suffix(u64),
+}
+
+impl ByteRange {
+    // returns `(offset, length)` or `None` if the range is not satisfiable
+    fn resolve(self, total: u64) -> Option<(u64, u64)> {
+        match self {
+            ByteRange::Bounded(start, end) if start < total => {
+                Some((start, end.min(total - 1) - start + 1))
+            }
+            ByteRange::From(start) if start < total => Some((start, total - start)),
+            ByteRange::Suffix(length) if length > 0 && total > 0 => {
+                let length = length.min(total);
+                Some((total - length, length))
+            }
+            _ => None,
+        }
+    }
+}
+
+// only single ranges are supported, multipart ranges are ignored and served in full
+fn parse_range(header: &str) -> Option<ByteRange> {
+    let range = header.trim().strip_prefix("bytes=")?.trim();
+    if range.contains(',') {
+        return None;
+    }
+    let (start, end) = range.split_once('-')?;
+    let (start, end) = (start.trim(), end.trim());
+    match (start.is_empty(), end.is_empty()) {
+        (true, false) => Some(ByteRange::Suffix(end.parse().ok()?)),
+        (false, true) => Some(ByteRange::From(start.parse().ok()?)),
+        (false, false) => {
+            let (start, end) = (start.parse().ok()?, end.parse().ok()?);
+            (start <= end).then_some(ByteRange::Bounded(start, end))
+        }
+        (true, true) => None,
+    }
+}
+
+struct ListingEntry {
+    name: String,
+    size: Option<u64>,
+    modified: Option<DateTime<FixedOffset>>,
+}
+
+fn render_listing(title: &str, prefix: &str, entries: &[ListingEntry]) -> String {
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Index of {0}</title></head>\n<body><h1>Index of {0}</h1>\n<table>\n",
+        escape_html(title)
+    );
+    for entry in entries {
+        let name = entry
+            .name
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .unwrap_or_default();
+        let suffix = if entry.name.ends_with('/') { "/" } else { "" };
+        html.push_str(&format!(
+            "<tr><td><a href=\"{}{}\">{}{}</a></td><td>{}</td><td>{}</td></tr>\n",
+            prefix,
+            encode_path(&entry.name),
+            escape_html(name),
+            suffix,
+            entry.size.map(|s| s.to_string()).unwrap_or_default(),
+            entry.modified.map(|m| http_date(&m)).unwrap_or_default(),
+        ));
+    }
+    html.push_str("</table>\n</body></html>\n");
+    html
+}
+
+pub(crate) fn encode_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| urlencoding::encode(segment).into_owned())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+pub(crate) fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+pub(crate) fn http_date(date: &DateTime<FixedOffset>) -> String {
+    date.with_timezone(&Utc)
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
+
+pub(crate) fn header_value(value: String) -> HeaderValue {
+    HeaderValue::try_from(value).unwrap_or_else(|_| HeaderValue::from_static(""))
+}
+
+pub(crate) fn empty() -> Body {
+    Empty::new().map_err(|never| match never {}).boxed()
+}
+
+pub(crate) fn full<B: Into<Bytes>>(content: B) -> Body {
+    Full::new(content.into())
+        .map_err(|never| match never {})
+        .boxed()
+}
+
+pub(crate) fn reader_body<R: AsyncRead + Send + Sync + Unpin + 'static>(reader: R) -> Body {
+    BodyExt::boxed(StreamBody::new(
+        ReaderStream::new(reader.compat()).map_ok(Frame::data),
+    ))
+}
+
+pub(crate) fn status_response(status: StatusCode) -> Response<Body> {
+    let mut resp = Response::new(empty());
+    *resp.status_mut() = status;
+    resp
+}
+
+pub(crate) fn error_response(err: Error) -> Response<Body> {
+    let status = match &err {
+        Error::NotFoundError => StatusCode::NOT_FOUND,
+        Error::ObjectModified(_) | Error::ObjectExists(_) => StatusCode::PRECONDITION_FAILED,
+        Error::InvalidPrefixOperation(_) => StatusCode::FORBIDDEN,
+        Error::NotADirectory(_) => StatusCode::CONFLICT,
+        _ => StatusCode::BAD_GATEWAY,
+    };
+    let mut resp = Response::new(full(err.to_string()));
+    *resp.status_mut() = status;
+    resp.headers_mut().insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static("text/plain; charset=utf-8"),
+    );
+    resp
+}
+
+fn conditional_status<T>(conditional: Conditional<T>) -> Response<Body> {
+    status_response(match conditional {
+        Conditional::NotModified => StatusCode::NOT_MODIFIED,
+        _ => StatusCode::PRECONDITION_FAILED,
+    })
+}
+
+fn html_response(html: String) -> Response<Body> {
+    let mut resp = Response::new(full(html));
+    resp.headers_mut().insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static("text/html; charset=utf-8"),
+    );
+    resp
+}
+
+fn object_response(
+    object: &DownloadableObject,
+    status: StatusCode,
+    length: Option<u64>,
+    body: Body,
+) -> Response<Body> {
+    let mut resp = Response::new(body);
+    *resp.status_mut() = status;
+    let headers = resp.headers_mut();
+    headers.insert(
+        CONTENT_TYPE,
+        header_value(
+            object
+                .content_type
+                .clone()
+                .unwrap_or_else(|| "application/octet-stream".to_string()),
+        ),
+    );
+    if let Some(length) = length {
+        headers.insert(CONTENT_LENGTH, header_value(length.to_string()));
+    }
+    if let Some(etag) = &object.etag {
+        headers.insert(ETAG, header_value(etag.clone()));
+    }
+    if let Some(last_modified) = &object.last_modified {
+        headers.insert(LAST_MODIFIED, header_value(http_date(last_modified)));
+    }
+    if object.seekable {
+        headers.insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    }
+    resp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path() {
+        assert_eq!(parse_path("/"), Some((None, "/".to_string())));
+        assert_eq!(
+            parse_path("/default"),
+            Some((Some("default".to_string()), "/".to_string()))
+        );
+        assert_eq!(
+            parse_path("/default/"),
+            Some((Some("default".to_string()), "/".to_string()))
+        );
+        assert_eq!(
+            parse_path("/default/foo/bar%20baz.txt"),
+            Some((Some("default".to_string()), "/foo/bar baz.txt".to_string()))
+        );
+        assert_eq!(
+            parse_path("/default/foo/"),
+            Some((Some("default".to_string()), "/foo/".to_string()))
+        );
+        assert_eq!(parse_path("//foo"), None);
+        assert_eq!(parse_path("/default/../foo"), None);
+    }
+
+    #[test]
+    fn range() {
+        assert_eq!(parse_range("bytes=0-499"), Some(ByteRange::Bounded(0, 499)));
+        assert_eq!(parse_range("bytes=500-"), Some(ByteRange::From(500)));
+        assert_eq!(parse_range("bytes=-500"), Some(ByteRange::Suffix(500)));
+        assert_eq!(parse_range("bytes=0-1,5-6"), None);
+        assert_eq!(parse_range("bytes=5-1"), None);
+        assert_eq!(parse_range("items=0-1"), None);
+        assert_eq!(parse_range("bytes=-"), None);
+
+        assert_eq!(ByteRange::Bounded(0, 499).resolve(1000), Some((0, 500)));
+        assert_eq!(
+            ByteRange::Bounded(900, 1999).resolve(1000),
+            Some((900, 100))
+        );
+        assert_eq!(ByteRange::Bounded(1000, 1999).resolve(1000), None);
+        assert_eq!(ByteRange::From(100).resolve(1000), Some((100, 900)));
+        assert_eq!(ByteRange::From(1000).resolve(1000), None);
+        assert_eq!(ByteRange::Suffix(100).resolve(1000), Some((900, 100)));
+        assert_eq!(ByteRange::Suffix(2000).resolve(1000), Some((0, 1000)));
+        assert_eq!(ByteRange::Suffix(0).resolve(1000), None);
+    }
+
+    #[test]
+    fn condition_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(IF_NONE_MATCH, HeaderValue::from_static("\"abc\""));
+        headers.insert(
+            IF_MODIFIED_SINCE,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        let conditions = conditions(&headers);
+        assert_eq!(conditions.if_none_match, Some("\"abc\"".to_string()));
+        assert_eq!(
+            conditions.if_modified_since,
+            Some(DateTime::parse_from_rfc3339("2015-10-21T07:28:00Z").unwrap())
+        );
+        assert_eq!(conditions.if_match, None);
+    }
+
+    #[test]
+    fn listing() {
+        let html = render_listing(
+            "/default/foo/",
+            "/default",
+            &[
+                ListingEntry {
+                    name: "/foo/a <b>.txt".to_string(),
+                    size: Some(12),
+                    modified: Some(DateTime::parse_from_rfc3339("2015-10-21T07:28:00Z").unwrap()),
+                },
+                ListingEntry {
+                    name: "/foo/sub/".to_string(),
+                    size: None,
+                    modified: None,
+                },
+            ],
+        );
+        assert!(html.contains("<title>Index of /default/foo/</title>"));
+        assert!(html.contains(
+            "<a href=\"/default/foo/a%20%3Cb%3E.txt\">a &lt;b&gt;.txt</a></td><td>12</td><td>Wed, 21 Oct 2015 07:28:00 GMT</td>"
+        ));
+        assert!(html.contains("<a href=\"/default/foo/sub/\">sub/</a></td><td></td><td></td>"));
+    }
+}
@@ -0,0 +1,626 @@
+use crate::bus::alert::{Alert, Severity};
+use crate::bus::contract::Contract;
+use crate::bus::host::Host;
+use crate::bus::object::{ListOptions, Metadata, Object, SortBy, SortDir};
+use crate::bus::Bus;
+use crate::{Either, Error, FileContractId, Hash, PublicKey, DEFAULT_CONCURRENCY, LIST_BATCH_SIZE};
+use bigdecimal::{BigDecimal, One, Zero};
+use chrono::Utc;
+use futures::{Stream, StreamExt, TryStreamExt};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+
+#[derive(Clone)]
+pub struct Api {
+    bus: Bus,
+}
+
+impl Api {
+    pub(super) fn new(bus: Bus) -> Self {
+        Self { bus }
+    }
+
+    pub fn scan(
+        &self,
+        options: &ScanOptions,
+    ) -> impl Stream<Item = Result<DegradedObject, Error>> + Send + Unpin {
+        let bus = self.bus.clone();
+        let options = options.clone();
+
+        futures::stream::once(async move {
+            let buckets = match &options.bucket {
+                Some(bucket) => vec![bucket.clone()],
+                None => bus
+                    .bucket()
+                    .get_all()
+                    .await?
+                    .into_iter()
+                    .map(|bucket| bucket.name)
+                    .collect(),
+            };
+            let context = Arc::new(Context::new(
+                bus.contract().get_all(None).await?,
+                bus.host().get_all(None, None).await?,
+            ));
+
+            Ok::<_, Error>(
+                futures::stream::iter(buckets)
+                    .map(move |bucket| {
+                        scan_bucket(bus.clone(), bucket, options.clone(), context.clone())
+                    })
+                    .flatten(),
+            )
+        })
+        .try_flatten()
+        .boxed()
+    }
+
+    pub async fn report(&self, options: &ScanOptions) -> Result<HealthReport, Error> {
+        let objects = self.scan(options).try_collect().await?;
+        Ok(HealthReport::new(options.threshold.clone(), objects))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanOptions {
+    pub bucket: Option<String>,
+    pub prefix: Option<String>,
+    pub threshold: BigDecimal,
+    pub concurrency: NonZeroUsize,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            bucket: None,
+            prefix: None,
+            threshold: BigDecimal::one(),
+            concurrency: DEFAULT_CONCURRENCY,
+        }
+    }
+}
+
+impl ScanOptions {
+    pub fn bucket<S: ToString>(mut self, bucket: S) -> Self {
+        self.bucket = Some(bucket.to_string());
+        self
+    }
+
+    pub fn prefix<S: ToString>(mut self, prefix: S) -> Self {
+        self.prefix = Some(prefix.to_string());
+        self
+    }
+
+    pub fn threshold(mut self, threshold: BigDecimal) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    pub fn concurrency(mut self, concurrency: NonZeroUsize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DegradedObject {
+    pub bucket: String,
+    pub name: String,
+    pub size: u64,
+    #[serde(with = "bigdecimal::serde::json_num")]
+    pub health: BigDecimal,
+    pub slabs: Vec<DegradedSlab>,
+}
+
+impl DegradedObject {
+    pub fn prefix(&self) -> &str {
+        match self.name.rfind('/') {
+            Some(i) => &self.name[..=i],
+            None => "/",
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DegradedSlab {
+    pub index: usize,
+    #[serde(
+        with = "bigdecimal::serde::json_num_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub health: Option<BigDecimal>,
+    pub min_shards: u8,
+    pub total_shards: usize,
+    pub good_shards: usize,
+    pub bad_shards: Vec<BadShard>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct BadShard {
+    pub index: usize,
+    pub host: PublicKey,
+    pub issues: Vec<HostIssue>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum HostIssue {
+    NoActiveContract,
+    UnknownHost,
+    Blocked,
+    Offline,
+    LostSectors { sectors: u64 },
+    Unusable { reasons: Vec<String> },
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthReport {
+    #[serde(with = "bigdecimal::serde::json_num")]
+    pub threshold: BigDecimal,
+    pub objects: Vec<DegradedObject>,
+    pub prefixes: BTreeMap<String, BTreeMap<String, PrefixSummary>>,
+    pub hosts: BTreeMap<PublicKey, HostSummary>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PrefixSummary {
+    pub objects: u64,
+    pub size: u64,
+    #[serde(with = "bigdecimal::serde::json_num")]
+    pub min_health: BigDecimal,
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct HostSummary {
+    pub issues: BTreeSet<HostIssue>,
+    pub objects: u64,
+    pub shards: u64,
+}
+
+impl HealthReport {
+    pub fn new(threshold: BigDecimal, objects: Vec<DegradedObject>) -> Self {
+        let mut prefixes: BTreeMap<String, BTreeMap<String, PrefixSummary>> = BTreeMap::new();
+        let mut hosts: BTreeMap<PublicKey, HostSummary> = BTreeMap::new();
+
+        for object in &objects {
+            prefixes
+                .entry(object.bucket.clone())
+                .or_default()
+                .entry(object.prefix().to_string())
+                .and_modify(|summary| {
+                    summary.objects += 1;
+                    summary.size += object.size;
+                    if object.health < summary.min_health {
+                        summary.min_health = object.health.clone();
+                    }
+                })
+                .or_insert_with(|| PrefixSummary {
+                    objects: 1,
+                    size: object.size,
+                    min_health: object.health.clone(),
+                });
+
+            let mut seen = BTreeSet::new();
+            for shard in object.slabs.iter().flat_map(|slab| &slab.bad_shards) {
+                let summary = hosts.entry(shard.host.clone()).or_default();
+                summary.shards += 1;
+                summary.issues.extend(shard.issues.iter().cloned());
+                if seen.insert(&shard.host) {
+                    summary.objects += 1;
+                }
+            }
+        }
+
+        Self {
+            threshold,
+            objects,
+            prefixes,
+            hosts,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    pub fn min_health(&self) -> Option<&BigDecimal> {
+        self.objects.iter().map(|object| &object.health).min()
+    }
+
+    // registering the alert under the same `id` again replaces the previous report
+    pub fn to_alert(&self, id: Hash) -> Option<Alert> {
+        let min_health = self.min_health()?;
+        let severity = if *min_health < BigDecimal::zero() {
+            // fewer than `min_shards` left, the object can't be recovered
+            Severity::Critical
+        } else if *min_health < BigDecimal::new(25.into(), 2) {
+            Severity::Error
+        } else {
+            Severity::Warning
+        };
+
+        let data = BTreeMap::from([
+            ("objects".to_string(), self.objects.len().into()),
+            (
+                "minHealth".to_string(),
+                serde_json::to_value(bigdecimal_json(min_health)).ok()?,
+            ),
+            (
+                "prefixes".to_string(),
+                serde_json::to_value(&self.prefixes).ok()?,
+            ),
+            ("hosts".to_string(), serde_json::to_value(&self.hosts).ok()?),
+        ]);
+
+        Some(Alert {
+            id,
+            severity,
+            message: format!(
+                "{} objects below {} health, {} hosts involved",
+                self.objects.len(),
+                self.threshold.normalized(),
+                self.hosts.len()
+            ),
+            data: Some(data),
+            timestamp: Utc::now().fixed_offset(),
+        })
+    }
+}
+
+fn bigdecimal_json(value: &BigDecimal) -> serde_json::Value {
+    #[derive(Serialize)]
+    struct Wrapper<'a>(#[serde(with = "bigdecimal::serde::json_num")] &'a BigDecimal);
+    serde_json::to_value(Wrapper(value)).unwrap_or_default()
+}
+
+struct Context {
+    active_contracts: BTreeSet<FileContractId>,
+    hosts: BTreeMap<PublicKey, Vec<HostIssue>>,
+}
+
+impl Context {
+    fn new(contracts: Vec<Contract>, hosts: Vec<Host>) -> Self {
+        Self {
+            active_contracts: contracts.into_iter().map(|c| c.id).collect(),
+            hosts: hosts
+                .into_iter()
+                .map(|host| (host.public_key.clone(), host_issues(&host)))
+                .collect(),
+        }
+    }
+
+    fn inspect(&self, bucket: String, object: Object) -> DegradedObject {
+        let slabs = object
+            .slabs
+            .unwrap_or_default()
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, slice)| {
+                let slab = slice.slab;
+                let bad_shards: Vec<BadShard> = slab
+                    .shards
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(index, sector)| {
+                        let issues = self.shard_issues(
+                            &sector.latest_host,
+                            sector.contracts.get(&sector.latest_host),
+                        );
+                        (!issues.is_empty()).then(|| BadShard {
+                            index,
+                            host: sector.latest_host.clone(),
+                            issues,
+                        })
+                    })
+                    .collect();
+                let degraded = !bad_shards.is_empty()
+                    || slab
+                        .health
+                        .as_ref()
+                        .is_some_and(|health| *health < BigDecimal::one());
+                degraded.then(|| DegradedSlab {
+                    index,
+                    health: slab.health.clone(),
+                    min_shards: slab.min_shards,
+                    total_shards: slab.shards.len(),
+                    good_shards: slab.shards.len() - bad_shards.len(),
+                    bad_shards,
+                })
+            })
+            .collect();
+
+        DegradedObject {
+            bucket,
+            name: object.metadata.name,
+            size: object.metadata.size,
+            health: object.metadata.health.as_decimal().clone(),
+            slabs,
+        }
+    }
+
+    fn shard_issues(
+        &self,
+        host: &PublicKey,
+        contracts: Option<&Vec<FileContractId>>,
+    ) -> Vec<HostIssue> {
+        let mut issues = vec![];
+        if !contracts
+            .into_iter()
+            .flatten()
+            .any(|id| self.active_contracts.contains(id))
+        {
+            issues.push(HostIssue::NoActiveContract);
+        }
+        match self.hosts.get(host) {
+            Some(host_issues) => issues.extend(host_issues.iter().cloned()),
+            None => issues.push(HostIssue::UnknownHost),
+        }
+        issues
+    }
+}
+
+fn host_issues(host: &Host) -> Vec<HostIssue> {
+    let mut issues = vec![];
+    if host.blocked {
+        issues.push(HostIssue::Blocked);
+    }
+    if host.interactions.total_scans > 1
+        && !host.interactions.last_scan_success
+        && !host.interactions.second_to_last_scan_success
+    {
+        issues.push(HostIssue::Offline);
+    }
+    if host.interactions.lost_sectors > 0 {
+        issues.push(HostIssue::LostSectors {
+            sectors: host.interactions.lost_sectors,
+        });
+    }
+    let reasons: BTreeSet<&str> = host
+        .checks
+        .values()
+        .flat_map(|check| check.usability.reasons())
+        .collect();
+    if !reasons.is_empty() {
+        issues.push(HostIssue::Unusable {
+            reasons: reasons.into_iter().map(str::to_string).collect(),
+        });
+    }
+    issues
+}
+
+fn scan_bucket(
+    bus: Bus,
+    bucket: String,
+    options: ScanOptions,
+    context: Arc<Context>,
+) -> impl Stream<Item = Result<DegradedObject, Error>> + Send + Unpin {
+    let mut list_options = ListOptions::new()
+        .sort_by(SortBy::Health)
+        .sort_dir(SortDir::Asc);
+    if let Some(prefix) = &options.prefix {
+        list_options = list_options.prefix(prefix);
+    }
+    let threshold = options.threshold.clone();
+    let listing = bus
+        .object()
+        .list(LIST_BATCH_SIZE, &list_options, Some(bucket.clone()));
+
+    futures::stream::once(futures::future::ready(listing))
+        .try_flatten()
+        .map_ok(|batch: Vec<Metadata>| futures::stream::iter(batch.into_iter().map(Ok)))
+        .try_flatten()
+        // sorted by health, so no further pages are needed once the threshold is reached
+        .try_take_while(move |m| futures::future::ready(Ok(*m.health.as_decimal() < threshold)))
+        .try_filter(|m| futures::future::ready(!m.name.ends_with('/')))
+        .map_ok(move |metadata| {
+            let bus = bus.clone();
+            let bucket = bucket.clone();
+            let context = context.clone();
+            async move {
+                // the listing doesn't include slabs
+                match bus
+                    .object()
+                    .get(&metadata.name, Some(bucket.clone()), None, None, None, None)
+                    .await?
+                {
+                    Some(Either::Left(object)) => Ok(Some(context.inspect(bucket, object))),
+                    // removed in the meantime
+                    _ => Ok(None),
+                }
+            }
+        })
+        .try_buffered(options.concurrency.get())
+        .try_filter_map(|object| futures::future::ready(Ok(object)))
+        .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{block_on, Stub};
+    use std::str::FromStr;
+
+    const GOOD_HOST: &str =
+        "ed25519:1111111111111111111111111111111111111111111111111111111111111111";
+    const BAD_HOST: &str =
+        "ed25519:2222222222222222222222222222222222222222222222222222222222222222";
+    const GONE_HOST: &str =
+        "ed25519:3333333333333333333333333333333333333333333333333333333333333333";
+    const ACTIVE: &str = "fcid:aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+    const EXPIRED: &str = "fcid:bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+
+    fn context() -> anyhow::Result<Context> {
+        Ok(Context {
+            active_contracts: BTreeSet::from([FileContractId::try_from(ACTIVE)?]),
+            hosts: BTreeMap::from([
+                (PublicKey::try_from(GOOD_HOST)?, vec![]),
+                (
+                    PublicKey::try_from(BAD_HOST)?,
+                    vec![
+                        HostIssue::Blocked,
+                        HostIssue::LostSectors { sectors: 3 },
+                        HostIssue::Unusable {
+                            reasons: vec!["gouging".to_string()],
+                        },
+                    ],
+                ),
+            ]),
+        })
+    }
+
+    fn object(name: &str, health: &str) -> anyhow::Result<Object> {
+        let shard = |host: &str, fcid: &str| {
+            format!(
+                r#"{{"contracts": {{"{host}": ["{fcid}"]}}, "latestHost": "{host}", "root": "h:0000000000000000000000000000000000000000000000000000000000000000"}}"#,
+            )
+        };
+        Ok(serde_json::from_str(&format!(
+            r#"{{
+    "eTag": "x",
+    "health": {health},
+    "modTime": "2024-07-05T12:37:58Z",
+    "name": "{name}",
+    "size": 100,
+    "key": "key:aba60a4c1b9ff360214a68f09f890f9afc00d1bf23c8c9435a02311b10ff1d61",
+    "slabs": [
+        {{
+            "slab": {{
+                "health": {health},
+                "key": "key:6317e69fb2048ed2137e245b19b91b6f037d929db17c0d9a70cb47be3544b2af",
+                "minShards": 1,
+                "shards": [{}, {}, {}]
+            }},
+            "offset": 0,
+            "length": 100
+        }}
+    ]
+}}"#,
+            shard(GOOD_HOST, ACTIVE),
+            shard(BAD_HOST, ACTIVE),
+            shard(GONE_HOST, EXPIRED),
+        ))?)
+    }
+
+    #[test]
+    fn inspect() -> anyhow::Result<()> {
+        let context = context()?;
+        let degraded = context.inspect("default".to_string(), object("/foo/bar", "0.5")?);
+        assert_eq!(degraded.prefix(), "/foo/");
+        assert_eq!(degraded.slabs.len(), 1);
+        let slab = &degraded.slabs[0];
+        assert_eq!(slab.total_shards, 3);
+        assert_eq!(slab.good_shards, 1);
+        assert_eq!(slab.bad_shards[0].index, 1);
+        assert_eq!(
+            slab.bad_shards[0].issues,
+            vec![
+                HostIssue::Blocked,
+                HostIssue::LostSectors { sectors: 3 },
+                HostIssue::Unusable {
+                    reasons: vec!["gouging".to_string()]
+                },
+            ]
+        );
+        assert_eq!(slab.bad_shards[1].index, 2);
+        assert_eq!(
+            slab.bad_shards[1].issues,
+            vec![HostIssue::NoActiveContract, HostIssue::UnknownHost]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn scan_stops_at_threshold() -> anyhow::Result<()> {
+        let stub = Stub::start(|req| {
+            if req.is("POST", "/bus/objects/list") {
+                let entry = |name: &str, health: &str| {
+                    format!(
+                        r#"{{"name": "{name}", "size": 1, "health": {health}, "modTime": "2024-06-27T11:56:19Z"}}"#
+                    )
+                };
+                let objects = [entry("/dir/", "0.2"), entry("/a", "0.5"), entry("/b", "1")];
+                (
+                    200,
+                    format!(
+                        r#"{{"hasMore": true, "nextMarker": "/b", "objects": [{}]}}"#,
+                        objects.join(",")
+                    ),
+                )
+            } else if req.is("GET", "/bus/objects/a?bucket=default") {
+                (
+                    200,
+                    r#"{"hasMore": false, "object": {"eTag": "x", "health": 0.5, "modTime": "2024-06-27T11:56:19Z",
+                    "name": "/a", "size": 1, "key": "key:aba60a4c1b9ff360214a68f09f890f9afc00d1bf23c8c9435a02311b10ff1d61",
+                    "slabs": []}}"#
+                        .to_string(),
+                )
+            } else {
+                (404, "not found".to_string())
+            }
+        });
+        let client = stub.client();
+        let options = ScanOptions::default();
+        let degraded: Vec<_> = block_on(
+            scan_bucket(
+                client.bus().clone(),
+                "default".to_string(),
+                options,
+                Arc::new(context()?),
+            )
+            .try_collect(),
+        )?;
+        assert_eq!(degraded.len(), 1);
+        assert_eq!(degraded[0].name, "/a");
+
+        let lists = stub
+            .requests()
+            .into_iter()
+            .filter(|r| r.is("POST", "/bus/objects/list"))
+            .count();
+        assert_eq!(lists, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn report() -> anyhow::Result<()> {
+        let context = context()?;
+        let objects = vec![
+            context.inspect("default".to_string(), object("/foo/a", "0.5")?),
+            context.inspect("default".to_string(), object("/foo/b", "-0.5")?),
+            context.inspect("other".to_string(), object("/c", "0.9")?),
+        ];
+        let report = HealthReport::new(BigDecimal::one(), objects);
+
+        let foo = &report.prefixes["default"]["/foo/"];
+        assert_eq!(foo.objects, 2);
+        assert_eq!(foo.size, 200);
+        assert_eq!(foo.min_health, BigDecimal::from_str("-0.5")?);
+        assert_eq!(report.prefixes["other"]["/"].objects, 1);
+
+        let bad = &report.hosts[&PublicKey::try_from(BAD_HOST)?];
+        assert_eq!(bad.objects, 3);
+        assert_eq!(bad.shards, 3);
+        assert!(bad.issues.contains(&HostIssue::Blocked));
+        assert_eq!(report.hosts.len(), 2);
+
+        let alert = report
+            .to_alert(Hash::Hash256([1; 32]))
+            .expect("report is not empty");
+        assert!(matches!(alert.severity, Severity::Critical));
+        assert_eq!(alert.message, "3 objects below 1 health, 2 hosts involved");
+        assert_eq!(alert.data.unwrap()["minHealth"], serde_json::json!(-0.5));
+
+        assert!(HealthReport::new(BigDecimal::one(), vec![])
+            .to_alert(Hash::Hash256([1; 32]))
+            .is_none());
+        Ok(())
+    }
+}
@@ -1,5 +1,14 @@
 use crate::autopilot::Autopilot;
+use crate::bucket::BucketHandle;
 use crate::bus::Bus;
+use crate::contract_set::Api as ContractSetApi;
+use crate::forecast::Api as ForecastApi;
+use crate::fs::RenterdFs;
+use crate::health::Api as HealthApi;
+use crate::manifest::Api as ManifestApi;
+use crate::spending::Api as SpendingApi;
+use crate::sync::Api as SyncApi;
+use crate::usage::Api as UsageApi;
 use crate::worker::Worker;
 use bandwidth::Bandwidth;
 use bigdecimal::{BigDecimal, FromPrimitive};
@@ -14,6 +23,7 @@ use serde_json::Value;
 use std::borrow::Cow;
 use std::fmt;
 use std::fmt::{Debug, Display, Formatter};
+use std::num::NonZeroUsize;
 use std::pin::Pin;
 use std::str::FromStr;
 use std::sync::Arc;
@@ -22,14 +32,47 @@ use url::Url;
 use zeroize::Zeroize;
 
 pub mod autopilot;
+pub mod bucket;
 pub mod bus;
+pub mod contract_set;
+pub mod forecast;
+pub mod fs;
+#[cfg(feature = "gateway")]
+pub mod gateway;
+pub mod health;
+pub mod manifest;
+pub mod spending;
+pub mod sync;
+#[cfg(test)]
+mod testing;
+pub mod usage;
+#[cfg(feature = "webdav")]
+pub mod webdav;
 pub mod worker;
 
+// defaults for paging through listings and for concurrent requests to renterd
+pub const LIST_BATCH_SIZE: NonZeroUsize = non_zero(1000);
+pub const DEFAULT_CONCURRENCY: NonZeroUsize = non_zero(4);
+
+const fn non_zero(n: usize) -> NonZeroUsize {
+    match NonZeroUsize::new(n) {
+        Some(n) => n,
+        None => panic!("value must not be zero"),
+    }
+}
+
 #[derive(Clone)]
 pub struct Client {
     bus: Bus,
     autopilot: Autopilot,
     worker: Worker,
+    sync: SyncApi,
+    usage: UsageApi,
+    spending: SpendingApi,
+    forecast: ForecastApi,
+    contract_set: ContractSetApi,
+    health: HealthApi,
+    manifest: ManifestApi,
 }
 
 impl Client {
@@ -44,6 +87,42 @@ impl Client {
     pub fn worker(&self) -> &Worker {
         &self.worker
     }
+
+    pub fn sync(&self) -> &SyncApi {
+        &self.sync
+    }
+
+    pub fn usage(&self) -> &UsageApi {
+        &self.usage
+    }
+
+    pub fn spending(&self) -> &SpendingApi {
+        &self.spending
+    }
+
+    pub fn forecast(&self) -> &ForecastApi {
+        &self.forecast
+    }
+
+    pub fn contract_set(&self) -> &ContractSetApi {
+        &self.contract_set
+    }
+
+    pub fn health(&self) -> &HealthApi {
+        &self.health
+    }
+
+    pub fn manifest(&self) -> &ManifestApi {
+        &self.manifest
+    }
+
+    pub fn fs(&self, bucket: Option<String>) -> RenterdFs {
+        RenterdFs::new(self.bus.clone(), self.worker.clone(), bucket)
+    }
+
+    pub fn bucket<S: ToString>(&self, name: S) -> BucketHandle {
+        BucketHandle::new(self.bus.clone(), self.worker.clone(), name.to_string())
+    }
 }
 
 struct ClientInner {
@@ -290,6 +369,30 @@ pub enum Error {
     NotSeekable(String),
     #[error("server sent an unexpected response, details: `{0}`")]
     UnexpectedResponse(String),
+    #[error("invalid byte range: `{0}`")]
+    InvalidRange(String),
+    #[error("the object at `{0}` was modified")]
+    ObjectModified(String),
+    #[error("length mismatch for `{0}`, expected {1} bytes, got {2}")]
+    LengthMismatch(String, u64, u64),
+    #[error("the object at `{0}` already exists")]
+    ObjectExists(String),
+    #[error("invalid prefix operation: `{0}`")]
+    InvalidPrefixOperation(String),
+    #[error("`{0}` is not a directory")]
+    NotADirectory(String),
+    #[error("invalid object: `{0}`")]
+    InvalidObject(String),
+    #[error("invalid path: `{0}`")]
+    InvalidPath(String),
+    #[error("confirmation token does not match the contents of `{0}`")]
+    ConfirmationMismatch(String),
+    #[error("contract set `{0}` was modified concurrently")]
+    ContractSetModified(String),
+    #[error("invalid contract set change: {0}")]
+    InvalidContractSetChange(String),
+    #[error("invalid list options: {0}")]
+    InvalidListOptions(String),
 }
 
 #[derive(Error, Debug)]
@@ -316,6 +419,14 @@ pub enum InvalidDataError {
     InvalidLastModified,
     #[error("invalid content length header")]
     InvalidContentLength,
+    #[error("invalid content range header {0}")]
+    InvalidContentRange(String),
+    #[error("invalid encryption key")]
+    InvalidEncryptionKey,
+    #[error("unsupported encryption key")]
+    UnsupportedEncryptionKey,
+    #[error("invalid manifest: {0}")]
+    InvalidManifest(String),
 }
 
 pub struct ClientBuilder {
@@ -410,10 +521,21 @@ impl ClientBuilder {
             reqwest_client,
         });
 
+        let bus = Bus::new(inner.clone());
+        let worker = Worker::new(inner.clone());
+        let autopilot = Autopilot::new(inner);
+
         Ok(Client {
-            bus: Bus::new(inner.clone()),
-            autopilot: Autopilot::new(inner.clone()),
-            worker: Worker::new(inner),
+            sync: SyncApi::new(bus.clone(), worker.clone()),
+            usage: UsageApi::new(bus.clone()),
+            spending: SpendingApi::new(bus.clone()),
+            forecast: ForecastApi::new(bus.clone(), autopilot.clone()),
+            contract_set: ContractSetApi::new(bus.clone()),
+            health: HealthApi::new(bus.clone()),
+            manifest: ManifestApi::new(bus.clone(), worker.clone()),
+            autopilot,
+            bus,
+            worker,
         })
     }
 }
@@ -424,7 +546,16 @@ fn encode_object_path<S: AsRef<str>>(path: S, prefix: &'static str) -> String {
         "./bus/objects/{}",
         urlencoding::encode(path.as_ref().trim_start_matches('/'))
     )*/
-    format!("{}/{}", prefix, path.as_ref().trim_start_matches('/'))
+    // the url parser escapes everything else itself, these would start the query, the fragment
+    // or an escape sequence, or turn into a `/`
+    let path = path
+        .as_ref()
+        .trim_start_matches('/')
+        .replace('%', "%25")
+        .replace('?', "%3F")
+        .replace('#', "%23")
+        .replace('\\', "%5C");
+    format!("{}/{}", prefix, path)
 }
 
 #[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
@@ -652,6 +783,80 @@ impl<'de> Visitor<'de> for FileContractIdVisitor {
 
 struct FileContractIdVisitor;
 
+#[derive(PartialEq, Eq, Clone)]
+pub struct EncryptionKey(pub [u8; 32]);
+
+impl Display for EncryptionKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("key:{}", hex::encode(self.0)))
+    }
+}
+
+// the key material is never printed by `Debug`
+impl Debug for EncryptionKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("key:[redacted]")
+    }
+}
+
+// error messages don't echo the key either
+impl TryFrom<&str> for EncryptionKey {
+    type Error = InvalidDataError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        match s.strip_prefix("key:") {
+            Some(hex) => {
+                let mut bytes = [0u8; 32];
+                hex::decode_to_slice(hex, &mut bytes)
+                    .map_err(|_| InvalidDataError::InvalidEncryptionKey)?;
+                Ok(EncryptionKey(bytes))
+            }
+            None => Err(InvalidDataError::UnsupportedEncryptionKey),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for EncryptionKey {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(EncryptionKeyVisitor)
+    }
+}
+
+impl Serialize for EncryptionKey {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.to_string().as_str())
+    }
+}
+
+impl Drop for EncryptionKey {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+struct EncryptionKeyVisitor;
+
+impl<'de> Visitor<'de> for EncryptionKeyVisitor {
+    type Value = EncryptionKey;
+
+    fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+        formatter.write_str("a string")
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        v.try_into().map_err(serde::de::Error::custom)
+    }
+}
+
 pub(crate) mod duration_ns {
     use bigdecimal::ToPrimitive;
     use serde::de::Visitor;
@@ -0,0 +1,694 @@
+use crate::bus::object::{ListOptions, Metadata};
+use crate::bus::Bus;
+use crate::worker::Worker;
+use crate::Error::InvalidDataError;
+use crate::{
+    Client, Either, Error, InvalidDataError as InvalidData, DEFAULT_CONCURRENCY, LIST_BATCH_SIZE,
+};
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, FixedOffset};
+use futures::{
+    AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt, Stream, StreamExt, TryStreamExt,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::num::NonZeroUsize;
+use std::str::FromStr;
+
+const CSV_HEADER: &str = "key,size,etag,mime_type,mod_time,health,user_metadata";
+
+#[derive(Clone)]
+pub struct Api {
+    bus: Bus,
+    worker: Worker,
+}
+
+impl Api {
+    pub(super) fn new(bus: Bus, worker: Worker) -> Self {
+        Self { bus, worker }
+    }
+
+    // user metadata isn't part of a listing, collecting it costs one request per object
+    pub fn entries(
+        &self,
+        options: &ManifestOptions,
+    ) -> impl Stream<Item = Result<ManifestEntry, Error>> + Send + Unpin {
+        let bus = self.bus.clone();
+        let options = options.clone();
+        let listing = bus.object().list(
+            LIST_BATCH_SIZE,
+            &ListOptions::new().prefix(options.prefix.clone()),
+            options.bucket.clone(),
+        );
+
+        futures::stream::once(futures::future::ready(listing))
+            .try_flatten()
+            .map_ok(|batch: Vec<Metadata>| {
+                futures::stream::iter(
+                    batch
+                        .into_iter()
+                        .filter(|metadata| !metadata.name.ends_with('/'))
+                        .map(Ok),
+                )
+            })
+            .try_flatten()
+            .map_ok(move |metadata| {
+                let bus = bus.clone();
+                let bucket = options.bucket.clone();
+                let user_metadata = options.user_metadata;
+                async move {
+                    if !user_metadata {
+                        return Ok(Some(ManifestEntry::new(metadata, BTreeMap::new())));
+                    }
+                    match bus
+                        .object()
+                        .get(&metadata.name, bucket, None, None, None, None)
+                        .await?
+                    {
+                        Some(Either::Left(object)) => Ok(Some(ManifestEntry::new(
+                            object.metadata,
+                            object.user_metadata.unwrap_or_default(),
+                        ))),
+                        _ => Ok(None),
+                    }
+                }
+            })
+            .try_buffered(options.concurrency.get())
+            .try_filter_map(|entry| futures::future::ready(Ok(entry)))
+            .boxed()
+    }
+
+    pub async fn export<W: AsyncWrite + Unpin>(
+        &self,
+        writer: W,
+        format: ManifestFormat,
+        options: &ManifestOptions,
+    ) -> Result<u64, Error> {
+        let mut writer = ManifestWriter::new(writer, format);
+        let mut entries = self.entries(options);
+        while let Some(entry) = entries.try_next().await? {
+            writer.write(&entry).await?;
+        }
+        writer.finish().await
+    }
+
+    pub async fn compare<R: AsyncBufRead + Send + Unpin + 'static>(
+        &self,
+        reader: R,
+        format: ManifestFormat,
+        options: &ManifestOptions,
+    ) -> Result<ManifestDiff, Error> {
+        let mut expected = BTreeMap::new();
+        let mut manifest = read_manifest(reader, format);
+        while let Some(entry) = manifest.try_next().await? {
+            expected.insert(entry.key.clone(), entry);
+        }
+
+        let mut diff = ManifestDiff::default();
+        let mut live = self.entries(options);
+        while let Some(actual) = live.try_next().await? {
+            match expected.remove(&actual.key) {
+                Some(expected) => {
+                    let fields = expected.differences(&actual, options.user_metadata);
+                    if fields.is_empty() {
+                        diff.unchanged += 1;
+                    } else {
+                        diff.changed.push(ChangedEntry {
+                            expected,
+                            actual,
+                            fields,
+                        });
+                    }
+                }
+                None => diff.extra.push(actual),
+            }
+        }
+        diff.missing = expected.into_values().collect();
+        Ok(diff)
+    }
+
+    // entries whose size or ETag no longer match the source are skipped
+    pub async fn restore(
+        &self,
+        entries: &[ManifestEntry],
+        source: &Client,
+        options: &RestoreOptions,
+    ) -> Result<RestoreReport, Error> {
+        let results: Vec<_> = futures::stream::iter(entries.iter().cloned())
+            .map(|entry| {
+                let api = self.clone();
+                let source = source.clone();
+                let options = options.clone();
+                async move {
+                    let result = api.restore_entry(&entry, &source, &options).await;
+                    (entry.key, result)
+                }
+            })
+            .buffer_unordered(options.concurrency.get())
+            .collect()
+            .await;
+
+        let mut report = RestoreReport::default();
+        for (key, result) in results {
+            match result {
+                Ok(Restore::Restored) => report.restored.push(key),
+                Ok(Restore::Planned) => report.planned.push(key),
+                Ok(Restore::Skipped) => report.skipped.push(key),
+                Err(err) => report.failed.push((key, err)),
+            }
+        }
+        Ok(report)
+    }
+
+    async fn restore_entry(
+        &self,
+        entry: &ManifestEntry,
+        source: &Client,
+        options: &RestoreOptions,
+    ) -> Result<Restore, Error> {
+        let object = match source
+            .worker()
+            .object()
+            .download(&entry.key, options.source_bucket.clone())
+            .await?
+        {
+            Some(object) => object,
+            None => return Err(Error::NotFoundError),
+        };
+        let etag_matches = match (&entry.etag, &object.etag) {
+            (Some(expected), Some(actual)) => unquote(expected) == unquote(actual),
+            _ => true,
+        };
+        if object.length != Some(entry.size) || !etag_matches {
+            return Ok(Restore::Skipped);
+        }
+        if options.dry_run {
+            return Ok(Restore::Planned);
+        }
+
+        let fs = crate::fs::RenterdFs::new(
+            self.bus.clone(),
+            self.worker.clone(),
+            options.bucket.clone(),
+        );
+        // open the source first so nothing is uploaded if it can't be read, pinned to the checked
+        // ETag so that an object changed in the meantime fails instead of being restored
+        let mut reader = object.pin_etag(true).open_stream(None).await?;
+        let mut writer = fs.create_with_metadata(
+            &entry.key,
+            entry.mime_type.clone(),
+            entry.user_metadata.clone(),
+        );
+        if let Err(err) = futures::io::copy(&mut reader, &mut writer).await {
+            writer.abort().await;
+            return Err(err.into());
+        }
+        writer.finish().await?;
+        Ok(Restore::Restored)
+    }
+}
+
+enum Restore {
+    Restored,
+    Planned,
+    Skipped,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestOptions {
+    pub bucket: Option<String>,
+    pub prefix: String,
+    pub user_metadata: bool,
+    pub concurrency: NonZeroUsize,
+}
+
+impl Default for ManifestOptions {
+    fn default() -> Self {
+        Self {
+            bucket: None,
+            prefix: "/".to_string(),
+            user_metadata: true,
+            concurrency: DEFAULT_CONCURRENCY,
+        }
+    }
+}
+
+impl ManifestOptions {
+    pub fn bucket<S: ToString>(mut self, bucket: S) -> Self {
+        self.bucket = Some(bucket.to_string());
+        self
+    }
+
+    pub fn prefix<S: ToString>(mut self, prefix: S) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    pub fn user_metadata(mut self, user_metadata: bool) -> Self {
+        self.user_metadata = user_metadata;
+        self
+    }
+
+    pub fn concurrency(mut self, concurrency: NonZeroUsize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RestoreOptions {
+    pub source_bucket: Option<String>,
+    pub bucket: Option<String>,
+    pub dry_run: bool,
+    pub concurrency: NonZeroUsize,
+}
+
+impl Default for RestoreOptions {
+    fn default() -> Self {
+        Self {
+            source_bucket: None,
+            bucket: None,
+            dry_run: false,
+            concurrency: DEFAULT_CONCURRENCY,
+        }
+    }
+}
+
+impl RestoreOptions {
+    pub fn source_bucket<S: ToString>(mut self, bucket: S) -> Self {
+        self.source_bucket = Some(bucket.to_string());
+        self
+    }
+
+    pub fn bucket<S: ToString>(mut self, bucket: S) -> Self {
+        self.bucket = Some(bucket.to_string());
+        self
+    }
+
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn concurrency(mut self, concurrency: NonZeroUsize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RestoreReport {
+    pub planned: Vec<String>,
+    pub restored: Vec<String>,
+    pub skipped: Vec<String>,
+    pub failed: Vec<(String, Error)>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestEntry {
+    pub key: String,
+    pub size: u64,
+    #[serde(rename = "eTag", default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    pub mod_time: DateTime<FixedOffset>,
+    #[serde(with = "bigdecimal::serde::json_num")]
+    pub health: BigDecimal,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub user_metadata: BTreeMap<String, String>,
+}
+
+impl ManifestEntry {
+    fn new(metadata: Metadata, user_metadata: BTreeMap<String, String>) -> Self {
+        Self {
+            key: metadata.name,
+            size: metadata.size,
+            etag: metadata.etag,
+            mime_type: metadata.mime_type,
+            mod_time: metadata.mod_time,
+            health: metadata.health.as_decimal().clone(),
+            user_metadata,
+        }
+    }
+
+    // mod time and health change without the content changing
+    pub fn differences(&self, other: &ManifestEntry, user_metadata: bool) -> Vec<ManifestField> {
+        let mut fields = vec![];
+        if self.size != other.size {
+            fields.push(ManifestField::Size);
+        }
+        if self.etag.as_deref().map(unquote) != other.etag.as_deref().map(unquote) {
+            fields.push(ManifestField::ETag);
+        }
+        if self.mime_type != other.mime_type {
+            fields.push(ManifestField::MimeType);
+        }
+        if user_metadata && self.user_metadata != other.user_metadata {
+            fields.push(ManifestField::UserMetadata);
+        }
+        fields
+    }
+
+    fn to_csv(&self) -> Result<String, Error> {
+        let user_metadata = if self.user_metadata.is_empty() {
+            String::new()
+        } else {
+            serde_json::to_string(&self.user_metadata).map_err(|e| InvalidDataError(e.into()))?
+        };
+        Ok([
+            csv_field(&self.key),
+            self.size.to_string(),
+            csv_field(self.etag.as_deref().unwrap_or_default()),
+            csv_field(self.mime_type.as_deref().unwrap_or_default()),
+            self.mod_time.to_rfc3339(),
+            self.health.normalized().to_string(),
+            csv_field(&user_metadata),
+        ]
+        .join(","))
+    }
+
+    fn from_csv(record: &str) -> Result<Self, Error> {
+        let fields = parse_csv_record(record)?;
+        let [key, size, etag, mime_type, mod_time, health, user_metadata]: [String; 7] =
+            fields.try_into().map_err(|fields: Vec<String>| {
+                invalid(format!("expected 7 fields, got {}", fields.len()))
+            })?;
+        let optional = |s: String| (!s.is_empty()).then_some(s);
+        Ok(Self {
+            key,
+            size: size
+                .parse()
+                .map_err(|_| invalid(format!("invalid size `{}`", size)))?,
+            etag: optional(etag),
+            mime_type: optional(mime_type),
+            mod_time: DateTime::parse_from_rfc3339(&mod_time)
+                .map_err(|_| invalid(format!("invalid mod time `{}`", mod_time)))?,
+            health: BigDecimal::from_str(&health)
+                .map_err(|_| invalid(format!("invalid health `{}`", health)))?,
+            user_metadata: if user_metadata.is_empty() {
+                BTreeMap::new()
+            } else {
+                serde_json::from_str(&user_metadata)
+                    .map_err(|e| invalid(format!("invalid user metadata: {}", e)))?
+            },
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestField {
+    Size,
+    ETag,
+    MimeType,
+    UserMetadata,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangedEntry {
+    pub expected: ManifestEntry,
+    pub actual: ManifestEntry,
+    pub fields: Vec<ManifestField>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ManifestDiff {
+    pub missing: Vec<ManifestEntry>,
+    pub changed: Vec<ChangedEntry>,
+    pub extra: Vec<ManifestEntry>,
+    pub unchanged: u64,
+}
+
+impl ManifestDiff {
+    pub fn is_clean(&self) -> bool {
+        self.missing.is_empty() && self.changed.is_empty() && self.extra.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestFormat {
+    JsonLines,
+    Csv,
+}
+
+pub struct ManifestWriter<W> {
+    writer: W,
+    format: ManifestFormat,
+    entries: u64,
+}
+
+impl<W: AsyncWrite + Unpin> ManifestWriter<W> {
+    pub fn new(writer: W, format: ManifestFormat) -> Self {
+        Self {
+            writer,
+            format,
+            entries: 0,
+        }
+    }
+
+    pub async fn write(&mut self, entry: &ManifestEntry) -> Result<(), Error> {
+        let line = match self.format {
+            ManifestFormat::JsonLines => {
+                serde_json::to_string(entry).map_err(|e| InvalidDataError(e.into()))?
+            }
+            ManifestFormat::Csv => {
+                if self.entries == 0 {
+                    self.writer.write_all(CSV_HEADER.as_bytes()).await?;
+                    self.writer.write_all(b"\n").await?;
+                }
+                entry.to_csv()?
+            }
+        };
+        self.writer.write_all(line.as_bytes()).await?;
+        self.writer.write_all(b"\n").await?;
+        self.entries += 1;
+        Ok(())
+    }
+
+    pub async fn finish(mut self) -> Result<u64, Error> {
+        if self.format == ManifestFormat::Csv && self.entries == 0 {
+            self.writer.write_all(CSV_HEADER.as_bytes()).await?;
+            self.writer.write_all(b"\n").await?;
+        }
+        self.writer.flush().await?;
+        Ok(self.entries)
+    }
+}
+
+pub fn read_manifest<R: AsyncBufRead + Send + Unpin + 'static>(
+    reader: R,
+    format: ManifestFormat,
+) -> impl Stream<Item = Result<ManifestEntry, Error>> + Send + Unpin {
+    futures::stream::try_unfold((reader, 0usize), move |(mut reader, mut line)| async move {
+        let mut record = String::new();
+        loop {
+            let mut buf = String::new();
+            if reader.read_line(&mut buf).await? == 0 {
+                if record.is_empty() {
+                    return Ok(None);
+                }
+                return Err(invalid(format!("unterminated record at line {}", line)));
+            }
+            line += 1;
+            record.push_str(&buf);
+
+            if format == ManifestFormat::Csv && record.matches('"').count() % 2 == 1 {
+                // a quoted field spans multiple lines
+                continue;
+            }
+            let trimmed = record.trim_end_matches(['\r', '\n']);
+            if format == ManifestFormat::Csv && line == 1 {
+                if trimmed != CSV_HEADER {
+                    return Err(invalid("missing csv header".to_string()));
+                }
+                record.clear();
+                continue;
+            }
+            if trimmed.trim().is_empty() {
+                record.clear();
+                continue;
+            }
+
+            let entry = match format {
+                ManifestFormat::JsonLines => serde_json::from_str(trimmed)
+                    .map_err(|e| invalid(format!("line {}: {}", line, e)))?,
+                ManifestFormat::Csv => ManifestEntry::from_csv(trimmed).map_err(|e| match e {
+                    InvalidDataError(InvalidData::InvalidManifest(msg)) => {
+                        invalid(format!("line {}: {}", line, msg))
+                    }
+                    e => e,
+                })?,
+            };
+            return Ok(Some((entry, (reader, line))));
+        }
+    })
+    .boxed()
+}
+
+fn invalid(msg: String) -> Error {
+    InvalidDataError(InvalidData::InvalidManifest(msg))
+}
+
+fn unquote(etag: &str) -> &str {
+    etag.trim_matches('"')
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn parse_csv_record(record: &str) -> Result<Vec<String>, Error> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut chars = record.chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => field.push(c),
+            (false, '"') if field.is_empty() => quoted = true,
+            (false, ',') => fields.push(std::mem::take(&mut field)),
+            (false, c) => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(invalid("unterminated quoted field".to_string()));
+    }
+    fields.push(field);
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, Stub};
+    use futures::executor::block_on;
+    use futures::io::Cursor;
+
+    fn entry(key: &str, user_metadata: &[(&str, &str)]) -> ManifestEntry {
+        ManifestEntry {
+            key: key.to_string(),
+            size: 12,
+            etag: Some("d41d8cd98f00b204e9800998ecf8427e".to_string()),
+            mime_type: Some("text/plain".to_string()),
+            mod_time: DateTime::parse_from_rfc3339("2024-07-05T12:37:58.998523074Z").unwrap(),
+            health: BigDecimal::from_str("1.25").unwrap(),
+            user_metadata: user_metadata
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    async fn roundtrip(format: ManifestFormat) -> anyhow::Result<String> {
+        let entries = vec![
+            entry("/foo/plain.txt", &[]),
+            entry("/foo/with, \"quotes\"\nand newline", &[("owner", "a,b")]),
+        ];
+        let mut buf = vec![];
+        let mut writer = ManifestWriter::new(&mut buf, format);
+        for entry in &entries {
+            writer.write(entry).await?;
+        }
+        assert_eq!(writer.finish().await?, 2);
+
+        let read: Vec<ManifestEntry> = read_manifest(Cursor::new(buf.clone()), format)
+            .try_collect()
+            .await?;
+        assert_eq!(read, entries);
+        Ok(String::from_utf8(buf)?)
+    }
+
+    #[test]
+    fn json_lines() -> anyhow::Result<()> {
+        let text = block_on(roundtrip(ManifestFormat::JsonLines))?;
+        assert_eq!(
+            text.lines().next().unwrap(),
+            r#"{"key":"/foo/plain.txt","size":12,"eTag":"d41d8cd98f00b204e9800998ecf8427e","mimeType":"text/plain","modTime":"2024-07-05T12:37:58.998523074Z","health":1.25}"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn csv() -> anyhow::Result<()> {
+        let text = block_on(roundtrip(ManifestFormat::Csv))?;
+        let mut lines = text.lines();
+        assert_eq!(lines.next(), Some(CSV_HEADER));
+        assert_eq!(
+            lines.next(),
+            Some("/foo/plain.txt,12,d41d8cd98f00b204e9800998ecf8427e,text/plain,2024-07-05T12:37:58.998523074+00:00,1.25,")
+        );
+
+        let missing_header = read_manifest(
+            Cursor::new(b"/foo,1,,,2024-07-05T12:37:58Z,1,\n"),
+            ManifestFormat::Csv,
+        );
+        assert!(block_on(missing_header.try_collect::<Vec<_>>()).is_err());
+
+        let bad = format!("{}\n/foo,one,,,2024-07-05T12:37:58Z,1,\n", CSV_HEADER);
+        let bad = read_manifest(Cursor::new(bad.into_bytes()), ManifestFormat::Csv);
+        let err = block_on(bad.try_collect::<Vec<_>>()).unwrap_err();
+        assert!(matches!(
+            err,
+            InvalidDataError(InvalidData::InvalidManifest(msg)) if msg == "line 2: invalid size `one`"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn dry_run_restore() -> anyhow::Result<()> {
+        let stub = Stub::start(|req| {
+            if req.is("HEAD", "/worker/objects/a.txt") {
+                (200, "abc".to_string())
+            } else {
+                (404, "object not found".to_string())
+            }
+        });
+        let client = stub.client();
+        let entry = ManifestEntry {
+            size: 3,
+            etag: None,
+            ..entry("/a.txt", &[])
+        };
+        let report = testing::block_on(client.manifest().restore(
+            &[entry],
+            &client,
+            &RestoreOptions::default().dry_run(true),
+        ))?;
+        assert_eq!(report.planned, vec!["/a.txt".to_string()]);
+        assert!(report.restored.is_empty());
+        assert!(report.failed.is_empty());
+        assert!(stub.requests().iter().all(|r| r.method == "HEAD"));
+        Ok(())
+    }
+
+    #[test]
+    fn differences() {
+        let expected = entry("/a", &[("owner", "alice")]);
+        let mut actual = expected.clone();
+        actual.etag = Some("\"d41d8cd98f00b204e9800998ecf8427e\"".to_string());
+        actual.health = BigDecimal::from(0);
+        actual.mod_time = DateTime::parse_from_rfc3339("2024-08-01T00:00:00Z").unwrap();
+        assert!(expected.differences(&actual, true).is_empty());
+
+        actual.size = 13;
+        actual.user_metadata.clear();
+        assert_eq!(
+            expected.differences(&actual, true),
+            vec![ManifestField::Size, ManifestField::UserMetadata]
+        );
+        assert_eq!(
+            expected.differences(&actual, false),
+            vec![ManifestField::Size]
+        );
+    }
+}
@@ -0,0 +1,348 @@
+use crate::bus::contract::{ArchivedContract, Contract, Spending, SpendingCategory};
+use crate::bus::Bus;
+use crate::{Error, PublicKey, DEFAULT_CONCURRENCY};
+use futures::{StreamExt, TryStreamExt};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::num::{NonZeroU64, NonZeroUsize};
+
+// roughly 30 days of blocks
+const DEFAULT_PERIOD: NonZeroU64 = match NonZeroU64::new(4320) {
+    Some(n) => n,
+    None => unreachable!(),
+};
+const TB: u128 = 1_000_000_000_000;
+
+#[derive(Clone)]
+pub struct Api {
+    bus: Bus,
+}
+
+impl Api {
+    pub(super) fn new(bus: Bus) -> Self {
+        Self { bus }
+    }
+
+    // only active contracts count towards the stored size, the data of an archived contract
+    // lives on in its renewal
+    pub async fn report(&self, options: &SpendingOptions) -> Result<SpendingReport, Error> {
+        let contracts = self
+            .bus
+            .contract()
+            .get_all(options.contract_set.clone())
+            .await?;
+        let ancestors: Vec<Vec<ArchivedContract>> = if options.include_archived {
+            let contract_ids: Vec<_> = contracts.iter().map(|c| c.id.clone()).collect();
+            futures::stream::iter(contract_ids)
+                .map(|contract_id| {
+                    let bus = self.bus.clone();
+                    let min_start_height = options.min_start_height;
+                    async move {
+                        bus.contract()
+                            .ancestors(&contract_id, min_start_height)
+                            .await
+                    }
+                })
+                .buffered(options.concurrency.get())
+                .try_collect()
+                .await?
+        } else {
+            vec![vec![]; contracts.len()]
+        };
+        // renterd only reports the uploaded size of the whole bus, it doesn't match the upload
+        // spending of a subset of the contracts
+        let uploaded = if options.contract_set.is_none()
+            && options.min_start_height.is_none()
+            && options.include_archived
+        {
+            Some(self.bus.stats().objects(None).await?.total_uploaded_size)
+        } else {
+            None
+        };
+
+        let mut report = SpendingReport::new(options.period, uploaded);
+        for (contract, ancestors) in contracts.iter().zip(ancestors) {
+            let sets = contract.contract_sets.as_deref().unwrap_or_default();
+            if options
+                .min_start_height
+                .is_none_or(|height| contract.start_height >= height)
+            {
+                report.add(&Record::active(contract), sets);
+            }
+            for ancestor in &ancestors {
+                report.add(&Record::archived(ancestor), sets);
+            }
+        }
+        Ok(report)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpendingOptions {
+    pub contract_set: Option<String>,
+    pub include_archived: bool,
+    pub min_start_height: Option<u64>,
+    pub period: NonZeroU64,
+    pub concurrency: NonZeroUsize,
+}
+
+impl Default for SpendingOptions {
+    fn default() -> Self {
+        Self {
+            contract_set: None,
+            include_archived: true,
+            min_start_height: None,
+            period: DEFAULT_PERIOD,
+            concurrency: DEFAULT_CONCURRENCY,
+        }
+    }
+}
+
+impl SpendingOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn contract_set<S: Into<String>>(mut self, contract_set: S) -> Self {
+        self.contract_set = Some(contract_set.into());
+        self
+    }
+
+    pub fn include_archived(mut self, include_archived: bool) -> Self {
+        self.include_archived = include_archived;
+        self
+    }
+
+    pub fn min_start_height(mut self, height: u64) -> Self {
+        self.min_start_height = Some(height);
+        self
+    }
+
+    pub fn period(mut self, period: NonZeroU64) -> Self {
+        self.period = period;
+        self
+    }
+
+    pub fn concurrency(mut self, concurrency: NonZeroUsize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SpendingReport {
+    pub period: u64,
+    pub total: SpendingTotals,
+    pub hosts: BTreeMap<PublicKey, SpendingTotals>,
+    pub contract_sets: BTreeMap<String, SpendingTotals>,
+    pub periods: BTreeMap<u64, SpendingTotals>,
+    // `None` if the report is restricted to some of the contracts
+    pub uploaded: Option<u64>,
+}
+
+impl SpendingReport {
+    fn new(period: NonZeroU64, uploaded: Option<u64>) -> Self {
+        Self {
+            period: period.get(),
+            total: SpendingTotals::default(),
+            hosts: BTreeMap::new(),
+            contract_sets: BTreeMap::new(),
+            periods: BTreeMap::new(),
+            uploaded,
+        }
+    }
+
+    fn add(&mut self, record: &Record, sets: &[String]) {
+        self.total.add(record);
+        self.hosts
+            .entry(record.host.clone())
+            .or_default()
+            .add(record);
+        for set in sets {
+            self.contract_sets
+                .entry(set.clone())
+                .or_default()
+                .add(record);
+        }
+        let period = record.start_height / self.period * self.period;
+        self.periods.entry(period).or_default().add(record);
+    }
+
+    pub fn categories(&self) -> BTreeMap<SpendingCategory, u128> {
+        SpendingCategory::ALL
+            .into_iter()
+            .map(|category| (category, self.total.spending.get(category)))
+            .collect()
+    }
+
+    pub fn upload_cost_per_tb(&self) -> Option<u128> {
+        per_tb(self.total.spending.uploads, self.uploaded?)
+    }
+
+    // renterd doesn't track the downloaded volume
+    pub fn download_cost_per_tb(&self, downloaded: u64) -> Option<u128> {
+        per_tb(self.total.spending.downloads, downloaded)
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SpendingTotals {
+    pub contracts: u64,
+    pub stored: u64,
+    #[serde(with = "crate::number_as_string")]
+    pub total_cost: u128,
+    pub spending: Spending,
+}
+
+impl SpendingTotals {
+    fn add(&mut self, record: &Record) {
+        self.contracts += 1;
+        self.stored += record.stored;
+        self.total_cost += record.total_cost;
+        self.spending += record.spending;
+    }
+
+    pub fn cost_per_tb_stored(&self) -> Option<u128> {
+        per_tb(self.spending.total(), self.stored)
+    }
+}
+
+struct Record<'a> {
+    host: &'a PublicKey,
+    start_height: u64,
+    stored: u64,
+    total_cost: u128,
+    spending: &'a Spending,
+}
+
+impl<'a> Record<'a> {
+    fn active(contract: &'a Contract) -> Self {
+        Self {
+            host: &contract.host_key,
+            start_height: contract.start_height,
+            stored: contract.size,
+            total_cost: contract.total_cost,
+            spending: &contract.spending,
+        }
+    }
+
+    fn archived(contract: &'a ArchivedContract) -> Self {
+        Self {
+            host: &contract.host_key,
+            start_height: contract.start_height,
+            stored: 0,
+            total_cost: contract.total_cost,
+            spending: &contract.spending,
+        }
+    }
+}
+
+fn per_tb(amount: u128, bytes: u64) -> Option<u128> {
+    (bytes > 0).then(|| amount.saturating_mul(TB) / bytes as u128)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aggregate() -> anyhow::Result<()> {
+        let host_a = PublicKey::try_from(
+            "ed25519:e156553dd877e99f24a5c02b72d1c1edd75dce76c663f6b939f20d3f7e9f01d9",
+        )?;
+        let host_b = PublicKey::try_from(
+            "ed25519:dfb16d76de07c537ad62647b39cba0497a6e339dfb1644bd8f8cda95893b1f16",
+        )?;
+        let spending = |uploads: u128, downloads: u128| Spending {
+            uploads,
+            downloads,
+            fund_account: 5,
+            ..Default::default()
+        };
+        let active = spending(100, 10);
+        let archived = spending(200, 0);
+        let other = spending(50, 50);
+        let autopilot = vec!["autopilot".to_string()];
+
+        let mut report =
+            SpendingReport::new(NonZeroU64::new(100).unwrap(), Some(2_000_000_000_000));
+        report.add(
+            &Record {
+                host: &host_a,
+                start_height: 250,
+                stored: 1_000_000_000_000,
+                total_cost: 1000,
+                spending: &active,
+            },
+            &autopilot,
+        );
+        report.add(
+            &Record {
+                host: &host_a,
+                start_height: 150,
+                stored: 0,
+                total_cost: 800,
+                spending: &archived,
+            },
+            &autopilot,
+        );
+        report.add(
+            &Record {
+                host: &host_b,
+                start_height: 299,
+                stored: 500_000_000_000,
+                total_cost: 400,
+                spending: &other,
+            },
+            &[],
+        );
+
+        assert_eq!(report.total.contracts, 3);
+        assert_eq!(report.total.stored, 1_500_000_000_000);
+        assert_eq!(report.total.total_cost, 2200);
+        assert_eq!(report.total.spending.uploads, 350);
+        assert_eq!(report.total.spending.downloads, 60);
+        assert_eq!(report.total.spending.fund_account, 15);
+        assert_eq!(report.total.cost_per_tb_stored(), Some(283));
+
+        assert_eq!(report.hosts.len(), 2);
+        assert_eq!(report.hosts[&host_a].contracts, 2);
+        assert_eq!(report.hosts[&host_a].spending.uploads, 300);
+        assert_eq!(report.hosts[&host_a].cost_per_tb_stored(), Some(320));
+        assert_eq!(report.hosts[&host_b].total_cost, 400);
+
+        assert_eq!(report.contract_sets.len(), 1);
+        assert_eq!(report.contract_sets["autopilot"].contracts, 2);
+
+        assert_eq!(
+            report.periods.keys().copied().collect::<Vec<_>>(),
+            [100, 200]
+        );
+        assert_eq!(report.periods[&100].spending.uploads, 200);
+        assert_eq!(report.periods[&200].contracts, 2);
+        assert_eq!(report.periods[&200].cost_per_tb_stored(), Some(146));
+
+        let categories = report.categories();
+        assert_eq!(categories[&SpendingCategory::Uploads], 350);
+        assert_eq!(categories[&SpendingCategory::SectorRoots], 0);
+        assert_eq!(report.upload_cost_per_tb(), Some(175));
+        let filtered = SpendingReport {
+            uploaded: None,
+            ..report.clone()
+        };
+        assert_eq!(filtered.upload_cost_per_tb(), None);
+        assert_eq!(report.download_cost_per_tb(0), None);
+        assert_eq!(report.download_cost_per_tb(3_000_000_000_000), Some(20));
+
+        let json = serde_json::to_value(&report)?;
+        assert_eq!(json["total"]["totalCost"], "2200");
+        assert_eq!(json["total"]["spending"]["uploads"], "350");
+        assert_eq!(
+            json["hosts"][host_a.to_string()]["stored"],
+            1_000_000_000_000u64
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,757 @@
+use crate::bus::object::{normalize_prefix, ListOptions, Metadata};
+use crate::bus::Bus;
+use crate::worker::object::{DownloadFileOptions, UploadFileOptions};
+use crate::worker::Worker;
+use crate::{Error, DEFAULT_CONCURRENCY, LIST_BATCH_SIZE};
+use chrono::{DateTime, FixedOffset, TimeDelta, Utc};
+use futures::{StreamExt, TryStreamExt};
+use md5::{Digest, Md5};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncReadExt;
+
+#[derive(Clone)]
+pub struct Api {
+    bus: Bus,
+    worker: Worker,
+}
+
+impl Api {
+    pub(super) fn new(bus: Bus, worker: Worker) -> Self {
+        Self { bus, worker }
+    }
+
+    pub async fn plan_upload<P: AsRef<Path>, S: AsRef<str>>(
+        &self,
+        local_dir: P,
+        prefix: S,
+        options: &SyncOptions,
+    ) -> Result<SyncPlan, Error> {
+        let prefix = normalize_prefix(prefix.as_ref());
+        let local = local_files(local_dir.as_ref()).await?;
+        let mut remote = self.remote_objects(&prefix, &options.bucket).await?;
+
+        let mut plan = SyncPlan::default();
+        for (relative, file) in local {
+            let remote = remote.remove(&relative);
+            let local_md5 = match &remote {
+                Some(remote)
+                    if options.compare_etag
+                        && remote.size == file.size
+                        && remote.etag.is_some() =>
+                {
+                    Some(file_md5(&file.path).await?)
+                }
+                _ => None,
+            };
+            match upload_reason(&file, remote.as_ref(), local_md5.as_deref()) {
+                Some(reason) => plan.actions.push(SyncAction::Upload {
+                    key: format!("{}{}", prefix, relative),
+                    local: file.path,
+                    size: file.size,
+                    reason,
+                }),
+                None => plan.unchanged += 1,
+            }
+        }
+
+        if options.delete_extraneous {
+            plan.actions
+                .extend(remote.into_values().map(|metadata| SyncAction::Delete {
+                    key: metadata.name,
+                    size: metadata.size,
+                }));
+        }
+
+        Ok(plan)
+    }
+
+    pub async fn upload_dir<P: AsRef<Path>, S: AsRef<str>>(
+        &self,
+        local_dir: P,
+        prefix: S,
+        options: SyncOptions,
+    ) -> Result<SyncReport, Error> {
+        let plan = self.plan_upload(local_dir, prefix, &options).await?;
+        let mut report = SyncReport {
+            unchanged: plan.unchanged,
+            ..SyncReport::default()
+        };
+
+        if options.dry_run {
+            report.plan = plan;
+            return Ok(report);
+        }
+
+        let results: Vec<_> = futures::stream::iter(plan.actions.iter().cloned())
+            .map(|action| {
+                let (bus, worker) = (self.bus.clone(), self.worker.clone());
+                let bucket = options.bucket.clone();
+                async move {
+                    let result = match &action {
+                        SyncAction::Upload { local, key, .. } => {
+                            worker
+                                .object()
+                                .upload_file(
+                                    local,
+                                    key,
+                                    UploadFileOptions {
+                                        bucket,
+                                        content_type: None,
+                                    },
+                                )
+                                .await
+                        }
+                        SyncAction::Delete { key, .. } => {
+                            bus.object().delete(key, bucket, false).await
+                        }
+                    };
+                    (action, result)
+                }
+            })
+            .buffer_unordered(options.concurrency.get())
+            .collect()
+            .await;
+
+        for (action, result) in results {
+            match (action, result) {
+                (SyncAction::Upload { key, .. }, Ok(())) => report.uploaded.push(key),
+                (SyncAction::Delete { key, .. }, Ok(())) => report.deleted.push(key),
+                (action, Err(err)) => report.failed.push((action.key().to_string(), err)),
+            }
+        }
+        report.plan = plan;
+        Ok(report)
+    }
+
+    pub async fn download_prefix<S: AsRef<str>, P: AsRef<Path>>(
+        &self,
+        bucket: Option<String>,
+        prefix: S,
+        local_dir: P,
+        options: RestoreOptions,
+    ) -> Result<RestoreReport, Error> {
+        let prefix = normalize_prefix(prefix.as_ref());
+        let local_dir = local_dir.as_ref();
+        let remote = self.remote_objects(&prefix, &bucket).await?;
+
+        let mut report = RestoreReport::default();
+        let mut pending = Vec::new();
+        for (relative, metadata) in remote {
+            let path = match local_path(local_dir, &relative) {
+                Some(path) => path,
+                None => {
+                    report.failed.push((
+                        metadata.name.clone(),
+                        Error::UnexpectedResponse(format!(
+                            "object key `{}` cannot be mapped to a local path",
+                            metadata.name
+                        )),
+                    ));
+                    continue;
+                }
+            };
+            let local = match local_file(&path).await {
+                Ok(local) => local,
+                Err(err) => {
+                    report.failed.push((metadata.name, err));
+                    continue;
+                }
+            };
+            let local_md5 = match &local {
+                Some(local)
+                    if options.compare_etag
+                        && local.size == metadata.size
+                        && metadata.etag.is_some() =>
+                {
+                    match file_md5(&local.path).await {
+                        Ok(md5) => Some(md5),
+                        Err(err) => {
+                            report.failed.push((metadata.name, err));
+                            continue;
+                        }
+                    }
+                }
+                _ => None,
+            };
+            if restore_needed(local.as_ref(), &metadata, local_md5.as_deref()) {
+                pending.push((metadata.name, path));
+            } else {
+                report.skipped += 1;
+            }
+        }
+
+        if options.dry_run {
+            report.planned = pending;
+            return Ok(report);
+        }
+
+        let results: Vec<_> = futures::stream::iter(pending.iter().cloned())
+            .map(|(key, path)| {
+                let api = self.clone();
+                let bucket = bucket.clone();
+                async move {
+                    let result = api.restore_file(&key, &path, bucket).await;
+                    (key, path, result)
+                }
+            })
+            .buffer_unordered(options.concurrency.get())
+            .collect()
+            .await;
+
+        for (key, path, result) in results {
+            match result {
+                Ok(()) => report.downloaded.push(path),
+                Err(err) => report.failed.push((key, err)),
+            }
+        }
+        report.planned = pending;
+        Ok(report)
+    }
+
+    async fn restore_file(
+        &self,
+        key: &str,
+        path: &Path,
+        bucket: Option<String>,
+    ) -> Result<(), Error> {
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        self.worker
+            .object()
+            .download_to_file(
+                key,
+                path,
+                DownloadFileOptions {
+                    bucket,
+                    overwrite: true,
+                    preserve_modified: true,
+                },
+            )
+            .await?
+            .ok_or(Error::NotFoundError)?;
+        Ok(())
+    }
+
+    async fn remote_objects(
+        &self,
+        prefix: &str,
+        bucket: &Option<String>,
+    ) -> Result<BTreeMap<String, Metadata>, Error> {
+        let mut stream = self.bus.object().list(
+            LIST_BATCH_SIZE,
+            &ListOptions::new().prefix(prefix),
+            bucket.clone(),
+        )?;
+        let mut remote = BTreeMap::new();
+        while let Some(batch) = stream.try_next().await? {
+            for metadata in batch {
+                if metadata.name.ends_with('/') {
+                    continue;
+                }
+                if let Some(relative) = metadata.name.strip_prefix(prefix) {
+                    remote.insert(relative.to_string(), metadata);
+                }
+            }
+        }
+        Ok(remote)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncOptions {
+    pub bucket: Option<String>,
+    pub delete_extraneous: bool,
+    pub dry_run: bool,
+    pub compare_etag: bool,
+    pub concurrency: NonZeroUsize,
+}
+
+impl Default for SyncOptions {
+    fn default() -> Self {
+        Self {
+            bucket: None,
+            delete_extraneous: false,
+            dry_run: false,
+            compare_etag: false,
+            concurrency: DEFAULT_CONCURRENCY,
+        }
+    }
+}
+
+impl SyncOptions {
+    pub fn bucket<S: ToString>(mut self, bucket: S) -> Self {
+        self.bucket = Some(bucket.to_string());
+        self
+    }
+
+    pub fn delete_extraneous(mut self, delete_extraneous: bool) -> Self {
+        self.delete_extraneous = delete_extraneous;
+        self
+    }
+
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn compare_etag(mut self, compare_etag: bool) -> Self {
+        self.compare_etag = compare_etag;
+        self
+    }
+
+    pub fn concurrency(mut self, concurrency: NonZeroUsize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RestoreOptions {
+    pub dry_run: bool,
+    pub compare_etag: bool,
+    pub concurrency: NonZeroUsize,
+}
+
+impl Default for RestoreOptions {
+    fn default() -> Self {
+        Self {
+            dry_run: false,
+            compare_etag: false,
+            concurrency: DEFAULT_CONCURRENCY,
+        }
+    }
+}
+
+impl RestoreOptions {
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn compare_etag(mut self, compare_etag: bool) -> Self {
+        self.compare_etag = compare_etag;
+        self
+    }
+
+    pub fn concurrency(mut self, concurrency: NonZeroUsize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RestoreReport {
+    pub planned: Vec<(String, PathBuf)>,
+    pub downloaded: Vec<PathBuf>,
+    pub skipped: usize,
+    pub failed: Vec<(String, Error)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UploadReason {
+    New,
+    SizeChanged,
+    ContentChanged,
+    Newer,
+}
+
+impl Display for UploadReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            UploadReason::New => "new",
+            UploadReason::SizeChanged => "size changed",
+            UploadReason::ContentChanged => "content changed",
+            UploadReason::Newer => "newer",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncAction {
+    Upload {
+        local: PathBuf,
+        key: String,
+        size: u64,
+        reason: UploadReason,
+    },
+    Delete {
+        key: String,
+        size: u64,
+    },
+}
+
+impl SyncAction {
+    pub fn key(&self) -> &str {
+        match self {
+            SyncAction::Upload { key, .. } => key,
+            SyncAction::Delete { key, .. } => key,
+        }
+    }
+}
+
+impl Display for SyncAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SyncAction::Upload {
+                local,
+                key,
+                size,
+                reason,
+            } => write!(
+                f,
+                "upload {} -> {} ({} bytes, {})",
+                local.display(),
+                key,
+                size,
+                reason
+            ),
+            SyncAction::Delete { key, size } => write!(f, "delete {} ({} bytes)", key, size),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyncPlan {
+    pub actions: Vec<SyncAction>,
+    pub unchanged: usize,
+}
+
+impl Display for SyncPlan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for action in &self.actions {
+            writeln!(f, "{}", action)?;
+        }
+        write!(
+            f,
+            "{} to upload, {} to delete, {} unchanged",
+            self.actions
+                .iter()
+                .filter(|a| matches!(a, SyncAction::Upload { .. }))
+                .count(),
+            self.actions
+                .iter()
+                .filter(|a| matches!(a, SyncAction::Delete { .. }))
+                .count(),
+            self.unchanged
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    pub plan: SyncPlan,
+    pub uploaded: Vec<String>,
+    pub deleted: Vec<String>,
+    pub failed: Vec<(String, Error)>,
+    pub unchanged: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LocalFile {
+    path: PathBuf,
+    size: u64,
+    modified: Option<DateTime<FixedOffset>>,
+}
+
+fn upload_reason(
+    local: &LocalFile,
+    remote: Option<&Metadata>,
+    local_md5: Option<&str>,
+) -> Option<UploadReason> {
+    let remote = match remote {
+        Some(remote) => remote,
+        None => return Some(UploadReason::New),
+    };
+    if remote.size != local.size {
+        return Some(UploadReason::SizeChanged);
+    }
+    if let (Some(local_md5), Some(etag)) = (local_md5, remote.etag.as_deref()) {
+        return (!etag.trim_matches('"').eq_ignore_ascii_case(local_md5))
+            .then_some(UploadReason::ContentChanged);
+    }
+    match local.modified {
+        Some(modified) if modified > remote.mod_time => Some(UploadReason::Newer),
+        _ => None,
+    }
+}
+
+fn restore_needed(local: Option<&LocalFile>, remote: &Metadata, local_md5: Option<&str>) -> bool {
+    let local = match local {
+        Some(local) if local.size == remote.size => local,
+        _ => return true,
+    };
+    if let (Some(local_md5), Some(etag)) = (local_md5, remote.etag.as_deref()) {
+        return !etag.trim_matches('"').eq_ignore_ascii_case(local_md5);
+    }
+    // restored files carry the remote modification time, allow for filesystems
+    // that store timestamps with less precision
+    match local.modified {
+        Some(modified) => modified < remote.mod_time - TimeDelta::seconds(1),
+        None => true,
+    }
+}
+
+fn relative_key(root: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?;
+    let parts: Vec<_> = relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect();
+    (!parts.is_empty()).then(|| parts.join("/"))
+}
+
+fn local_path(root: &Path, relative: &str) -> Option<PathBuf> {
+    let mut path = root.to_path_buf();
+    for part in relative.split('/') {
+        match part {
+            "" | "." => continue,
+            ".." => return None,
+            part => path.push(part),
+        }
+    }
+    (path != root).then_some(path)
+}
+
+async fn local_file(path: &Path) -> Result<Option<LocalFile>, Error> {
+    // a symlink is replaced by the restored file rather than written through
+    match tokio::fs::symlink_metadata(path).await {
+        Ok(metadata) if metadata.is_file() => Ok(Some(LocalFile {
+            path: path.to_path_buf(),
+            size: metadata.len(),
+            modified: metadata
+                .modified()
+                .ok()
+                .map(|m| DateTime::<Utc>::from(m).fixed_offset()),
+        })),
+        Ok(_) => Ok(None),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+async fn local_files(root: &Path) -> Result<BTreeMap<String, LocalFile>, Error> {
+    let mut files = BTreeMap::new();
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let mut entries = tokio::fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            // symlinks are skipped, following them could loop forever
+            let metadata = tokio::fs::symlink_metadata(&path).await?;
+            if metadata.is_dir() {
+                dirs.push(path);
+            } else if metadata.is_file() {
+                if let Some(key) = relative_key(root, &path) {
+                    files.insert(
+                        key,
+                        LocalFile {
+                            path,
+                            size: metadata.len(),
+                            modified: metadata
+                                .modified()
+                                .ok()
+                                .map(|m| DateTime::<Utc>::from(m).fixed_offset()),
+                        },
+                    );
+                }
+            }
+        }
+    }
+    Ok(files)
+}
+
+pub(crate) async fn file_md5(path: &Path) -> Result<String, Error> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut hasher = Md5::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Percentage;
+    use bigdecimal::BigDecimal;
+
+    fn remote(size: u64, mod_time: &str, etag: Option<&str>) -> anyhow::Result<Metadata> {
+        Ok(Metadata {
+            etag: etag.map(|e| e.to_string()),
+            health: Percentage::from_decimal(BigDecimal::from(1)),
+            mod_time: DateTime::parse_from_rfc3339(mod_time)?,
+            name: "/foo/bar".to_string(),
+            size,
+            mime_type: None,
+        })
+    }
+
+    #[test]
+    fn upload_decision() -> anyhow::Result<()> {
+        let local = LocalFile {
+            path: PathBuf::from("/tmp/bar"),
+            size: 10,
+            modified: Some(DateTime::parse_from_rfc3339("2024-07-05T12:00:00Z")?),
+        };
+
+        assert_eq!(upload_reason(&local, None, None), Some(UploadReason::New));
+
+        let older = remote(10, "2024-07-01T12:00:00Z", None)?;
+        let newer = remote(10, "2024-07-06T12:00:00Z", None)?;
+        assert_eq!(
+            upload_reason(&local, Some(&older), None),
+            Some(UploadReason::Newer)
+        );
+        assert_eq!(upload_reason(&local, Some(&newer), None), None);
+        assert_eq!(
+            upload_reason(
+                &local,
+                Some(&remote(11, "2024-07-06T12:00:00Z", None)?),
+                None
+            ),
+            Some(UploadReason::SizeChanged)
+        );
+
+        let with_etag = remote(
+            10,
+            "2024-07-01T12:00:00Z",
+            Some("d41d8cd98f00b204e9800998ecf8427e"),
+        )?;
+        assert_eq!(
+            upload_reason(
+                &local,
+                Some(&with_etag),
+                Some("d41d8cd98f00b204e9800998ecf8427e")
+            ),
+            None
+        );
+        assert_eq!(
+            upload_reason(
+                &local,
+                Some(&with_etag),
+                Some("9e107d9d372bb6826bd81d3542a419d6")
+            ),
+            Some(UploadReason::ContentChanged)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn restore_decision() -> anyhow::Result<()> {
+        let remote = remote(
+            10,
+            "2024-07-05T12:00:00.5Z",
+            Some("d41d8cd98f00b204e9800998ecf8427e"),
+        )?;
+        let local = |size, modified: &str| -> anyhow::Result<LocalFile> {
+            Ok(LocalFile {
+                path: PathBuf::from("/tmp/bar"),
+                size,
+                modified: Some(DateTime::parse_from_rfc3339(modified)?),
+            })
+        };
+
+        assert!(restore_needed(None, &remote, None));
+        assert!(restore_needed(
+            Some(&local(9, "2024-07-05T12:00:00Z")?),
+            &remote,
+            None
+        ));
+        assert!(!restore_needed(
+            Some(&local(10, "2024-07-05T12:00:00Z")?),
+            &remote,
+            None
+        ));
+        assert!(restore_needed(
+            Some(&local(10, "2024-07-01T12:00:00Z")?),
+            &remote,
+            None
+        ));
+        assert!(!restore_needed(
+            Some(&local(10, "2024-07-01T12:00:00Z")?),
+            &remote,
+            Some("d41d8cd98f00b204e9800998ecf8427e")
+        ));
+        assert!(restore_needed(
+            Some(&local(10, "2024-07-06T12:00:00Z")?),
+            &remote,
+            Some("9e107d9d372bb6826bd81d3542a419d6")
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn keys_and_paths() {
+        assert_eq!(
+            relative_key(Path::new("/tmp/out"), Path::new("/tmp/out/a/b.txt")),
+            Some("a/b.txt".to_string())
+        );
+        assert_eq!(
+            relative_key(Path::new("/tmp/out"), Path::new("/tmp/out")),
+            None
+        );
+        assert_eq!(
+            relative_key(Path::new("/tmp/out"), Path::new("/tmp/other/b.txt")),
+            None
+        );
+
+        assert_eq!(
+            local_path(Path::new("/tmp/restore"), "a//b/./c.txt"),
+            Some(PathBuf::from("/tmp/restore/a/b/c.txt"))
+        );
+        assert_eq!(local_path(Path::new("/tmp/restore"), "a/../../etc"), None);
+        assert_eq!(local_path(Path::new("/tmp/restore"), ""), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_cycle() -> anyhow::Result<()> {
+        let root = std::env::temp_dir().join(format!("renterd-sync-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("sub"))?;
+        std::fs::write(root.join("sub/a.txt"), b"a")?;
+        std::os::unix::fs::symlink(&root, root.join("sub/loop"))?;
+        std::os::unix::fs::symlink(root.join("sub/a.txt"), root.join("b.txt"))?;
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let files = runtime.block_on(local_files(&root));
+        std::fs::remove_dir_all(&root)?;
+        assert_eq!(files?.keys().collect::<Vec<_>>(), ["sub/a.txt"]);
+        Ok(())
+    }
+
+    #[test]
+    fn plan_display() {
+        let plan = SyncPlan {
+            actions: vec![
+                SyncAction::Upload {
+                    local: PathBuf::from("/tmp/out/a.txt"),
+                    key: "/builds/a.txt".to_string(),
+                    size: 12,
+                    reason: UploadReason::New,
+                },
+                SyncAction::Delete {
+                    key: "/builds/old.txt".to_string(),
+                    size: 4,
+                },
+            ],
+            unchanged: 3,
+        };
+        assert_eq!(
+            plan.to_string(),
+            "upload /tmp/out/a.txt -> /builds/a.txt (12 bytes, new)\n\
+             delete /builds/old.txt (4 bytes)\n\
+             1 to upload, 1 to delete, 3 unchanged"
+        );
+    }
+}
@@ -0,0 +1,217 @@
+use crate::bus::contract::Contract;
+use crate::{Client, ClientBuilder};
+use std::future::Future;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Request {
+    pub method: String,
+    // below `/api`, including the query string
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Request {
+    pub fn is(&self, method: &str, path: &str) -> bool {
+        self.method == method && self.path == path
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+pub(crate) struct Stub {
+    url: String,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl Stub {
+    pub fn start<F>(handler: F) -> Self
+    where
+        F: Fn(&Request) -> (u16, String) + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/api/", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(vec![]));
+        let recorded = requests.clone();
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let request = match read_request(&mut stream) {
+                    Some(request) => request,
+                    None => continue,
+                };
+                let (status, body) = handler(&request);
+                recorded.lock().unwrap().push(request);
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {} Stub\r\ncontent-type: application/json\r\n\
+                     content-length: {}\r\nconnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+            }
+        });
+        Self { url, requests }
+    }
+
+    pub fn client(&self) -> Client {
+        ClientBuilder::new()
+            .api_endpoint_url(&self.url)
+            .api_password("password")
+            .build()
+            .unwrap()
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+fn read_request<S: Read>(stream: S) -> Option<Request> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let path = parts.next()?.strip_prefix("/api")?.to_string();
+
+    let mut headers = vec![];
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header).ok()?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().ok()?;
+            }
+            headers.push((name.to_string(), value.trim().to_string()));
+        }
+    }
+    let chunked = headers.iter().any(|(name, value)| {
+        name.eq_ignore_ascii_case("transfer-encoding") && value.eq_ignore_ascii_case("chunked")
+    });
+    let body = if chunked {
+        read_chunked(&mut reader)?
+    } else {
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).ok()?;
+        body
+    };
+    Some(Request {
+        method,
+        path,
+        headers,
+        body: String::from_utf8_lossy(&body).to_string(),
+    })
+}
+
+// streamed uploads arrive without a content length
+fn read_chunked<R: BufRead>(reader: &mut R) -> Option<Vec<u8>> {
+    let mut body = vec![];
+    loop {
+        let mut size = String::new();
+        reader.read_line(&mut size).ok()?;
+        let size = usize::from_str_radix(size.trim(), 16).ok()?;
+        let mut chunk = vec![0; size + 2];
+        reader.read_exact(&mut chunk).ok()?;
+        if size == 0 {
+            return Some(body);
+        }
+        body.extend_from_slice(&chunk[..size]);
+    }
+}
+
+// a bucket where `/dir/` holds a file, `/empty/` is only a marker and nothing else exists
+pub(crate) fn directories(req: &Request) -> (u16, String) {
+    let objects = if !req.is("POST", "/bus/objects/list") {
+        return (404, "object not found".to_string());
+    } else if req.body.contains(r#""prefix":"/dir/""#) {
+        r#"[{"name": "/dir/a.txt", "size": 1, "health": 1, "modTime": "2024-06-27T11:56:19Z"}]"#
+    } else if req.body.contains(r#""prefix":"/empty/""#) {
+        r#"[{"name": "/empty/", "size": 0, "health": 1, "modTime": "2024-06-27T11:56:19Z"}]"#
+    } else {
+        "[]"
+    };
+    (
+        200,
+        format!(
+            r#"{{"hasMore": false, "nextMarker": "", "objects": {}}}"#,
+            objects
+        ),
+    )
+}
+
+pub(crate) fn contract(
+    id: char,
+    host: &str,
+    start_height: u64,
+    window_start: u64,
+    size: u64,
+) -> Contract {
+    serde_json::from_str(&contract_json(id, host, start_height, window_start, size)).unwrap()
+}
+
+// an active contract whose id repeats `id`, its proof window spans 144 blocks
+pub(crate) fn contract_json(
+    id: char,
+    host: &str,
+    start_height: u64,
+    window_start: u64,
+    size: u64,
+) -> String {
+    format!(
+        r#"
+ {{
+	"id": "fcid:{}",
+	"hostIP": "host.example:9882",
+	"hostKey": "ed25519:{host}",
+	"siamuxAddr": "host.example:9883",
+	"proofHeight": 0,
+	"revisionHeight": 0,
+	"revisionNumber": 1,
+	"size": {size},
+	"startHeight": {start_height},
+	"state": "active",
+	"windowStart": {window_start},
+	"windowEnd": {},
+	"contractPrice": "100",
+	"renewedFrom": "fcid:0000000000000000000000000000000000000000000000000000000000000000",
+	"spending": {{
+		"uploads": "1000",
+		"downloads": "20",
+		"fundAccount": "30",
+		"deletions": "0",
+		"sectorRoots": "0"
+	}},
+	"totalCost": "500",
+	"contractSets": null
+}}
+        "#,
+        id.to_string().repeat(64),
+        window_start + 144
+    )
+}
+
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
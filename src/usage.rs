@@ -0,0 +1,433 @@
+use crate::bus::object::{normalize_prefix, ListOptions, Metadata};
+use crate::bus::Bus;
+use crate::{Either, Error, DEFAULT_CONCURRENCY, LIST_BATCH_SIZE};
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, FixedOffset};
+use futures::future::BoxFuture;
+use futures::stream::FuturesUnordered;
+use futures::{FutureExt, Stream, StreamExt, TryStreamExt};
+use std::collections::{HashMap, VecDeque};
+use std::num::NonZeroUsize;
+
+#[derive(Clone)]
+pub struct Api {
+    bus: Bus,
+}
+
+impl Api {
+    pub(super) fn new(bus: Bus) -> Self {
+        Self { bus }
+    }
+
+    // children always come before their parent and the last item is `prefix` itself,
+    // directories deeper than `max_depth` still count towards their ancestors
+    pub fn du<S: AsRef<str>>(
+        &self,
+        prefix: S,
+        options: &DuOptions,
+    ) -> impl Stream<Item = Result<DirUsage, Error>> + Send + Unpin {
+        let walk = Walk::new(
+            self.bus.clone(),
+            normalize_prefix(prefix.as_ref()),
+            options.clone(),
+        );
+        futures::stream::unfold(walk, |mut walk| async move {
+            walk.next().await.map(|item| (item, walk))
+        })
+        .boxed()
+    }
+
+    pub async fn summarize<S: AsRef<str>>(
+        &self,
+        prefix: S,
+        bucket: Option<String>,
+    ) -> Result<DirUsage, Error> {
+        let prefix = normalize_prefix(prefix.as_ref());
+        let stats = subtree_stats(self.bus.clone(), prefix.clone(), bucket).await?;
+        Ok(DirUsage {
+            prefix,
+            depth: 0,
+            stats,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuOptions {
+    pub bucket: Option<String>,
+    pub max_depth: Option<usize>,
+    pub concurrency: NonZeroUsize,
+}
+
+impl Default for DuOptions {
+    fn default() -> Self {
+        Self {
+            bucket: None,
+            max_depth: None,
+            concurrency: DEFAULT_CONCURRENCY,
+        }
+    }
+}
+
+impl DuOptions {
+    pub fn bucket<S: ToString>(mut self, bucket: S) -> Self {
+        self.bucket = Some(bucket.to_string());
+        self
+    }
+
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    pub fn concurrency(mut self, concurrency: NonZeroUsize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirUsage {
+    pub prefix: String,
+    pub depth: usize,
+    pub stats: UsageStats,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UsageStats {
+    pub objects: u64,
+    pub size: u64,
+    pub min_health: Option<BigDecimal>,
+    pub newest: Option<DateTime<FixedOffset>>,
+    pub oldest: Option<DateTime<FixedOffset>>,
+    health_sum: BigDecimal,
+}
+
+impl UsageStats {
+    pub fn avg_health(&self) -> Option<BigDecimal> {
+        (self.objects > 0).then(|| &self.health_sum / BigDecimal::from(self.objects))
+    }
+
+    fn add(&mut self, metadata: &Metadata) {
+        let health = metadata.health.as_decimal();
+        self.objects += 1;
+        self.size += metadata.size;
+        self.health_sum += health;
+        if self.min_health.as_ref().is_none_or(|min| health < min) {
+            self.min_health = Some(health.clone());
+        }
+        if self.newest.is_none_or(|newest| metadata.mod_time > newest) {
+            self.newest = Some(metadata.mod_time);
+        }
+        if self.oldest.is_none_or(|oldest| metadata.mod_time < oldest) {
+            self.oldest = Some(metadata.mod_time);
+        }
+    }
+
+    fn merge(&mut self, other: &UsageStats) {
+        self.objects += other.objects;
+        self.size += other.size;
+        self.health_sum += &other.health_sum;
+        if let Some(health) = &other.min_health {
+            if self.min_health.as_ref().is_none_or(|min| health < min) {
+                self.min_health = Some(health.clone());
+            }
+        }
+        if let Some(newest) = other.newest {
+            if self.newest.is_none_or(|n| newest > n) {
+                self.newest = Some(newest);
+            }
+        }
+        if let Some(oldest) = other.oldest {
+            if self.oldest.is_none_or(|o| oldest < o) {
+                self.oldest = Some(oldest);
+            }
+        }
+    }
+}
+
+struct Node {
+    parent: Option<String>,
+    depth: usize,
+    stats: UsageStats,
+    pending: usize,
+}
+
+struct Listing {
+    prefix: String,
+    stats: UsageStats,
+    dirs: Vec<String>,
+}
+
+struct Walk {
+    bus: Bus,
+    options: DuOptions,
+    queue: VecDeque<String>,
+    in_flight: FuturesUnordered<BoxFuture<'static, Result<Listing, Error>>>,
+    nodes: HashMap<String, Node>,
+    ready: VecDeque<DirUsage>,
+    failed: bool,
+}
+
+impl Walk {
+    fn new(bus: Bus, prefix: String, options: DuOptions) -> Self {
+        let mut nodes = HashMap::new();
+        nodes.insert(
+            prefix.clone(),
+            Node {
+                parent: None,
+                depth: 0,
+                stats: UsageStats::default(),
+                pending: 0,
+            },
+        );
+        Self {
+            bus,
+            options,
+            queue: VecDeque::from([prefix]),
+            in_flight: FuturesUnordered::new(),
+            nodes,
+            ready: VecDeque::new(),
+            failed: false,
+        }
+    }
+
+    async fn next(&mut self) -> Option<Result<DirUsage, Error>> {
+        loop {
+            if let Some(usage) = self.ready.pop_front() {
+                return Some(Ok(usage));
+            }
+            if self.failed {
+                return None;
+            }
+            while self.in_flight.len() < self.options.concurrency.get() {
+                match self.queue.pop_front() {
+                    Some(prefix) => self.fetch(prefix),
+                    None => break,
+                }
+            }
+            match self.in_flight.next().await {
+                Some(Ok(listing)) => self.complete_listing(listing),
+                Some(Err(err)) => {
+                    self.failed = true;
+                    return Some(Err(err));
+                }
+                None => return None,
+            }
+        }
+    }
+
+    fn fetch(&mut self, prefix: String) {
+        let depth = self.nodes[&prefix].depth;
+        let bus = self.bus.clone();
+        let bucket = self.options.bucket.clone();
+        let root = depth == 0;
+        if self.options.max_depth.is_some_and(|max| depth >= max) {
+            // no need to walk further, a flat listing of the subtree is cheaper
+            self.in_flight.push(
+                async move {
+                    let stats = subtree_stats(bus, prefix.clone(), bucket).await?;
+                    Ok(Listing {
+                        prefix,
+                        stats,
+                        dirs: vec![],
+                    })
+                }
+                .boxed(),
+            );
+        } else {
+            self.in_flight
+                .push(async move { list_dir(bus, prefix, bucket, root).await }.boxed());
+        }
+    }
+
+    fn complete_listing(&mut self, listing: Listing) {
+        let depth = {
+            let node = self.nodes.get_mut(&listing.prefix).unwrap();
+            node.stats.merge(&listing.stats);
+            node.pending = listing.dirs.len();
+            node.depth
+        };
+        for dir in listing.dirs {
+            self.nodes.insert(
+                dir.clone(),
+                Node {
+                    parent: Some(listing.prefix.clone()),
+                    depth: depth + 1,
+                    stats: UsageStats::default(),
+                    pending: 0,
+                },
+            );
+            self.queue.push_back(dir);
+        }
+
+        let mut prefix = listing.prefix;
+        while self.nodes[&prefix].pending == 0 {
+            let node = self.nodes.remove(&prefix).unwrap();
+            let parent = node.parent.clone();
+            if let Some(parent) = &parent {
+                let parent = self.nodes.get_mut(parent).unwrap();
+                parent.stats.merge(&node.stats);
+                parent.pending -= 1;
+            }
+            if self.options.max_depth.is_none_or(|max| node.depth <= max) {
+                self.ready.push_back(DirUsage {
+                    prefix,
+                    depth: node.depth,
+                    stats: node.stats,
+                });
+            }
+            match parent {
+                Some(parent) => prefix = parent,
+                None => break,
+            }
+        }
+    }
+}
+
+async fn list_dir(
+    bus: Bus,
+    prefix: String,
+    bucket: Option<String>,
+    root: bool,
+) -> Result<Listing, Error> {
+    let mut listing = Listing {
+        prefix,
+        stats: UsageStats::default(),
+        dirs: vec![],
+    };
+    match bus
+        .object()
+        .get_stream(
+            listing.prefix.clone(),
+            LIST_BATCH_SIZE,
+            &ListOptions::default(),
+            bucket,
+        )
+        .await?
+    {
+        Some(Either::Right(mut stream)) => {
+            while let Some(batch) = stream.try_next().await? {
+                for metadata in batch {
+                    if metadata.name.ends_with('/') {
+                        listing.dirs.push(metadata.name);
+                    } else {
+                        listing.stats.add(&metadata);
+                    }
+                }
+            }
+        }
+        Some(Either::Left(_)) => return Err(Error::NotADirectory(listing.prefix)),
+        // a subdirectory removed while walking is simply empty now
+        None if root => return Err(Error::NotFoundError),
+        None => {}
+    }
+    Ok(listing)
+}
+
+async fn subtree_stats(
+    bus: Bus,
+    prefix: String,
+    bucket: Option<String>,
+) -> Result<UsageStats, Error> {
+    let mut stats = UsageStats::default();
+    let mut stream =
+        bus.object()
+            .list(LIST_BATCH_SIZE, &ListOptions::new().prefix(prefix), bucket)?;
+    while let Some(batch) = stream.try_next().await? {
+        for metadata in batch.iter().filter(|m| !m.name.ends_with('/')) {
+            stats.add(metadata);
+        }
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn metadata(name: &str, size: u64, health: &str, mod_time: &str) -> Metadata {
+        serde_json::from_str(&format!(
+            r#"{{"health": {}, "modTime": "{}", "name": "{}", "size": {}}}"#,
+            health, mod_time, name, size
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn stats() -> anyhow::Result<()> {
+        let mut a = UsageStats::default();
+        assert_eq!(a.avg_health(), None);
+        a.add(&metadata("/a/1", 10, "1", "2024-07-01T00:00:00Z"));
+        a.add(&metadata("/a/2", 20, "0.5", "2024-07-03T00:00:00Z"));
+
+        let mut b = UsageStats::default();
+        b.add(&metadata("/b/1", 30, "0.3", "2024-06-01T00:00:00Z"));
+        a.merge(&b);
+
+        assert_eq!(a.objects, 3);
+        assert_eq!(a.size, 60);
+        assert_eq!(a.min_health, Some(BigDecimal::from_str("0.3")?));
+        assert_eq!(a.avg_health(), Some(BigDecimal::from_str("0.6")?));
+        assert_eq!(
+            a.newest,
+            Some(DateTime::parse_from_rfc3339("2024-07-03T00:00:00Z")?)
+        );
+        assert_eq!(
+            a.oldest,
+            Some(DateTime::parse_from_rfc3339("2024-06-01T00:00:00Z")?)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn aggregation() {
+        let bus = crate::ClientBuilder::new()
+            .api_endpoint_url("http://localhost:9880/api/")
+            .api_password("password")
+            .build()
+            .unwrap()
+            .bus()
+            .clone();
+        let mut walk = Walk::new(bus, "/".to_string(), DuOptions::default().max_depth(1));
+        assert_eq!(walk.queue.pop_front().as_deref(), Some("/"));
+
+        let mut stats = UsageStats::default();
+        stats.add(&metadata("/file", 1, "1", "2024-07-01T00:00:00Z"));
+        walk.complete_listing(Listing {
+            prefix: "/".to_string(),
+            stats,
+            dirs: vec!["/a/".to_string(), "/b/".to_string()],
+        });
+        assert!(walk.ready.is_empty());
+        assert_eq!(walk.queue.len(), 2);
+
+        let mut stats = UsageStats::default();
+        stats.add(&metadata("/a/file", 2, "1", "2024-07-01T00:00:00Z"));
+        walk.complete_listing(Listing {
+            prefix: "/a/".to_string(),
+            stats,
+            dirs: vec![],
+        });
+        assert_eq!(walk.ready.len(), 1);
+        assert_eq!(walk.ready[0].prefix, "/a/");
+        assert_eq!(walk.ready[0].stats.size, 2);
+
+        let mut stats = UsageStats::default();
+        stats.add(&metadata("/b/c/file", 4, "1", "2024-07-01T00:00:00Z"));
+        walk.complete_listing(Listing {
+            prefix: "/b/".to_string(),
+            stats,
+            dirs: vec![],
+        });
+        assert_eq!(walk.ready.len(), 3);
+        assert_eq!(walk.ready[1].prefix, "/b/");
+        assert_eq!(walk.ready[1].depth, 1);
+        assert_eq!(walk.ready[2].prefix, "/");
+        assert_eq!(walk.ready[2].depth, 0);
+        assert_eq!(walk.ready[2].stats.objects, 3);
+        assert_eq!(walk.ready[2].stats.size, 7);
+        assert!(walk.nodes.is_empty());
+    }
+}
@@ -0,0 +1,584 @@
+use crate::bus::object::{normalize_prefix, ConflictPolicy, PrefixOptions, RenameMode};
+use crate::bus::Bus;
+use crate::fs::{Entry, RenterdFs};
+use crate::gateway::{
+    encode_path, error_response, escape_html, full, header_value, http_date, parse_path,
+    serve_connections, status_response, Body, Gateway,
+};
+use crate::worker::object::{guess_content_type, quote_etag};
+use crate::worker::Worker;
+use crate::{Client, Error};
+use bytes::Bytes;
+use futures::{AsyncWriteExt, TryStreamExt};
+use http_body_util::BodyExt;
+use hyper::header::{HeaderValue, ALLOW, CONTENT_TYPE};
+use hyper::{HeaderMap, Method, Request, Response, StatusCode};
+use std::collections::BTreeSet;
+use std::fmt::Display;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+
+const ALLOWED_METHODS: &str = "OPTIONS, GET, HEAD, PUT, DELETE, PROPFIND, MKCOL, COPY, MOVE";
+
+#[derive(Clone)]
+pub struct WebDav {
+    bus: Bus,
+    worker: Worker,
+    gateway: Gateway,
+    buckets: Option<Arc<BTreeSet<String>>>,
+}
+
+impl WebDav {
+    pub fn new(client: &Client) -> Self {
+        Self {
+            bus: client.bus().clone(),
+            worker: client.worker().clone(),
+            gateway: Gateway::new(client).listings(true),
+            buckets: None,
+        }
+    }
+
+    pub fn buckets<I: IntoIterator<Item = S>, S: ToString>(mut self, buckets: I) -> Self {
+        let buckets: BTreeSet<String> = buckets.into_iter().map(|b| b.to_string()).collect();
+        self.gateway = self.gateway.buckets(buckets.iter());
+        self.buckets = Some(Arc::new(buckets));
+        self
+    }
+
+    pub async fn serve(self, listener: TcpListener) -> Result<(), Error> {
+        serve_connections(listener, move |req| {
+            let webdav = self.clone();
+            async move { webdav.handle(req).await }
+        })
+        .await
+    }
+
+    pub async fn handle<B>(&self, req: Request<B>) -> Response<Body>
+    where
+        B: hyper::body::Body<Data = Bytes> + Send + Unpin,
+        B::Error: Display,
+    {
+        match *req.method() {
+            Method::GET | Method::HEAD => return self.gateway.handle(req).await,
+            Method::OPTIONS => {
+                let mut resp = status_response(StatusCode::OK);
+                let headers = resp.headers_mut();
+                headers.insert("dav", HeaderValue::from_static("1"));
+                headers.insert(ALLOW, HeaderValue::from_static(ALLOWED_METHODS));
+                return resp;
+            }
+            _ => {}
+        }
+
+        let (bucket, key) = match parse_path(req.uri().path()) {
+            Some((Some(bucket), key)) if self.allowed(&bucket) => (bucket, key),
+            Some((Some(_), _)) => return status_response(StatusCode::NOT_FOUND),
+            Some((None, _)) if req.method().as_str() == "PROPFIND" => {
+                return self
+                    .propfind_root(depth(req.headers()))
+                    .await
+                    .unwrap_or_else(error_response)
+            }
+            // the root only lists buckets, it can't be modified
+            Some((None, _)) => return status_response(StatusCode::FORBIDDEN),
+            None => return status_response(StatusCode::BAD_REQUEST),
+        };
+
+        let fs = self.fs(&bucket);
+        let resp = match req.method().as_str() {
+            "PROPFIND" => self.propfind(&fs, &key, depth(req.headers())).await,
+            "PUT" => put(&fs, &key, req).await,
+            "DELETE" => delete(&fs, &key).await,
+            "MKCOL" => mkcol(&fs, &key).await,
+            method @ ("COPY" | "MOVE") => match destination(req.headers()) {
+                Some((dest_bucket, dest_key)) if self.allowed(&dest_bucket) => {
+                    self.transfer(
+                        &fs,
+                        &key,
+                        &self.fs(&dest_bucket),
+                        &dest_key,
+                        overwrite(req.headers()),
+                        method == "MOVE",
+                    )
+                    .await
+                }
+                Some(_) => Ok(status_response(StatusCode::FORBIDDEN)),
+                None => Ok(status_response(StatusCode::BAD_REQUEST)),
+            },
+            _ => {
+                let mut resp = status_response(StatusCode::METHOD_NOT_ALLOWED);
+                resp.headers_mut()
+                    .insert(ALLOW, HeaderValue::from_static(ALLOWED_METHODS));
+                Ok(resp)
+            }
+        };
+        resp.unwrap_or_else(error_response)
+    }
+
+    fn allowed(&self, bucket: &str) -> bool {
+        self.buckets
+            .as_ref()
+            .is_none_or(|buckets| buckets.contains(bucket))
+    }
+
+    fn fs(&self, bucket: &str) -> RenterdFs {
+        RenterdFs::new(
+            self.bus.clone(),
+            self.worker.clone(),
+            Some(bucket.to_string()),
+        )
+    }
+
+    async fn propfind_root(&self, depth: Depth) -> Result<Response<Body>, Error> {
+        let mut responses = vec![("/".to_string(), directory("/"))];
+        if depth != Depth::Zero {
+            for bucket in self.bus.bucket().get_all().await? {
+                if self.allowed(&bucket.name) {
+                    let mut entry = directory(&format!("/{}/", bucket.name));
+                    entry.modified = Some(bucket.created_at);
+                    responses.push((format!("/{}/", bucket.name), entry));
+                }
+            }
+        }
+        Ok(multistatus(&responses))
+    }
+
+    async fn propfind(
+        &self,
+        fs: &RenterdFs,
+        key: &str,
+        depth: Depth,
+    ) -> Result<Response<Body>, Error> {
+        let entry = match fs.metadata(key).await? {
+            Some(entry) => entry,
+            None => return Ok(status_response(StatusCode::NOT_FOUND)),
+        };
+        let mut entries = vec![entry.clone()];
+        if entry.is_dir && depth != Depth::Zero {
+            let children: Vec<Entry> = fs.read_dir(&entry.path).await?.try_collect().await?;
+            entries.extend(children);
+        }
+        let responses: Vec<_> = entries
+            .into_iter()
+            .map(|entry| (format!("/{}{}", fs.bucket(), entry.path), entry))
+            .collect();
+        Ok(multistatus(&responses))
+    }
+
+    async fn transfer(
+        &self,
+        source_fs: &RenterdFs,
+        source_key: &str,
+        destination_fs: &RenterdFs,
+        destination_key: &str,
+        overwrite: bool,
+        remove_source: bool,
+    ) -> Result<Response<Body>, Error> {
+        let source = match source_fs.metadata(source_key).await? {
+            Some(source) => source,
+            None => return Ok(status_response(StatusCode::NOT_FOUND)),
+        };
+        let same_bucket = source_fs.bucket() == destination_fs.bucket();
+        let destination_key = if source.is_dir {
+            normalize_prefix(destination_key)
+        } else {
+            destination_key.trim_end_matches('/').to_string()
+        };
+        if source.path == "/" || destination_key == "/" {
+            return Ok(status_response(StatusCode::FORBIDDEN));
+        }
+        if same_bucket && source.path == destination_key {
+            return Ok(status_response(StatusCode::FORBIDDEN));
+        }
+        let existed = destination_fs.metadata(&destination_key).await?.is_some();
+        if existed && !overwrite {
+            return Ok(status_response(StatusCode::PRECONDITION_FAILED));
+        }
+
+        let objects = self.bus.object();
+        match (source.is_dir, remove_source && same_bucket) {
+            (is_dir, true) => {
+                let mode = if is_dir {
+                    RenameMode::Multi
+                } else {
+                    RenameMode::Single
+                };
+                objects
+                    .rename(
+                        source.path,
+                        destination_key,
+                        source_fs.bucket().to_string(),
+                        overwrite,
+                        mode,
+                    )
+                    .await?
+            }
+            (true, false) => {
+                let options = PrefixOptions::default().conflict(if overwrite {
+                    ConflictPolicy::Overwrite
+                } else {
+                    ConflictPolicy::Fail
+                });
+                let (source_bucket, destination_bucket) = (
+                    source_fs.bucket().to_string(),
+                    destination_fs.bucket().to_string(),
+                );
+                let report = if remove_source {
+                    objects
+                        .move_prefix(
+                            &source.path,
+                            source_bucket,
+                            &destination_key,
+                            destination_bucket,
+                            &options,
+                        )
+                        .await?
+                } else {
+                    objects
+                        .copy_prefix(
+                            &source.path,
+                            source_bucket,
+                            &destination_key,
+                            destination_bucket,
+                            &options,
+                        )
+                        .await?
+                };
+                if let Some((key, err)) = report.failed.into_iter().next() {
+                    return Err(Error::UnexpectedResponse(format!(
+                        "transfer of `{}` failed: {}",
+                        key, err
+                    )));
+                }
+            }
+            (false, false) => {
+                objects
+                    .copy(
+                        source.path.clone(),
+                        source_fs.bucket().to_string(),
+                        destination_key,
+                        destination_fs.bucket().to_string(),
+                    )
+                    .await?;
+                if remove_source {
+                    source_fs.remove(&source.path).await?;
+                }
+            }
+        }
+
+        Ok(status_response(if existed {
+            StatusCode::NO_CONTENT
+        } else {
+            StatusCode::CREATED
+        }))
+    }
+}
+
+async fn put<B>(fs: &RenterdFs, key: &str, req: Request<B>) -> Result<Response<Body>, Error>
+where
+    B: hyper::body::Body<Data = Bytes> + Send + Unpin,
+    B::Error: Display,
+{
+    if key.ends_with('/') {
+        return Ok(status_response(StatusCode::METHOD_NOT_ALLOWED));
+    }
+    let existed = fs.metadata(key).await?.is_some();
+    let content_type = req
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string())
+        .or_else(|| guess_content_type(Path::new(key)));
+
+    let mut body = req.into_body();
+    let mut writer = fs.create(key, content_type);
+    while let Some(frame) = body.frame().await {
+        let data = match frame {
+            Ok(frame) => match frame.into_data() {
+                Ok(data) => data,
+                Err(_) => continue,
+            },
+            Err(err) => {
+                writer.abort().await;
+                return Err(Error::IoError(std::io::Error::other(err.to_string())));
+            }
+        };
+        if writer.write_all(&data).await.is_err() {
+            // the upload ended early, its error is reported by `finish`
+            break;
+        }
+    }
+    writer.finish().await?;
+
+    Ok(status_response(if existed {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::CREATED
+    }))
+}
+
+async fn delete(fs: &RenterdFs, key: &str) -> Result<Response<Body>, Error> {
+    if key == "/" {
+        return Ok(status_response(StatusCode::FORBIDDEN));
+    }
+    match fs.metadata(key).await? {
+        Some(entry) if entry.is_dir => fs.remove_dir_all(&entry.path).await?,
+        Some(entry) => fs.remove(&entry.path).await?,
+        None => return Ok(status_response(StatusCode::NOT_FOUND)),
+    }
+    Ok(status_response(StatusCode::NO_CONTENT))
+}
+
+async fn mkcol(fs: &RenterdFs, key: &str) -> Result<Response<Body>, Error> {
+    if fs.metadata(key).await?.is_some() {
+        return Ok(status_response(StatusCode::METHOD_NOT_ALLOWED));
+    }
+    // renterd represents directories as empty objects with a trailing slash
+    fs.create(normalize_prefix(key), None).finish().await?;
+    Ok(status_response(StatusCode::CREATED))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Depth {
+    Zero,
+    One,
+}
+
+// `infinity` is treated like `1`, recursive listings of whole buckets are too expensive
+fn depth(headers: &HeaderMap) -> Depth {
+    match headers.get("depth").and_then(|v| v.to_str().ok()) {
+        Some("0") => Depth::Zero,
+        _ => Depth::One,
+    }
+}
+
+fn overwrite(headers: &HeaderMap) -> bool {
+    !matches!(
+        headers.get("overwrite").and_then(|v| v.to_str().ok()),
+        Some("F" | "f")
+    )
+}
+
+fn destination(headers: &HeaderMap) -> Option<(String, String)> {
+    let destination = headers.get("destination")?.to_str().ok()?;
+    let path = if destination.contains("://") {
+        url::Url::parse(destination).ok()?.path().to_string()
+    } else {
+        destination.to_string()
+    };
+    match parse_path(&path)? {
+        (Some(bucket), key) => Some((bucket, key)),
+        (None, _) => None,
+    }
+}
+
+fn directory(path: &str) -> Entry {
+    Entry {
+        path: path.to_string(),
+        is_dir: true,
+        size: 0,
+        modified: None,
+        etag: None,
+        mime_type: None,
+        health: None,
+    }
+}
+
+fn multistatus(responses: &[(String, Entry)]) -> Response<Body> {
+    let mut resp = Response::new(full(render_multistatus(responses)));
+    *resp.status_mut() = StatusCode::MULTI_STATUS;
+    resp.headers_mut().insert(
+        CONTENT_TYPE,
+        header_value("application/xml; charset=utf-8".to_string()),
+    );
+    resp
+}
+
+fn render_multistatus(responses: &[(String, Entry)]) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:multistatus xmlns:D=\"DAV:\">\n",
+    );
+    for (href, entry) in responses {
+        xml.push_str("<D:response><D:href>");
+        xml.push_str(&escape_html(&encode_path(href)));
+        xml.push_str("</D:href><D:propstat><D:prop>");
+        xml.push_str(&format!(
+            "<D:displayname>{}</D:displayname>",
+            escape_html(entry.name())
+        ));
+        if entry.is_dir {
+            xml.push_str("<D:resourcetype><D:collection/></D:resourcetype>");
+        } else {
+            xml.push_str(&format!(
+                "<D:resourcetype/><D:getcontentlength>{}</D:getcontentlength>",
+                entry.size
+            ));
+        }
+        if let Some(modified) = &entry.modified {
+            xml.push_str(&format!(
+                "<D:getlastmodified>{}</D:getlastmodified>",
+                http_date(modified)
+            ));
+        }
+        if let Some(etag) = entry.etag.as_deref().filter(|etag| !etag.is_empty()) {
+            xml.push_str(&format!(
+                "<D:getetag>{}</D:getetag>",
+                escape_html(&quote_etag(etag))
+            ));
+        }
+        if let Some(mime_type) = entry.mime_type.as_deref().filter(|m| !m.is_empty()) {
+            xml.push_str(&format!(
+                "<D:getcontenttype>{}</D:getcontenttype>",
+                escape_html(mime_type)
+            ));
+        }
+        xml.push_str("</D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>\n");
+    }
+    xml.push_str("</D:multistatus>\n");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{block_on, directories, Stub};
+    use chrono::DateTime;
+    use http_body_util::Full;
+
+    fn request(method: &str, path: &str, headers: &[(&str, &str)]) -> Request<Full<Bytes>> {
+        let mut req = Request::builder().method(method).uri(path);
+        for (name, value) in headers {
+            req = req.header(*name, *value);
+        }
+        req.body(Full::new(Bytes::from_static(b"data"))).unwrap()
+    }
+
+    #[test]
+    fn missing_paths() -> anyhow::Result<()> {
+        let stub = Stub::start(|req: &crate::testing::Request| {
+            if req.method == "PUT" || req.is("POST", "/bus/objects/copy") {
+                (200, String::new())
+            } else {
+                directories(req)
+            }
+        });
+        let webdav = WebDav::new(&stub.client());
+        let status = |req| block_on(webdav.handle(req)).status();
+
+        assert_eq!(
+            status(request("PROPFIND", "/default/missing", &[])),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            status(request("DELETE", "/default/missing", &[])),
+            StatusCode::NOT_FOUND
+        );
+        assert!(!stub.requests().iter().any(|req| req.method == "DELETE"));
+
+        assert_eq!(
+            status(request("MKCOL", "/default/dir", &[])),
+            StatusCode::METHOD_NOT_ALLOWED
+        );
+        assert_eq!(
+            status(request("MKCOL", "/default/new", &[])),
+            StatusCode::CREATED
+        );
+        assert_eq!(
+            status(request("PUT", "/default/new.txt", &[])),
+            StatusCode::CREATED
+        );
+        assert_eq!(
+            status(request(
+                "COPY",
+                "/default/dir",
+                &[("destination", "/default/copy"), ("overwrite", "F")]
+            )),
+            StatusCode::CREATED
+        );
+        assert_eq!(
+            status(request(
+                "COPY",
+                "/default/dir",
+                &[("destination", "/default/empty"), ("overwrite", "F")]
+            )),
+            StatusCode::PRECONDITION_FAILED
+        );
+
+        let uploads: Vec<_> = stub
+            .requests()
+            .into_iter()
+            .filter(|req| req.method == "PUT")
+            .map(|req| req.path)
+            .collect();
+        assert_eq!(
+            uploads,
+            [
+                "/worker/objects/new/?bucket=default",
+                "/worker/objects/new.txt?bucket=default"
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn headers() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(depth(&headers), Depth::One);
+        assert!(overwrite(&headers));
+        assert_eq!(destination(&headers), None);
+
+        headers.insert("depth", HeaderValue::from_static("0"));
+        headers.insert("overwrite", HeaderValue::from_static("F"));
+        headers.insert(
+            "destination",
+            HeaderValue::from_static("http://localhost:8080/default/foo/bar%20baz.txt"),
+        );
+        assert_eq!(depth(&headers), Depth::Zero);
+        assert!(!overwrite(&headers));
+        assert_eq!(
+            destination(&headers),
+            Some(("default".to_string(), "/foo/bar baz.txt".to_string()))
+        );
+
+        headers.insert("depth", HeaderValue::from_static("infinity"));
+        headers.insert("destination", HeaderValue::from_static("/other/dir/"));
+        assert_eq!(depth(&headers), Depth::One);
+        assert_eq!(
+            destination(&headers),
+            Some(("other".to_string(), "/dir/".to_string()))
+        );
+
+        headers.insert("destination", HeaderValue::from_static("/"));
+        assert_eq!(destination(&headers), None);
+    }
+
+    #[test]
+    fn multistatus() -> anyhow::Result<()> {
+        let xml = render_multistatus(&[
+            ("/default/foo/".to_string(), directory("/foo/")),
+            (
+                "/default/foo/a & b.txt".to_string(),
+                Entry {
+                    path: "/foo/a & b.txt".to_string(),
+                    is_dir: false,
+                    size: 12,
+                    modified: Some(DateTime::parse_from_rfc3339("2015-10-21T07:28:00Z")?),
+                    etag: Some("d41d8cd98f00b204e9800998ecf8427e".to_string()),
+                    mime_type: Some("text/plain".to_string()),
+                    health: None,
+                },
+            ),
+        ]);
+        assert_eq!(
+            xml,
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<D:multistatus xmlns:D="DAV:">
+<D:response><D:href>/default/foo/</D:href><D:propstat><D:prop><D:displayname>foo</D:displayname><D:resourcetype><D:collection/></D:resourcetype></D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>
+<D:response><D:href>/default/foo/a%20%26%20b.txt</D:href><D:propstat><D:prop><D:displayname>a &amp; b.txt</D:displayname><D:resourcetype/><D:getcontentlength>12</D:getcontentlength><D:getlastmodified>Wed, 21 Oct 2015 07:28:00 GMT</D:getlastmodified><D:getetag>&quot;d41d8cd98f00b204e9800998ecf8427e&quot;</D:getetag><D:getcontenttype>text/plain</D:getcontenttype></D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>
+</D:multistatus>
+"#
+        );
+        Ok(())
+    }
+}
@@ -1,12 +1,24 @@
 use crate::Error::InvalidDataError;
-use crate::InvalidDataError::{InvalidContentLength, InvalidLastModified};
+use crate::InvalidDataError::{InvalidContentLength, InvalidContentRange, InvalidLastModified};
 use crate::{
     encode_object_path, ApiRequest, ApiRequestBuilder, ClientInner, Error, RequestContent,
+    DEFAULT_CONCURRENCY,
 };
-use chrono::{DateTime, FixedOffset};
-use futures::{AsyncRead, TryStreamExt};
-use reqwest::header::{ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_TYPE, ETAG, LAST_MODIFIED};
+use bytes::Bytes;
+use chrono::{DateTime, FixedOffset, Utc};
+use futures::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, StreamExt, TryStreamExt};
+use reqwest::header::{
+    HeaderMap, ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG, LAST_MODIFIED,
+};
+use reqwest::{Response, StatusCode};
+use std::collections::BTreeMap;
+use std::io::SeekFrom;
+use std::num::{NonZeroU64, NonZeroUsize};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncSeekExt, AsyncWriteExt as _};
+use tokio_util::compat::{TokioAsyncReadCompatExt, TokioAsyncWriteCompatExt};
 
 #[derive(Clone)]
 pub struct Api {
@@ -23,74 +35,73 @@ impl Api {
         path: S,
         bucket: Option<String>,
     ) -> Result<Option<DownloadableObject>, Error> {
+        Ok(
+            match self
+                .download_conditional(path, bucket, &Conditions::default())
+                .await?
+            {
+                Some(Conditional::Modified(object)) => Some(object),
+                Some(_) => {
+                    return Err(Error::UnexpectedResponse(
+                        "unconditional request was answered conditionally".to_string(),
+                    ))
+                }
+                None => None,
+            },
+        )
+    }
+
+    pub async fn download_conditional<S: AsRef<str>>(
+        &self,
+        path: S,
+        bucket: Option<String>,
+        conditions: &Conditions,
+    ) -> Result<Option<Conditional<DownloadableObject>>, Error> {
         let object_path = path.as_ref().to_string();
-        let resp = match self
-            .inner
-            .send_api_request_optional(download_head_req(path, &bucket))
-            .await?
-        {
-            Some(resp) => resp,
+        let resp = match conditional_response(
+            self.inner
+                .send_api_request_optional(download_head_req(path, &bucket, conditions))
+                .await,
+        )? {
+            Some(Conditional::Modified(resp)) => resp,
+            Some(Conditional::NotModified) => return Ok(Some(Conditional::NotModified)),
+            Some(Conditional::PreconditionFailed) => {
+                return Ok(Some(Conditional::PreconditionFailed))
+            }
             None => return Ok(None),
         };
 
-        let accept_byte_ranges = resp
-            .headers()
-            .get(ACCEPT_RANGES)
-            .and_then(|accept_ranges| accept_ranges.to_str().ok())
-            .map(|accept_ranges| accept_ranges.starts_with("bytes"))
-            .unwrap_or(false);
-
-        // due to a bug in reqwest, `content_length()` for head requests always returns `Some(0)`
-        // the `Content-Length` header value is correct though, so manually parsing it is a workaround
-        // see: https://github.com/seanmonstar/reqwest/issues/1814
-        let content_length = resp
-            .headers()
-            .get(CONTENT_LENGTH)
-            .map(|cl| {
-                cl.to_str()
-                    .map_err(|_| InvalidDataError(InvalidContentLength))
-                    .and_then(|cl| {
-                        cl.parse::<u64>()
-                            .map_err(|_| InvalidDataError(InvalidContentLength))
-                    })
-            })
-            .transpose()?;
-
-        let content_type = resp
-            .headers()
-            .get(CONTENT_TYPE)
-            .and_then(|content_type| content_type.to_str().map(|s| s.to_string()).ok())
-            .filter(|s| !s.is_empty());
-
-        let etag = resp
-            .headers()
-            .get(ETAG)
-            .and_then(|content_type| content_type.to_str().map(|s| s.to_string()).ok())
-            .filter(|s| !s.is_empty());
-
-        let last_modified = if let Some(date_header) = resp.headers().get(LAST_MODIFIED) {
-            Some(
-                DateTime::parse_from_rfc2822(
-                    date_header
-                        .to_str()
-                        .map_err(|_| InvalidDataError(InvalidLastModified))?,
-                )
-                .map_err(|_| InvalidDataError(InvalidLastModified))?,
-            )
-        } else {
-            None
+        Ok(Some(Conditional::Modified(downloadable_object(
+            object_path,
+            bucket,
+            resp.headers(),
+            self.inner.clone(),
+        )?)))
+    }
+
+    pub async fn open_conditional<S: AsRef<str>>(
+        &self,
+        path: S,
+        bucket: Option<String>,
+        conditions: &Conditions,
+    ) -> Result<Option<Conditional<(DownloadableObject, impl AsyncRead + Send + Unpin)>>, Error>
+    {
+        let object_path = path.as_ref().to_string();
+        let resp = match conditional_response(
+            self.inner
+                .send_api_request_optional(download_get_req(path, &bucket, None, conditions))
+                .await,
+        )? {
+            Some(Conditional::Modified(resp)) => resp,
+            Some(Conditional::NotModified) => return Ok(Some(Conditional::NotModified)),
+            Some(Conditional::PreconditionFailed) => {
+                return Ok(Some(Conditional::PreconditionFailed))
+            }
+            None => return Ok(None),
         };
 
-        Ok(Some(DownloadableObject {
-            path: object_path,
-            bucket,
-            etag,
-            length: content_length,
-            last_modified,
-            seekable: accept_byte_ranges && content_length.map_or(false, |len| len > 0),
-            content_type,
-            inner: self.inner.clone(),
-        }))
+        let object = downloadable_object(object_path, bucket, resp.headers(), self.inner.clone())?;
+        Ok(Some(Conditional::Modified((object, into_async_read(resp)))))
     }
 
     pub async fn delete<S: AsRef<str>>(
@@ -112,26 +123,328 @@ impl Api {
         content_type: Option<String>,
         bucket: Option<String>,
         stream: U,
+    ) -> Result<(), Error> {
+        self.upload_with_metadata(path, content_type, &BTreeMap::new(), bucket, stream)
+            .await
+    }
+
+    pub async fn upload_with_metadata<
+        S: AsRef<str>,
+        U: AsyncRead + Send + Sync + Unpin + 'static,
+    >(
+        &self,
+        path: S,
+        content_type: Option<String>,
+        user_metadata: &BTreeMap<String, String>,
+        bucket: Option<String>,
+        stream: U,
     ) -> Result<(), Error> {
         let _ = self
             .inner
-            .send_api_request(upload_req(path, content_type, bucket, stream))
+            .send_api_request(upload_req(
+                path,
+                content_type,
+                user_metadata,
+                bucket,
+                stream,
+            ))
             .await?;
         Ok(())
     }
+
+    pub async fn upload_file<P: AsRef<Path>, S: AsRef<str>>(
+        &self,
+        file: P,
+        path: S,
+        options: UploadFileOptions,
+    ) -> Result<(), Error> {
+        let file = file.as_ref();
+        let content_type = options.content_type.or_else(|| guess_content_type(file));
+        let stream = tokio::fs::File::open(file).await?.compat();
+        self.upload(path, content_type, options.bucket, stream)
+            .await
+    }
+
+    pub async fn download_to_file<S: AsRef<str>, P: AsRef<Path>>(
+        &self,
+        path: S,
+        file: P,
+        options: DownloadFileOptions,
+    ) -> Result<Option<DownloadableObject>, Error> {
+        let file = file.as_ref();
+        if !options.overwrite && tokio::fs::try_exists(file).await? {
+            return Err(Error::IoError(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("`{}` already exists", file.display()),
+            )));
+        }
+
+        let object = match self.download(path, options.bucket).await? {
+            Some(object) => object.pin_etag(true),
+            None => return Ok(None),
+        };
+
+        let temp_file = temp_path(file);
+        if let Err(err) = download_into(&object, &temp_file, options.preserve_modified).await {
+            let _ = tokio::fs::remove_file(&temp_file).await;
+            return Err(err);
+        }
+        if let Err(err) = tokio::fs::rename(&temp_file, file).await {
+            let _ = tokio::fs::remove_file(&temp_file).await;
+            return Err(err.into());
+        }
+        Ok(Some(object))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UploadFileOptions {
+    pub bucket: Option<String>,
+    pub content_type: Option<String>,
+}
+
+impl UploadFileOptions {
+    pub fn bucket<S: ToString>(mut self, bucket: S) -> Self {
+        self.bucket = Some(bucket.to_string());
+        self
+    }
+
+    pub fn content_type<S: ToString>(mut self, content_type: S) -> Self {
+        self.content_type = Some(content_type.to_string());
+        self
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DownloadFileOptions {
+    pub bucket: Option<String>,
+    pub overwrite: bool,
+    pub preserve_modified: bool,
+}
+
+impl DownloadFileOptions {
+    pub fn bucket<S: ToString>(mut self, bucket: S) -> Self {
+        self.bucket = Some(bucket.to_string());
+        self
+    }
+
+    pub fn overwrite(mut self, overwrite: bool) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    pub fn preserve_modified(mut self, preserve_modified: bool) -> Self {
+        self.preserve_modified = preserve_modified;
+        self
+    }
+}
+
+pub(crate) fn guess_content_type(file: &Path) -> Option<String> {
+    mime_guess::from_path(file)
+        .first()
+        .map(|mime| mime.essence_str().to_string())
+}
+
+fn temp_path(file: &Path) -> PathBuf {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or_default();
+    let file_name = file
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    file.with_file_name(format!(
+        ".{}.{}-{:08x}.part",
+        file_name,
+        std::process::id(),
+        nanos
+    ))
+}
+
+async fn download_into(
+    object: &DownloadableObject,
+    file: &Path,
+    preserve_modified: bool,
+) -> Result<(), Error> {
+    let mut writer = tokio::fs::File::create(file).await?.compat_write();
+    let written = futures::io::copy(object.open_stream(None).await?, &mut writer).await?;
+    if let Some(expected) = object.length {
+        if written != expected {
+            return Err(Error::LengthMismatch(
+                object.path.clone(),
+                expected,
+                written,
+            ));
+        }
+    }
+    writer.flush().await?;
+    let file = writer.into_inner();
+    file.sync_all().await?;
+    if let Some(last_modified) = object.last_modified.filter(|_| preserve_modified) {
+        file.into_std()
+            .await
+            .set_modified(SystemTime::from(last_modified))?;
+    }
+    Ok(())
+}
+
+fn downloadable_object(
+    path: String,
+    bucket: Option<String>,
+    headers: &HeaderMap,
+    inner: Arc<ClientInner>,
+) -> Result<DownloadableObject, Error> {
+    let accept_byte_ranges = headers
+        .get(ACCEPT_RANGES)
+        .and_then(|accept_ranges| accept_ranges.to_str().ok())
+        .map(|accept_ranges| accept_ranges.starts_with("bytes"))
+        .unwrap_or(false);
+
+    // due to a bug in reqwest, `content_length()` for head requests always returns `Some(0)`
+    // the `Content-Length` header value is correct though, so manually parsing it is a workaround
+    // see: https://github.com/seanmonstar/reqwest/issues/1814
+    let content_length = headers
+        .get(CONTENT_LENGTH)
+        .map(|cl| {
+            cl.to_str()
+                .map_err(|_| InvalidDataError(InvalidContentLength))
+                .and_then(|cl| {
+                    cl.parse::<u64>()
+                        .map_err(|_| InvalidDataError(InvalidContentLength))
+                })
+        })
+        .transpose()?;
+
+    let content_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().map(|s| s.to_string()).ok())
+        .filter(|s| !s.is_empty());
+
+    let etag = headers
+        .get(ETAG)
+        .and_then(|content_type| content_type.to_str().map(|s| s.to_string()).ok())
+        .filter(|s| !s.is_empty());
+
+    let last_modified = if let Some(date_header) = headers.get(LAST_MODIFIED) {
+        Some(
+            DateTime::parse_from_rfc2822(
+                date_header
+                    .to_str()
+                    .map_err(|_| InvalidDataError(InvalidLastModified))?,
+            )
+            .map_err(|_| InvalidDataError(InvalidLastModified))?,
+        )
+    } else {
+        None
+    };
+
+    Ok(DownloadableObject {
+        path,
+        bucket,
+        etag,
+        length: content_length,
+        last_modified,
+        seekable: accept_byte_ranges && content_length.map_or(false, |len| len > 0),
+        content_type,
+        pin_etag: false,
+        inner,
+    })
+}
+
+fn conditional_response(
+    resp: Result<Option<Response>, Error>,
+) -> Result<Option<Conditional<Response>>, Error> {
+    match resp {
+        Ok(Some(resp)) if resp.status() == StatusCode::NOT_MODIFIED => {
+            Ok(Some(Conditional::NotModified))
+        }
+        Ok(Some(resp)) => Ok(Some(Conditional::Modified(resp))),
+        Ok(None) => Ok(None),
+        Err(Error::HttpResponseError(412, _)) => Ok(Some(Conditional::PreconditionFailed)),
+        Err(err) => Err(err),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Conditional<T> {
+    Modified(T),
+    NotModified,
+    PreconditionFailed,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Conditions {
+    pub if_match: Option<String>,
+    pub if_none_match: Option<String>,
+    pub if_modified_since: Option<DateTime<FixedOffset>>,
+}
+
+impl Conditions {
+    pub fn if_match<S: ToString>(mut self, etag: S) -> Self {
+        self.if_match = Some(etag.to_string());
+        self
+    }
+
+    pub fn if_none_match<S: ToString>(mut self, etag: S) -> Self {
+        self.if_none_match = Some(etag.to_string());
+        self
+    }
+
+    pub fn if_modified_since(mut self, date: DateTime<FixedOffset>) -> Self {
+        self.if_modified_since = Some(date);
+        self
+    }
+
+    fn headers(&self) -> impl Iterator<Item = (&'static str, String)> + '_ {
+        [
+            self.if_match
+                .as_ref()
+                .map(|etag| ("if-match", quote_etag(etag))),
+            self.if_none_match
+                .as_ref()
+                .map(|etag| ("if-none-match", quote_etag(etag))),
+            self.if_modified_since.map(|date| {
+                (
+                    "if-modified-since",
+                    date.with_timezone(&Utc)
+                        .format("%a, %d %b %Y %H:%M:%S GMT")
+                        .to_string(),
+                )
+            }),
+        ]
+        .into_iter()
+        .flatten()
+    }
+}
+
+// `Metadata::etag` from the bus comes without quotes, while the worker expects
+// entity tags in the quoted form it sends itself in the `ETag` header
+pub(crate) fn quote_etag(etag: &str) -> String {
+    if etag == "*" || etag.starts_with('"') || etag.starts_with("W/") {
+        etag.to_string()
+    } else {
+        format!("\"{}\"", etag)
+    }
 }
 
 fn upload_req<S: AsRef<str>, U: AsyncRead + Send + Sync + Unpin + 'static>(
     path: S,
     content_type: Option<String>,
+    user_metadata: &BTreeMap<String, String>,
     bucket: Option<String>,
     stream: U,
 ) -> ApiRequest {
     let url = encode_object_path(path, "./worker/objects");
     let params = bucket.map(|b| vec![("bucket", b)]);
+    let headers: Vec<_> = user_metadata
+        .iter()
+        .map(|(key, value)| (format!("x-sia-meta-{}", key), value.clone()))
+        .collect();
 
     ApiRequestBuilder::put(url)
         .params(params)
+        .headers((!headers.is_empty()).then_some(headers))
         .content(Some(RequestContent::Stream(Box::new(stream), content_type)))
         .build()
 }
@@ -146,28 +459,120 @@ fn delete_req<S: AsRef<str>>(path: S, bucket: Option<String>, batch: bool) -> Ap
     ApiRequestBuilder::delete(url).params(Some(params)).build()
 }
 
-fn download_head_req<S: AsRef<str>>(path: S, bucket: &Option<String>) -> ApiRequest {
+fn download_head_req<S: AsRef<str>>(
+    path: S,
+    bucket: &Option<String>,
+    conditions: &Conditions,
+) -> ApiRequest {
     let (path, params) = dl_req_prep(path, bucket);
-    ApiRequestBuilder::head(path).params(params).build()
+    let headers: Vec<_> = conditions.headers().collect();
+    ApiRequestBuilder::head(path)
+        .params(params)
+        .headers((!headers.is_empty()).then_some(headers))
+        .build()
 }
 
 fn download_get_req<S: AsRef<str>>(
     path: S,
     bucket: &Option<String>,
     offset_length: Option<(u64, Option<u64>)>,
+    conditions: &Conditions,
 ) -> ApiRequest {
     let (path, params) = dl_req_prep(path, bucket);
-    let mut builder = ApiRequestBuilder::get(path).params(params);
-    if let Some((offset, length)) = offset_length {
-        if offset > 0 {
-            let value = match length {
-                Some(length) if length > 0 => format!("bytes={}-{}", offset, length - 1),
-                _ => format!("bytes={}-", offset),
-            };
-            builder = builder.headers(Some(vec![("range", value)]));
+    let headers: Vec<_> = offset_length
+        .and_then(|(offset, length)| range_header(offset, length))
+        .map(|value| ("range", value))
+        .into_iter()
+        .chain(conditions.headers())
+        .collect();
+    ApiRequestBuilder::get(path)
+        .params(params)
+        .headers((!headers.is_empty()).then_some(headers))
+        .build()
+}
+
+fn expected_range(
+    path: &str,
+    offset: u64,
+    length: u64,
+    total: Option<u64>,
+) -> Result<ContentRange, Error> {
+    if length == 0 {
+        return Err(Error::InvalidRange(format!(
+            "zero length range at offset {}",
+            offset
+        )));
+    }
+    let length = match total {
+        Some(total) if offset >= total => {
+            return Err(Error::InvalidRange(format!(
+                "offset {} is beyond the end of `{}` ({} bytes)",
+                offset, path, total
+            )));
+        }
+        Some(total) => length.min(total - offset),
+        None => length,
+    };
+    let end = offset.checked_add(length - 1).ok_or_else(|| {
+        Error::InvalidRange(format!(
+            "range of {} bytes at offset {} exceeds the maximum object size",
+            length, offset
+        ))
+    })?;
+    Ok(ContentRange {
+        start: offset,
+        end,
+        total,
+    })
+}
+
+fn range_header(offset: u64, length: Option<u64>) -> Option<String> {
+    match length {
+        Some(length) if length > 0 => Some(format!(
+            "bytes={}-{}",
+            offset,
+            offset.saturating_add(length - 1)
+        )),
+        _ if offset > 0 => Some(format!("bytes={}-", offset)),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentRange {
+    pub start: u64,
+    pub end: u64,
+    pub total: Option<u64>,
+}
+
+impl ContentRange {
+    pub fn length(&self) -> u64 {
+        self.end - self.start + 1
+    }
+}
+
+impl TryFrom<&str> for ContentRange {
+    type Error = crate::InvalidDataError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        let invalid = || InvalidContentRange(s.to_string());
+        let (range, total) = s
+            .trim()
+            .strip_prefix("bytes ")
+            .and_then(|r| r.split_once('/'))
+            .ok_or_else(invalid)?;
+        let (start, end) = range.split_once('-').ok_or_else(invalid)?;
+        let start = start.trim().parse::<u64>().map_err(|_| invalid())?;
+        let end = end.trim().parse::<u64>().map_err(|_| invalid())?;
+        let total = match total.trim() {
+            "*" => None,
+            total => Some(total.parse::<u64>().map_err(|_| invalid())?),
+        };
+        if end < start || total.is_some_and(|total| end >= total) {
+            return Err(invalid());
         }
+        Ok(ContentRange { start, end, total })
     }
-    builder.build()
 }
 
 fn dl_req_prep<S: AsRef<str>>(
@@ -188,10 +593,43 @@ pub struct DownloadableObject {
     pub seekable: bool,
     pub etag: Option<String>,
     pub last_modified: Option<DateTime<FixedOffset>>,
+    pin_etag: bool,
     inner: Arc<ClientInner>,
 }
 
 impl DownloadableObject {
+    pub fn pin_etag(mut self, pin_etag: bool) -> Self {
+        self.pin_etag = pin_etag;
+        self
+    }
+
+    fn conditions(&self, pin_etag: bool) -> Conditions {
+        Conditions {
+            if_match: self.etag.clone().filter(|_| pin_etag),
+            ..Conditions::default()
+        }
+    }
+
+    async fn send_get(
+        &self,
+        offset_length: Option<(u64, Option<u64>)>,
+        pin_etag: bool,
+    ) -> Result<Response, Error> {
+        match self
+            .inner
+            .send_api_request(download_get_req(
+                &self.path,
+                &self.bucket,
+                offset_length,
+                &self.conditions(pin_etag),
+            ))
+            .await
+        {
+            Err(Error::HttpResponseError(412, _)) => Err(Error::ObjectModified(self.path.clone())),
+            resp => resp,
+        }
+    }
+
     pub async fn open_stream(
         &self,
         offset: impl Into<Option<u64>>,
@@ -203,30 +641,268 @@ impl DownloadableObject {
         }
 
         let resp = self
-            .inner
-            .send_api_request(download_get_req(
-                &self.path,
-                &self.bucket,
-                offset.map(|o| (o, self.length)),
-            ))
+            .send_get(offset.map(|o| (o, None)), self.pin_etag)
             .await?;
 
-        Ok(resp
-            .bytes_stream()
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
-            .into_async_read())
+        Ok(into_async_read(resp))
+    }
+
+    pub async fn open_range(
+        &self,
+        offset: u64,
+        length: u64,
+    ) -> Result<impl AsyncRead + Send + Unpin, Error> {
+        let (resp, range) = self.range_request(offset, length, self.pin_etag).await?;
+        Ok(into_async_read(resp).take(range.length()))
+    }
+
+    pub async fn read_range(&self, offset: u64, length: u64) -> Result<Bytes, Error> {
+        self.fetch_range(offset, length, self.pin_etag).await
+    }
+
+    async fn fetch_range(&self, offset: u64, length: u64, pin_etag: bool) -> Result<Bytes, Error> {
+        let (resp, range) = self.range_request(offset, length, pin_etag).await?;
+        let bytes = resp.bytes().await?;
+        if bytes.len() as u64 != range.length() {
+            return Err(Error::UnexpectedResponse(format!(
+                "expected {} bytes for range {}-{}, received {}",
+                range.length(),
+                range.start,
+                range.end,
+                bytes.len()
+            )));
+        }
+        Ok(bytes)
+    }
+
+    pub async fn read_ranges<I: IntoIterator<Item = (u64, u64)>>(
+        &self,
+        ranges: I,
+    ) -> Result<Vec<Bytes>, Error> {
+        // renterd only serves single ranges per request, so multiple ranges are
+        // fetched concurrently and returned in the order they were requested
+        futures::stream::iter(ranges)
+            .map(|(offset, length)| self.read_range(offset, length))
+            .buffered(DEFAULT_CONCURRENCY.get())
+            .try_collect()
+            .await
+    }
+
+    pub fn parallel(&self) -> ParallelDownload<'_> {
+        ParallelDownload {
+            object: self,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            concurrency: DEFAULT_CONCURRENCY,
+            max_retries: DEFAULT_MAX_RETRIES,
+            // every chunk is a separate request, without pinning the etag an object
+            // overwritten in the meantime would end up mixing both versions
+            pin_etag: true,
+        }
+    }
+
+    async fn range_request(
+        &self,
+        offset: u64,
+        length: u64,
+        pin_etag: bool,
+    ) -> Result<(Response, ContentRange), Error> {
+        if !self.seekable {
+            return Err(Error::NotSeekable(self.path.clone()));
+        }
+        let expected = expected_range(&self.path, offset, length, self.length)?;
+        let length = expected.length();
+
+        let resp = self
+            .send_get(Some((offset, Some(length))), pin_etag)
+            .await?;
+
+        match resp.status() {
+            StatusCode::PARTIAL_CONTENT => {
+                let content_range: ContentRange = resp
+                    .headers()
+                    .get(CONTENT_RANGE)
+                    .and_then(|cr| cr.to_str().ok())
+                    .ok_or(InvalidDataError(InvalidContentRange("".to_string())))?
+                    .try_into()?;
+                if content_range.start != expected.start
+                    || content_range.end != expected.end
+                    || (expected.total.is_some()
+                        && content_range.total.is_some()
+                        && content_range.total != expected.total)
+                {
+                    return Err(Error::UnexpectedResponse(format!(
+                        "requested range {}-{}, server sent {}-{}",
+                        expected.start, expected.end, content_range.start, content_range.end
+                    )));
+                }
+                Ok((resp, content_range))
+            }
+            // the server is allowed to ignore the range header if it covers the whole object
+            StatusCode::OK if offset == 0 && expected.total == Some(length) => Ok((resp, expected)),
+            status => Err(Error::UnexpectedResponse(format!(
+                "expected partial content for range {}-{}, got status code {}",
+                expected.start,
+                expected.end,
+                status.as_u16()
+            ))),
+        }
     }
 }
 
+// one full slab with renterd's default redundancy settings (10 data shards of 4 MiB)
+const DEFAULT_CHUNK_SIZE: NonZeroU64 = match NonZeroU64::new(40 * 1024 * 1024) {
+    Some(n) => n,
+    None => unreachable!(),
+};
+const DEFAULT_MAX_RETRIES: usize = 3;
+
+pub struct ParallelDownload<'a> {
+    object: &'a DownloadableObject,
+    chunk_size: NonZeroU64,
+    concurrency: NonZeroUsize,
+    max_retries: usize,
+    pin_etag: bool,
+}
+
+impl<'a> ParallelDownload<'a> {
+    pub fn chunk_size(mut self, chunk_size: NonZeroU64) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    pub fn concurrency(mut self, concurrency: NonZeroUsize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    pub fn max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    pub fn pin_etag(mut self, pin_etag: bool) -> Self {
+        self.pin_etag = pin_etag;
+        self
+    }
+
+    pub async fn write_to<W: AsyncWrite + Unpin>(self, mut writer: W) -> Result<u64, Error> {
+        let chunks = self.chunks()?;
+        // `buffered` keeps the chunks in order while at most `concurrency` chunks
+        // are either in flight or waiting to be written
+        let mut results = futures::stream::iter(chunks)
+            .map(|(offset, length)| self.fetch_chunk(offset, length))
+            .buffered(self.concurrency.get());
+
+        let mut written = 0;
+        while let Some(bytes) = results.try_next().await? {
+            writer.write_all(&bytes).await?;
+            written += bytes.len() as u64;
+        }
+        writer.flush().await?;
+        Ok(written)
+    }
+
+    pub async fn write_to_file<P: AsRef<Path>>(self, path: P) -> Result<u64, Error> {
+        let path = path.as_ref();
+        let temp_file = temp_path(path);
+        let written = match self.write_chunks(&temp_file).await {
+            Ok(written) => written,
+            Err(err) => {
+                let _ = tokio::fs::remove_file(&temp_file).await;
+                return Err(err);
+            }
+        };
+        if let Err(err) = tokio::fs::rename(&temp_file, path).await {
+            let _ = tokio::fs::remove_file(&temp_file).await;
+            return Err(err.into());
+        }
+        Ok(written)
+    }
+
+    async fn write_chunks(&self, path: &Path) -> Result<u64, Error> {
+        let chunks = self.chunks()?;
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(path)
+            .await?;
+        file.set_len(self.object.length.unwrap_or_default()).await?;
+
+        let this = self;
+        let mut results = futures::stream::iter(chunks)
+            .map(|(offset, length)| async move {
+                this.fetch_chunk(offset, length)
+                    .await
+                    .map(|bytes| (offset, bytes))
+            })
+            .buffer_unordered(self.concurrency.get());
+
+        let mut written = 0;
+        while let Some((offset, bytes)) = results.try_next().await? {
+            file.seek(SeekFrom::Start(offset)).await?;
+            file.write_all(&bytes).await?;
+            written += bytes.len() as u64;
+        }
+        file.sync_all().await?;
+        Ok(written)
+    }
+
+    fn chunks(&self) -> Result<Vec<(u64, u64)>, Error> {
+        match self.object.length {
+            // empty objects aren't seekable, but there is nothing to fetch anyway
+            Some(0) => Ok(vec![]),
+            Some(length) if self.object.seekable => Ok(chunks(length, self.chunk_size.get())),
+            _ => Err(Error::NotSeekable(self.object.path.clone())),
+        }
+    }
+
+    async fn fetch_chunk(&self, offset: u64, length: u64) -> Result<Bytes, Error> {
+        let mut attempt = 0;
+        loop {
+            match self.object.fetch_range(offset, length, self.pin_etag).await {
+                Ok(bytes) => return Ok(bytes),
+                Err(err) if attempt < self.max_retries && is_retryable(&err) => {
+                    attempt += 1;
+                    tokio::time::sleep(Duration::from_millis(250 * attempt as u64)).await;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+fn chunks(length: u64, chunk_size: u64) -> Vec<(u64, u64)> {
+    (0..length)
+        .step_by(chunk_size as usize)
+        .map(|offset| (offset, chunk_size.min(length - offset)))
+        .collect()
+}
+
+fn is_retryable(err: &Error) -> bool {
+    match err {
+        Error::ReqwestError(_) | Error::IoError(_) | Error::UnexpectedResponse(_) => true,
+        Error::HttpResponseError(status, _) => *status >= 500,
+        _ => false,
+    }
+}
+
+fn into_async_read(resp: Response) -> impl AsyncRead + Send + Unpin {
+    resp.bytes_stream()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+        .into_async_read()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing::{block_on, Stub};
     use crate::RequestType;
     use futures::io::Cursor;
 
     #[test]
     fn download_req() -> anyhow::Result<()> {
-        let req = download_head_req("/foo/bar", &None);
+        let req = download_head_req("/foo/bar", &None, &Conditions::default());
         assert_eq!(req.path, "./worker/objects/foo/bar");
         assert_eq!(req.request_type, RequestType::Head);
         assert_eq!(req.params, None);
@@ -236,6 +912,7 @@ mod tests {
             "/foo/bar/baz/test.file",
             &Some("testbucket".to_string()),
             None,
+            &Conditions::default(),
         );
         assert_eq!(req.path, "./worker/objects/foo/bar/baz/test.file");
         assert_eq!(req.request_type, RequestType::Get);
@@ -250,12 +927,210 @@ mod tests {
             "/foo/bar/baz/test.file",
             &Some("testbucket".to_string()),
             Some((10203, Some(1234567))),
+            &Conditions::default(),
         );
         assert_eq!(
             req.headers,
-            Some(vec![("range".into(), "bytes=10203-1234566".into())])
+            Some(vec![("range".into(), "bytes=10203-1244769".into())])
         );
 
+        let req = download_get_req(
+            "/foo/bar",
+            &None,
+            Some((0, Some(1000))),
+            &Conditions::default(),
+        );
+        assert_eq!(
+            req.headers,
+            Some(vec![("range".into(), "bytes=0-999".into())])
+        );
+
+        let req = download_get_req(
+            "/foo/bar",
+            &None,
+            Some((1000, None)),
+            &Conditions::default(),
+        );
+        assert_eq!(
+            req.headers,
+            Some(vec![("range".into(), "bytes=1000-".into())])
+        );
+
+        let req = download_get_req("/foo/bar", &None, Some((0, None)), &Conditions::default());
+        assert_eq!(req.headers, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn conditional_req() -> anyhow::Result<()> {
+        let conditions = Conditions::default()
+            .if_none_match("d41d8cd98f00b204e9800998ecf8427e")
+            .if_modified_since(DateTime::parse_from_rfc3339("2024-07-05T14:37:58+02:00")?);
+        let req = download_head_req("/foo/bar", &None, &conditions);
+        assert_eq!(req.request_type, RequestType::Head);
+        assert_eq!(
+            req.headers,
+            Some(vec![
+                (
+                    "if-none-match".into(),
+                    "\"d41d8cd98f00b204e9800998ecf8427e\"".into()
+                ),
+                (
+                    "if-modified-since".into(),
+                    "Fri, 05 Jul 2024 12:37:58 GMT".into()
+                )
+            ])
+        );
+
+        let conditions = Conditions::default().if_match("\"322fc5d8\"");
+        let req = download_get_req("/foo/bar", &None, Some((0, Some(10))), &conditions);
+        assert_eq!(
+            req.headers,
+            Some(vec![
+                ("range".into(), "bytes=0-9".into()),
+                ("if-match".into(), "\"322fc5d8\"".into())
+            ])
+        );
+
+        assert_eq!(quote_etag("*"), "*");
+        assert_eq!(quote_etag("W/\"abc\""), "W/\"abc\"");
+        Ok(())
+    }
+
+    #[test]
+    fn file_helpers() {
+        assert_eq!(
+            guess_content_type(Path::new("/tmp/report.pdf")),
+            Some("application/pdf".to_string())
+        );
+        assert_eq!(
+            guess_content_type(Path::new("index.HTML")),
+            Some("text/html".to_string())
+        );
+        assert_eq!(guess_content_type(Path::new("/tmp/no_extension")), None);
+
+        let temp = temp_path(Path::new("/tmp/foo/bar.zip"));
+        assert_eq!(temp.parent(), Some(Path::new("/tmp/foo")));
+        let temp_name = temp.file_name().unwrap().to_string_lossy();
+        assert!(temp_name.starts_with(".bar.zip."));
+        assert!(temp_name.ends_with(".part"));
+    }
+
+    fn object(url: &str, length: u64, etag: Option<&str>) -> anyhow::Result<DownloadableObject> {
+        Ok(DownloadableObject {
+            path: "/foo".to_string(),
+            bucket: None,
+            length: Some(length),
+            content_type: None,
+            seekable: length > 0,
+            etag: etag.map(|etag| etag.to_string()),
+            last_modified: None,
+            pin_etag: false,
+            inner: Arc::new(ClientInner {
+                api_endpoint_url: url.parse()?,
+                api_password: String::new(),
+                reqwest_client: reqwest::Client::new(),
+            }),
+        })
+    }
+
+    #[test]
+    fn parallel_empty() -> anyhow::Result<()> {
+        let object = object("http://127.0.0.1:9980/api/", 0, None)?;
+        let mut buf = vec![];
+        let written = futures::executor::block_on(object.parallel().write_to(&mut buf))?;
+        assert_eq!(written, 0);
+        assert!(buf.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn parallel_modified() -> anyhow::Result<()> {
+        let stub = Stub::start(|req| {
+            if req.header("if-match") == Some("\"v1\"") {
+                (412, "precondition failed".to_string())
+            } else {
+                (500, "not pinned".to_string())
+            }
+        });
+        let object = object(stub.url(), 8, Some("v1"))?;
+        let dir = std::env::temp_dir().join(format!("renterd-parallel-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let file = dir.join("foo");
+        let res = block_on(
+            object
+                .parallel()
+                .chunk_size(NonZeroU64::new(4).unwrap())
+                .write_to_file(&file),
+        );
+        assert!(matches!(res, Err(Error::ObjectModified(path)) if path == "/foo"));
+        // neither the target nor the temporary file are left behind
+        assert_eq!(std::fs::read_dir(&dir)?.count(), 0);
+        std::fs::remove_dir(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn chunking() {
+        assert_eq!(chunks(0, 10), vec![]);
+        assert_eq!(chunks(5, 10), vec![(0, 5)]);
+        assert_eq!(chunks(10, 10), vec![(0, 10)]);
+        assert_eq!(chunks(25, 10), vec![(0, 10), (10, 10), (20, 5)]);
+    }
+
+    #[test]
+    fn content_range() -> anyhow::Result<()> {
+        let range: ContentRange = "bytes 1000-1999/5000".try_into()?;
+        assert_eq!(
+            range,
+            ContentRange {
+                start: 1000,
+                end: 1999,
+                total: Some(5000)
+            }
+        );
+        assert_eq!(range.length(), 1000);
+
+        let range: ContentRange = "bytes 0-0/*".try_into()?;
+        assert_eq!(range.total, None);
+        assert_eq!(range.length(), 1);
+
+        for invalid in [
+            "bytes 1999-1000/5000",
+            "bytes 1000-5000/5000",
+            "bytes */5000",
+            "items 0-10/20",
+            "bytes 0-10",
+        ] {
+            match ContentRange::try_from(invalid) {
+                Err(InvalidContentRange(_)) => {}
+                _ => panic!("invalid content range error expected for `{}`", invalid),
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn expected_range() -> anyhow::Result<()> {
+        let range = super::expected_range("/foo", 1000, 5000, Some(4000))?;
+        assert_eq!((range.start, range.end, range.length()), (1000, 3999, 3000));
+
+        let range = super::expected_range("/foo", u64::MAX - 9, 10, None)?;
+        assert_eq!(range.end, u64::MAX);
+
+        for (offset, length, total) in [
+            (0, 0, Some(100)),
+            (100, 1, Some(100)),
+            (u64::MAX - 9, 11, None),
+            (u64::MAX, u64::MAX, None),
+        ] {
+            match super::expected_range("/foo", offset, length, total) {
+                Err(Error::InvalidRange(_)) => {}
+                _ => panic!("invalid range error expected for {}+{}", offset, length),
+            }
+        }
         Ok(())
     }
 
@@ -283,6 +1158,7 @@ mod tests {
         let req = upload_req(
             "/foo/bar/file.ext",
             Some("application/funny-bytes".to_string()),
+            &BTreeMap::from([("owner".to_string(), "alice".to_string())]),
             Some("bucket_name".to_string()),
             cursor,
         );
@@ -293,6 +1169,10 @@ mod tests {
             req.params,
             Some(vec![("bucket".into(), "bucket_name".into())])
         );
+        assert_eq!(
+            req.headers,
+            Some(vec![("x-sia-meta-owner".into(), "alice".into())])
+        );
         if let Some(RequestContent::Stream(_stream, content_type)) = req.content {
             assert_eq!(content_type, Some("application/funny-bytes".to_string()));
         } else {